@@ -83,10 +83,20 @@ in {
             default = null;
             description = ''
               Command to set the wallpaper. Use "%f" as a placeholder for the file path.
+              Other placeholders and TIMEWALL_* environment variables are described in the README.
               The command is NOT passed through a shell.
             '';
             example = ["awww" "img" "%f"];
           };
+          backends = lib.mkOption {
+            type = with lib.types; listOf (enum ["builtin" "gnome" "plasma" "sway" "hyprpaper" "awww" "swaybg" "feh"]);
+            default = [];
+            description = ''
+              Wallpaper setting backends to try in order, if no custom command is set.
+              When empty, backends are detected automatically.
+            '';
+            example = ["hyprpaper" "swaybg"];
+          };
           overlap = lib.mkOption {
             type = lib.types.int;
             default = 0;
@@ -101,6 +111,104 @@ in {
             default = true;
             description = "Whether to suppress the setter command output.";
           };
+          long_running = lib.mkOption {
+            type = lib.types.bool;
+            default = false;
+            description = ''
+              Whether the setter command keeps running to display the wallpaper, like `swaybg`.
+              The process is terminated when the next wallpaper is set.
+            '';
+          };
+          timeout = lib.mkOption {
+            type = lib.types.int;
+            default = 10000;
+            description = ''
+              Maximum time in milliseconds to wait for a setter command which is not long-running.
+              The command is killed and the wallpaper update fails if it takes longer.
+            '';
+          };
+        };
+
+        hooks = {
+          on_change = lib.mkOption {
+            type = with lib.types; nullOr (listOf str);
+            default = null;
+            description = "Command to run when the displayed image changes.";
+            example = ["notify-send" "timewall" "Frame %n of %N"];
+          };
+          on_appearance_change = lib.mkOption {
+            type = with lib.types; nullOr (listOf str);
+            default = null;
+            description = "Command to run when the system appearance changes.";
+          };
+          on_wallpaper_switch = lib.mkOption {
+            type = with lib.types; nullOr (listOf str);
+            default = null;
+            description = "Command to run when a different wallpaper file is set.";
+          };
+          quiet = lib.mkOption {
+            type = lib.types.bool;
+            default = true;
+            description = "Whether to suppress the hook commands output.";
+          };
+          timeout = lib.mkOption {
+            type = lib.types.int;
+            default = 10000;
+            description = "Maximum time in milliseconds a hook command can run before it's killed.";
+          };
+        };
+
+        cache = {
+          resize = lib.mkOption {
+            type = with lib.types; nullOr str;
+            default = null;
+            description = ''
+              Resize cached images to the resolution of the largest connected output ("output")
+              or a given size, e.g. "2560x1440".
+            '';
+            example = "output";
+          };
+          resize_mode = lib.mkOption {
+            type = lib.types.enum ["cover" "contain" "center"];
+            default = "cover";
+            description = "How the images are fitted into the target size.";
+          };
+          format = lib.mkOption {
+            type = lib.types.enum ["png" "jpeg" "webp" "ppm"];
+            default = "png";
+            description = "Format of the cached images.";
+          };
+          quality = lib.mkOption {
+            type = lib.types.ints.between 1 100;
+            default = 90;
+            description = "Quality of the cached images in JPEG format.";
+          };
+          compression = lib.mkOption {
+            type = lib.types.ints.between 0 9;
+            default = 6;
+            description = "Compression level of the cached images in PNG format.";
+          };
+          lazy = lib.mkOption {
+            type = lib.types.bool;
+            default = false;
+            description = "Unpack only the images which are needed, instead of all of them upfront.";
+          };
+          max_size_mb = lib.mkOption {
+            type = with lib.types; nullOr ints.unsigned;
+            default = null;
+            description = "Remove least recently used wallpapers when the cache grows larger than this.";
+          };
+        };
+
+        restore = {
+          enable = lib.mkOption {
+            type = lib.types.bool;
+            default = true;
+            description = ''
+              Whether to restore the wallpaper which was set before timewall
+              on `timewall unset` and daemon exit.
+            '';
+          };
         };
 
         daemon = {
@@ -128,12 +236,17 @@ in {
       {
         inherit (cfg.config) daemon;
         inherit (cfg.config) geoclue;
+        inherit (cfg.config) restore;
+        cache = lib.filterAttrs (_: value: value != null) cfg.config.cache;
       }
       // lib.optionalAttrs (cfg.config.location.lat != null && cfg.config.location.lon != null) {
         inherit (cfg.config) location;
       }
-      // lib.optionalAttrs (cfg.config.setter.command != null) {
-        inherit (cfg.config) setter;
+      // lib.optionalAttrs (cfg.config.setter.command != null || cfg.config.setter.backends != []) {
+        setter = lib.filterAttrs (_: value: value != null) cfg.config.setter;
+      }
+      // {
+        hooks = lib.filterAttrs (_: value: value != null) cfg.config.hooks;
       }
     );
 
@@ -1,46 +1,205 @@
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::mpsc::Receiver;
-use std::time::Duration;
-use std::{env, path::Path};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use std::{env, fs, path::Path};
 
 use anyhow::Result;
 use anyhow::{anyhow, bail, Context};
 use chrono::prelude::*;
-use log::debug;
+use libheif_rs::HeifContext;
+use log::{debug, warn};
 
+use crate::animation::{self, AnimationOptions};
 use crate::appearance::{get_system_appearance, Appearance};
-use crate::cache::{CachedCall, CachedCallRetval};
-use crate::config::{Config, Geoclue};
+use crate::cache::{now_unix_seconds, CachedCall, CachedCallRetval};
+use crate::cache_list::CacheListing;
+use crate::clock::PreviewPacing;
+use crate::config::{Config, Geoclue, ResizeTarget, Schedule};
+use crate::doctor::DoctorReport;
 use crate::geo::Coords;
 use crate::geoclue;
 use crate::heif;
-use crate::info::ImageInfo;
-use crate::loader::WallpaperLoader;
-use crate::schedule::{
-    current_image_index_h24, current_image_index_solar, get_image_index_order_appearance,
-    get_image_index_order_h24, get_image_index_order_solar,
+use crate::hooks::{get_hook_events, run_hooks};
+use crate::image::{self, resize::Fit, Encoding, ImageFormat};
+use crate::info::{self, ImageInfo, InfoFormat};
+use crate::lint::{LintReport, Severity};
+use crate::loader::{WallpaperLoader, LAST_USED_RESOLUTION};
+use crate::outputs::connected_outputs;
+use crate::schedule::{current_image_index_h24, current_image_index_solar, get_image_index_order};
+use crate::selection::FrameSelection;
+use crate::setter::{
+    placeholders::Placeholders, restart_setters, restore_previous_wallpaper,
+    save_previous_wallpaper, set_wallpaper, supervisor::Supervisor, unset_wallpaper,
 };
-use crate::setter::{set_wallpaper, unset_wallpaper};
 use crate::signals::{interruptible_sleep, WakeEvent};
-use crate::wallpaper::{self, properties::Properties, Wallpaper};
+use crate::terminal::{Graphics, TerminalDisplay, TerminalImage, TerminalSize};
+use crate::thumbnails;
+use crate::wallpaper::{
+    self, metadata::get_apple_desktop_metadata_from_heif, properties::Properties, Wallpaper,
+};
 use crate::{cache::LastWallpaper, schedule::current_image_index_appearance};
 
-pub fn info<P: AsRef<Path>>(path: P) -> Result<()> {
-    validate_wallpaper_file(&path)?;
-    print!("{}", ImageInfo::from_image(&path)?);
+pub fn info<P: AsRef<Path>>(
+    paths: &[P],
+    format: InfoFormat,
+    show: bool,
+    frame: Option<usize>,
+) -> Result<()> {
+    if let [path] = paths {
+        if !path.as_ref().is_dir() {
+            validate_wallpaper_file(path)?;
+            print!("{}", ImageInfo::from_image(path)?.format(format)?);
+            if show {
+                show_in_terminal(path, frame)?;
+            }
+            return Ok(());
+        }
+    }
+    if show {
+        bail!("only a single wallpaper file can be shown");
+    }
+
+    for path in paths.iter().filter(|path| !path.as_ref().is_dir()) {
+        validate_wallpaper_file(path)?;
+    }
+    let wall_paths = info::find_wallpapers(paths);
+    let mut infos = Vec::with_capacity(wall_paths.len());
+    let mut failed = 0;
+    for result in info::read_all(&wall_paths) {
+        match result {
+            Ok(info) => infos.push(info),
+            Err(err) => {
+                eprintln!("Error: {err:#}");
+                failed += 1;
+            }
+        }
+    }
+
+    print!("{}", info::format_all(&infos, format)?);
+    if failed > 0 {
+        bail!("couldn't read {failed} of {} wallpapers", wall_paths.len());
+    }
+    Ok(())
+}
+
+pub fn lint<P: AsRef<Path>>(paths: &[P]) -> Result<()> {
+    for path in paths.iter().filter(|path| !path.as_ref().is_dir()) {
+        validate_wallpaper_file(path)?;
+    }
+    let wall_paths = info::find_wallpapers(paths);
+    let (mut errors, mut warnings, mut failed) = (0, 0, 0);
+    for wall_path in &wall_paths {
+        match LintReport::from_image(wall_path) {
+            Ok(report) => {
+                print!("{report}");
+                errors += report.count(Severity::Error);
+                warnings += report.count(Severity::Warning);
+            }
+            Err(err) => {
+                eprintln!("Error: {}: {err:#}", wall_path.display());
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "Checked {} wallpapers: {errors} errors, {warnings} warnings",
+        wall_paths.len()
+    );
+    if failed > 0 {
+        bail!("couldn't read {failed} of {} wallpapers", wall_paths.len());
+    }
+    if errors > 0 {
+        bail!("found {errors} errors");
+    }
     Ok(())
 }
 
-pub fn unpack<IP: AsRef<Path>, OP: AsRef<Path>>(source: IP, destination: OP) -> Result<()> {
+pub fn render_preview<IP: AsRef<Path>, OP: AsRef<Path>>(
+    path: IP,
+    output: OP,
+    date: Option<NaiveDate>,
+    selection: &FrameSelection,
+    options: &AnimationOptions,
+) -> Result<()> {
+    let config = Config::find()?;
+    validate_wallpaper_file(&path)?;
+    let heif_ctx = heif::from_file(&path)?;
+    let properties = load_properties(&config, &path, &heif_ctx)?;
+
+    let coords = if matches!(properties, Properties::Solar(_)) {
+        try_get_location(&config)
+            .inspect_err(|err| warn!("{err:#}, images are shown in the schedule order"))
+            .ok()
+    } else {
+        None
+    };
+    let date = match date {
+        Some(date) => date,
+        None => get_now_time()?.date_naive(),
+    };
+    let order = animation::simulate_day(&properties, &config.schedule, date, coords.as_ref())?;
+    let order = selection
+        .select(order.len(), options.repeat, |time| {
+            position_at_time(&properties, &config.schedule, &order, date, time, || {
+                coords.context("location is required to find frames by time")
+            })
+        })?
+        .into_iter()
+        .map(|position| order[position])
+        .collect::<Vec<_>>();
+
+    let frames = animation::animation_frames(&heif::get_rgb_images(&heif_ctx)?, &order, options)?;
+    animation::write_frames(&frames, options.repeat, output)
+}
+
+pub fn unpack<IP: AsRef<Path>, OP: AsRef<Path>>(
+    source: IP,
+    destination: OP,
+    format: ImageFormat,
+) -> Result<()> {
     validate_wallpaper_file(&source)?;
-    wallpaper::unpack(source, destination)
+    let encoding = Encoding {
+        format,
+        ..Encoding::default()
+    };
+    wallpaper::unpack(source, destination, None, encoding)
+}
+
+pub fn thumbnails<IP: AsRef<Path>, OP: AsRef<Path>>(
+    source: IP,
+    output: OP,
+    width: u32,
+    columns: Option<u32>,
+    separate: bool,
+    format: ImageFormat,
+) -> Result<()> {
+    validate_wallpaper_file(&source)?;
+    let output = output.as_ref();
+    let encoding = Encoding {
+        format,
+        ..Encoding::default()
+    };
+    let rendered = thumbnails::render_thumbnails(source, width)?;
+
+    if separate {
+        fs::create_dir_all(output)
+            .with_context(|| format!("couldn't create directory {}", output.display()))?;
+        thumbnails::write_thumbnails(&rendered, output, encoding)
+    } else {
+        let columns = columns.unwrap_or_else(|| thumbnails::default_columns(rendered.len()));
+        let sheet = thumbnails::contact_sheet(&rendered, columns);
+        image::write_image(sheet.view(), encoding, output)
+    }
 }
 
 pub fn set<P: AsRef<Path>>(
     path: Option<&P>,
     daemon: bool,
     user_appearance: Option<Appearance>,
+    at: Option<DateTime<Local>>,
     wake_rx: &Receiver<WakeEvent>,
 ) -> Result<()> {
     if daemon && user_appearance.is_some() {
@@ -48,67 +207,274 @@ pub fn set<P: AsRef<Path>>(
     }
 
     let config = Config::find()?;
+    if config.restore.enable {
+        save_previous_wallpaper(&config.setter);
+    }
 
+    let mut previous_wall_path = LastWallpaper::find()?.get()?;
+    let mut previous_appearance: Option<Appearance> = None;
     let mut previous_image_index: Option<usize> = None;
+    let mut failed_attempts: u32 = 0;
+    let mut supervisor = Supervisor::new();
+    let mut exited_setters: Vec<usize> = Vec::new();
+    let mut wallpaper_loader = PersistentLoader::new();
     loop {
         let wall_path = get_effective_wall_path(path.as_ref())?;
-        let wallpaper = WallpaperLoader::new().load(&wall_path);
-
-        let current_image_index = current_image_index(&wallpaper, &config, user_appearance)?;
-        if previous_image_index == Some(current_image_index) {
-            debug!("current image is the same as the previous one, skipping update");
-        } else {
-            previous_image_index.replace(current_image_index);
+        let loaded = match load_current_image(
+            &config,
+            &mut wallpaper_loader,
+            &wall_path,
+            user_appearance,
+            at,
+        ) {
+            Ok(loaded) => Some(loaded),
+            // In the daemon mode, skip the update and try again later.
+            Err(err) if daemon => {
+                failed_attempts += 1;
+                log::error!("failed to load wallpaper (attempt {failed_attempts}): {err:#}");
+                None
+            }
+            Err(err) => return Err(err),
+        };
 
-            let current_image_path = wallpaper
-                .images
-                .get(current_image_index)
-                .with_context(|| "missing image specified by metadata")?;
+        if let Some((wallpaper, current_image_index)) = loaded {
+            let placeholders =
+                get_placeholders(wallpaper, &wall_path, current_image_index, user_appearance);
+            if previous_image_index == Some(current_image_index) {
+                if exited_setters.is_empty() {
+                    debug!("current image is the same as the previous one, skipping update");
+                } else {
+                    restart_exited_setters(
+                        placeholders,
+                        &config,
+                        &mut supervisor,
+                        &mut exited_setters,
+                    );
+                }
+            } else {
+                match placeholders.and_then(|placeholders| {
+                    debug!("setting wallpaper to {}", placeholders.image.display());
+                    let processes = set_wallpaper(&placeholders, &config.setter)?;
+                    Ok((placeholders, processes))
+                }) {
+                    Ok((placeholders, processes)) => {
+                        supervisor.replace(processes);
+                        exited_setters.clear();
+                        previous_image_index.replace(current_image_index);
+                        failed_attempts = 0;
 
-            debug!("setting wallpaper to {}", current_image_path.display());
-            set_wallpaper(current_image_path, config.setter.as_ref())?;
+                        let hook_events = get_hook_events(
+                            previous_appearance,
+                            previous_wall_path.as_deref(),
+                            &placeholders,
+                        );
+                        let hook_handles = run_hooks(&config.hooks, &hook_events, &placeholders);
+                        previous_appearance = Some(placeholders.appearance);
+                        previous_wall_path = Some(wall_path.clone());
+                        // In the daemon mode hooks just run in the background.
+                        if !daemon {
+                            for hook_handle in hook_handles {
+                                let _ = hook_handle.join();
+                            }
+                        }
+                    }
+                    Err(err) if daemon => {
+                        failed_attempts += 1;
+                        log::error!("failed to set wallpaper (attempt {failed_attempts}): {err:#}");
+                    }
+                    Err(err) => return Err(err),
+                }
 
-            if !daemon {
-                eprintln!("Wallpaper set!");
-                break;
+                if !daemon {
+                    eprintln!("Wallpaper set!");
+                    break;
+                }
             }
+
+            // Unpack the rest of the lazily loaded wallpaper while waiting for the next update.
+            wallpaper_loader.unpack_missing_in_background();
         }
 
-        let update_interval_seconds = config.daemon.update_interval_seconds;
-        debug!("sleeping for {update_interval_seconds} seconds");
-        match interruptible_sleep(Duration::from_secs(update_interval_seconds), wake_rx)? {
-            Some(WakeEvent::Terminated) => {
-                unset_wallpaper()?;
-                break;
+        let should_terminate = wait_for_update(
+            &config,
+            failed_attempts,
+            wake_rx,
+            &mut supervisor,
+            &mut exited_setters,
+        )?;
+        if should_terminate {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Restart setters which exited, setting the image with given placeholders again.
+fn restart_exited_setters(
+    placeholders: Result<Placeholders>,
+    config: &Config,
+    supervisor: &mut Supervisor,
+    exited_setters: &mut Vec<usize>,
+) {
+    debug!("restarting setters which exited: {exited_setters:?}");
+    let restarted = placeholders
+        .and_then(|placeholders| restart_setters(&placeholders, &config.setter, exited_setters));
+    match restarted {
+        Ok(processes) => supervisor.add(processes),
+        Err(err) => log::error!("failed to restart setters: {err:#}"),
+    }
+    exited_setters.clear();
+}
+
+/// Load the wallpaper and determine which of its images should be set at a given time,
+/// or now if no time is given.
+fn load_current_image<'a>(
+    config: &Config,
+    loader: &'a mut PersistentLoader,
+    wall_path: &Path,
+    user_appearance: Option<Appearance>,
+    at: Option<DateTime<Local>>,
+) -> Result<(&'a Wallpaper, usize)> {
+    let wallpaper = loader.load(config, wall_path)?;
+    let image_index = current_image_index(
+        &wallpaper.properties,
+        &config.schedule,
+        user_appearance,
+        at.map_or_else(get_now_time, Ok)?,
+        || try_get_location(config),
+    )?;
+    Ok((wallpaper, image_index))
+}
+
+/// Wait until the wallpaper should be updated in the daemon mode.
+///
+/// Returns `true` if the daemon was terminated in the meantime. The wallpaper is unset then.
+fn wait_for_update(
+    config: &Config,
+    failed_attempts: u32,
+    wake_rx: &Receiver<WakeEvent>,
+    supervisor: &mut Supervisor,
+    exited_setters: &mut Vec<usize>,
+) -> Result<bool> {
+    let update_interval = Duration::from_secs(config.daemon.update_interval_seconds);
+    let sleep_duration = get_retry_delay(failed_attempts, update_interval);
+    debug!("sleeping for {} seconds", sleep_duration.as_secs());
+    let wake_event = sleep_until_wake(sleep_duration, wake_rx, supervisor, exited_setters)?;
+    match wake_event {
+        Some(WakeEvent::Terminated) => {
+            unset_wallpaper()?;
+            if config.restore.enable {
+                restore_previous_wallpaper()?;
             }
-            Some(WakeEvent::ThemeChanged) => {
-                debug!("woke up due to theme change, re-evaluating wallpaper");
+            return Ok(true);
+        }
+        Some(WakeEvent::ThemeChanged) => {
+            debug!("woke up due to theme change, re-evaluating wallpaper");
+        }
+        Some(WakeEvent::ChildExited) => {
+            log::warn!("setter processes exited unexpectedly, restarting them");
+        }
+        None => {}
+    }
+    Ok(false)
+}
+
+/// Sleep until the given duration passes or something requires attention.
+///
+/// Exits of child processes which aren't supervised setters don't interrupt the sleep.
+/// Setters which exited and should be restarted are added to `exited_setters`.
+fn sleep_until_wake(
+    sleep_duration: Duration,
+    wake_rx: &Receiver<WakeEvent>,
+    supervisor: &mut Supervisor,
+    exited_setters: &mut Vec<usize>,
+) -> Result<Option<WakeEvent>> {
+    let sleep_until = Instant::now() + sleep_duration;
+    loop {
+        let remaining = sleep_until.saturating_duration_since(Instant::now());
+        match interruptible_sleep(remaining, wake_rx)? {
+            Some(WakeEvent::ChildExited) => {
+                let newly_exited = supervisor.reap_exited();
+                if newly_exited.is_empty() {
+                    continue;
+                }
+                if supervisor.allow_restart(Instant::now()) {
+                    exited_setters.extend(newly_exited);
+                    return Ok(Some(WakeEvent::ChildExited));
+                }
+                log::error!(
+                    "setter processes keep exiting, not restarting them until the next wallpaper change"
+                );
             }
-            None => {}
+            wake_event => return Ok(wake_event),
         }
     }
+}
 
-    Ok(())
+/// Get placeholders for setting a given image of the wallpaper.
+fn get_placeholders(
+    wallpaper: &Wallpaper,
+    wall_path: &Path,
+    image_index: usize,
+    user_appearance: Option<Appearance>,
+) -> Result<Placeholders> {
+    let image_path = wallpaper.image(image_index)?;
+    let image_order = get_image_index_order(&wallpaper.properties);
+
+    Ok(Placeholders {
+        image: image_path,
+        image_index,
+        frame_number: get_frame_number(&image_order, image_index),
+        frame_count: image_order.len(),
+        schedule_type: wallpaper.properties.schedule_type(),
+        appearance: resolve_appearance(user_appearance),
+        wallpaper: wall_path.to_path_buf(),
+        output: None,
+    })
 }
 
 pub fn unset() -> Result<()> {
-    let did_unset = unset_wallpaper()?;
-    if did_unset {
+    let config = Config::find()?;
+    let did_terminate = unset_wallpaper()?;
+    let did_restore = config.restore.enable && restore_previous_wallpaper()?;
+    if did_terminate || did_restore {
         eprintln!("Wallpaper unset!");
     } else {
-        eprintln!("No setter process found. Can't unset.");
+        eprintln!("No setter process or previous wallpaper found. Can't unset.");
     }
     Ok(())
 }
 
+pub fn doctor() -> Result<()> {
+    let config = Config::find()?;
+    print!("{}", DoctorReport::new(&config.setter));
+    Ok(())
+}
+
+/// Get delay before retrying a failed wallpaper update.
+///
+/// Delay doubles with each consecutive failure, but it's never longer than the update interval.
+/// Without failures, it's just the update interval.
+fn get_retry_delay(failed_attempts: u32, update_interval: Duration) -> Duration {
+    const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+    if failed_attempts == 0 {
+        return update_interval;
+    }
+    INITIAL_RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(failed_attempts.saturating_sub(1)))
+        .min(update_interval)
+}
+
 fn get_effective_wall_path<P: AsRef<Path>>(given_path: Option<P>) -> Result<PathBuf> {
-    let last_wallpaper = LastWallpaper::find();
+    let last_wallpaper = LastWallpaper::find()?;
 
     if let Some(path) = given_path {
         validate_wallpaper_file(&path)?;
         last_wallpaper.save(&path);
         Ok(path.as_ref().to_path_buf())
-    } else if let Some(last_path) = last_wallpaper.get() {
+    } else if let Some(last_path) = last_wallpaper.get()? {
         debug!("last used wallpaper at {}", last_path.display());
         Ok(last_path)
     } else {
@@ -118,51 +484,339 @@ fn get_effective_wall_path<P: AsRef<Path>>(given_path: Option<P>) -> Result<Path
 
 pub fn preview<P: AsRef<Path>>(
     path: P,
-    delay: u64,
+    pacing: PreviewPacing,
+    selection: &FrameSelection,
     repeat: bool,
     wake_rx: &Receiver<WakeEvent>,
 ) -> Result<()> {
     let config = Config::find()?;
     validate_wallpaper_file(&path)?;
-    let wallpaper = WallpaperLoader::new().load(&path);
-    let image_order = match wallpaper.properties {
-        Properties::H24(ref props) => get_image_index_order_h24(&props.time_info),
-        Properties::Solar(ref props) => get_image_index_order_solar(&props.solar_info),
-        Properties::Appearance(ref props) => get_image_index_order_appearance(props),
-    };
+    let wallpaper = load_wallpaper(&config, &path)?;
+    let frame_count = get_image_index_order(&wallpaper.properties).len();
+    if config.restore.enable {
+        save_previous_wallpaper(&config.setter);
+    }
+    // Frames are shown one after another, so unpack all of them right away.
+    let _background_unpack = wallpaper.unpack_missing_in_background();
 
-    let mut should_terminate = false;
-    while !should_terminate {
-        for image_index in &image_order {
-            if should_terminate {
-                break;
-            }
+    let appearance = resolve_appearance(None);
+    play_preview(
+        &wallpaper.properties,
+        &config,
+        pacing,
+        selection,
+        repeat,
+        wake_rx,
+        |image_index, frame_number| {
+            let placeholders = Placeholders {
+                image: wallpaper.image(image_index)?,
+                image_index,
+                frame_number,
+                frame_count,
+                schedule_type: wallpaper.properties.schedule_type(),
+                appearance,
+                wallpaper: path.as_ref().to_path_buf(),
+                output: None,
+            };
+            set_wallpaper(&placeholders, &config.setter)?;
+            Ok(())
+        },
+    )?;
+
+    unset_wallpaper()?;
+    if config.restore.enable {
+        restore_previous_wallpaper()?;
+    }
+    Ok(())
+}
+
+pub fn preview_terminal<P: AsRef<Path>>(
+    path: P,
+    pacing: PreviewPacing,
+    selection: &FrameSelection,
+    repeat: bool,
+    wake_rx: &Receiver<WakeEvent>,
+) -> Result<()> {
+    let config = Config::find()?;
+    validate_wallpaper_file(&path)?;
+    let heif_ctx = heif::from_file(&path)?;
+    let properties = load_properties(&config, &path, &heif_ctx)?;
 
-            let image_path = wallpaper.images.get(*image_index).unwrap();
-            set_wallpaper(image_path, config.setter.as_ref())?;
+    let (graphics, size) = (Graphics::detect(), TerminalSize::query());
+    let terminal_images = heif::get_rgb_images(&heif_ctx)?
+        .iter()
+        .map(|image| TerminalImage::new(image, graphics, size))
+        .collect::<Result<Vec<_>>>()?;
 
-            should_terminate = matches!(
-                interruptible_sleep(Duration::from_millis(delay), wake_rx)?,
-                Some(WakeEvent::Terminated)
-            );
+    let mut display = TerminalDisplay::default();
+    play_preview(
+        &properties,
+        &config,
+        pacing,
+        selection,
+        repeat,
+        wake_rx,
+        |image_index, _| {
+            let terminal_image = terminal_images
+                .get(image_index)
+                .with_context(|| format!("there is no image with index {image_index} in HEIF"))?;
+            display.show(terminal_image)
+        },
+    )
+}
+
+/// Show selected images of the wallpaper one after another with `show`, which receives the image
+/// index and the frame number. Stops after a single pass, unless `repeat` is set, or when
+/// terminated.
+fn play_preview(
+    properties: &Properties,
+    config: &Config,
+    pacing: PreviewPacing,
+    selection: &FrameSelection,
+    repeat: bool,
+    wake_rx: &Receiver<WakeEvent>,
+    mut show: impl FnMut(usize, usize) -> Result<()>,
+) -> Result<()> {
+    let image_order = get_image_index_order(properties);
+    let positions = match pacing {
+        PreviewPacing::Even(_) => selection.select(image_order.len(), repeat, |time| {
+            position_at_time(
+                properties,
+                &config.schedule,
+                &image_order,
+                get_now_time()?.date_naive(),
+                time,
+                || try_get_location(config),
+            )
+        })?,
+        PreviewPacing::Realistic(_) => Vec::new(),
+    };
+    // Realistic preview checks the schedule every virtual minute, so resolve the location once.
+    let coords = match (pacing, properties) {
+        (PreviewPacing::Realistic(_), Properties::Solar(_)) => Some(try_get_location(config)?),
+        _ => None,
+    };
+    let sleep_terminated = |duration| -> Result<bool> {
+        Ok(matches!(
+            interruptible_sleep(duration, wake_rx)?,
+            Some(WakeEvent::Terminated)
+        ))
+    };
+
+    loop {
+        match pacing {
+            PreviewPacing::Even(delay) => {
+                for position in &positions {
+                    show(image_order[*position], position + 1)?;
+                    if sleep_terminated(delay)? {
+                        return Ok(());
+                    }
+                }
+            }
+            PreviewPacing::Realistic(clock) => {
+                let (from, to) = selection.time_window()?;
+                let mut previous_image_index = None;
+                for now in clock.with_window(from, to).ticks() {
+                    let image_index =
+                        current_image_index(properties, &config.schedule, None, now, || {
+                            coords.context("location is required for sun based schedules")
+                        })?;
+                    if previous_image_index != Some(image_index) {
+                        debug!("{now}: showing image {image_index}");
+                        show(image_index, get_frame_number(&image_order, image_index))?;
+                        previous_image_index = Some(image_index);
+                    }
+                    if sleep_terminated(clock.step_duration())? {
+                        return Ok(());
+                    }
+                }
+            }
         }
 
         if !repeat {
-            break;
+            return Ok(());
         }
     }
+}
 
-    unset_wallpaper()?;
+pub fn cache_list() -> Result<()> {
+    print!("{}", CacheListing::new(&WallpaperLoader::new()?));
+    Ok(())
+}
+
+pub fn cache_verify() -> Result<()> {
+    let config = Config::find()?;
+    let mut loader = WallpaperLoader::new()?.with_lazy(config.cache.lazy);
+    print!("{}", loader.verify_cache()?);
     Ok(())
 }
 
-pub fn clear(all: bool) {
-    let mut loader = WallpaperLoader::new();
-    let last_wallpaper = (!all).then(|| LastWallpaper::find().get()).flatten();
-    loader.clear_cache(last_wallpaper);
+pub fn clear(all: bool) -> Result<()> {
+    let mut loader = WallpaperLoader::new()?;
+    let last_wallpaper = LastWallpaper::find()?;
+    let skip_wall_path = if all { None } else { last_wallpaper.get()? };
+    loader.clear_cache(skip_wall_path)?;
     if all {
-        LastWallpaper::find().clear();
+        last_wallpaper.clear();
+    }
+    Ok(())
+}
+
+/// Load the wallpaper through the cache configured by the user.
+fn load_wallpaper<P: AsRef<Path>>(config: &Config, path: P) -> Result<Wallpaper> {
+    let mut loader = new_loader(config, get_cache_fit(config))?;
+    load_with_loader(config, &mut loader, path)
+}
+
+/// Create a wallpaper loader configured by the user, resizing images using a given fit.
+fn new_loader(config: &Config, fit: Option<Fit>) -> Result<WallpaperLoader> {
+    Ok(WallpaperLoader::new()?
+        .with_fit(fit)
+        .with_encoding(config.cache.encoding())
+        .with_lazy(config.cache.lazy)
+        .with_max_size(config.cache.max_size()))
+}
+
+/// Load the wallpaper using a given loader, with the configured schedule override applied.
+fn load_with_loader<P: AsRef<Path>>(
+    config: &Config,
+    loader: &mut WallpaperLoader,
+    path: P,
+) -> Result<Wallpaper> {
+    let mut wallpaper = loader.load(&path)?;
+    wallpaper.properties =
+        apply_schedule_override(config, &path, wallpaper.properties, || loader.hash(&path))?;
+    Ok(wallpaper)
+}
+
+/// Wallpaper loader kept across daemon updates, along with the last loaded wallpaper.
+///
+/// The wallpaper is only loaded again when its file or the cache fit changes, when its cache entry
+/// disappears, or once in `LAST_USED_RESOLUTION` to keep the entry's last use time up to date.
+struct PersistentLoader {
+    loader: Option<(Option<Fit>, WallpaperLoader)>,
+    loaded: Option<LoadedWallpaper>,
+    background_unpack: Option<JoinHandle<()>>,
+}
+
+/// Wallpaper loaded by `PersistentLoader`.
+struct LoadedWallpaper {
+    path: PathBuf,
+    hash: String,
+    loaded_at: u64,
+    wallpaper: Wallpaper,
+}
+
+impl PersistentLoader {
+    const fn new() -> Self {
+        Self {
+            loader: None,
+            loaded: None,
+            background_unpack: None,
+        }
     }
+
+    /// Load the wallpaper through the cache configured by the user, reusing the last loaded
+    /// wallpaper if it's still up to date.
+    fn load(&mut self, config: &Config, path: &Path) -> Result<&Wallpaper> {
+        let fit = get_cache_fit(config);
+        let wallpaper_loader = match &mut self.loader {
+            Some((loader_fit, wallpaper_loader)) if *loader_fit == fit => wallpaper_loader,
+            slot => {
+                self.loaded = None;
+                &mut slot.insert((fit, new_loader(config, fit)?)).1
+            }
+        };
+
+        let hash = wallpaper_loader.hash(path)?;
+        let now = now_unix_seconds();
+        let is_up_to_date = self.loaded.as_ref().is_some_and(|loaded| {
+            loaded.path == path
+                && loaded.hash == hash
+                && now.saturating_sub(loaded.loaded_at) < LAST_USED_RESOLUTION
+                && loaded.wallpaper.is_available()
+        });
+        if !is_up_to_date {
+            self.loaded = None;
+        }
+        let loaded = match &mut self.loaded {
+            Some(loaded) => loaded,
+            loaded => loaded.insert(LoadedWallpaper {
+                path: path.to_path_buf(),
+                hash,
+                loaded_at: now,
+                wallpaper: load_with_loader(config, wallpaper_loader, path)?,
+            }),
+        };
+        Ok(&loaded.wallpaper)
+    }
+
+    /// Extract missing images of the last loaded wallpaper in a background thread, unless they're
+    /// already being extracted.
+    fn unpack_missing_in_background(&mut self) {
+        if self
+            .background_unpack
+            .as_ref()
+            .is_none_or(JoinHandle::is_finished)
+        {
+            self.background_unpack = self
+                .loaded
+                .as_ref()
+                .and_then(|loaded| loaded.wallpaper.unpack_missing_in_background());
+        }
+    }
+}
+
+/// Read wallpaper properties straight from HEIF, with the configured schedule override applied.
+fn load_properties<P: AsRef<Path>>(
+    config: &Config,
+    path: P,
+    heif_ctx: &HeifContext,
+) -> Result<Properties> {
+    let properties =
+        Properties::from_apple_desktop(&get_apple_desktop_metadata_from_heif(heif_ctx)?)?;
+    apply_schedule_override(config, &path, properties, || {
+        WallpaperLoader::new()?.hash(&path)
+    })
+}
+
+/// Apply the schedule override configured for the wallpaper, if there's one.
+fn apply_schedule_override<P: AsRef<Path>>(
+    config: &Config,
+    path: P,
+    properties: Properties,
+    hash: impl FnOnce() -> Result<String>,
+) -> Result<Properties> {
+    let path = path.as_ref();
+    let Some(schedule_override) = config.find_wallpaper_override(path, hash)? else {
+        return Ok(properties);
+    };
+    debug!("applying schedule override to {}", path.display());
+    schedule_override
+        .apply(properties)
+        .with_context(|| format!("invalid schedule override for {}", path.display()))
+}
+
+/// Get the size cached images should be resized to, if resizing is enabled.
+fn get_cache_fit(config: &Config) -> Option<Fit> {
+    let (width, height) = match config.cache.resize? {
+        ResizeTarget::Size(width, height) => (width, height),
+        ResizeTarget::Output => {
+            let largest_output = connected_outputs()
+                .into_iter()
+                .filter_map(|output| output.resolution)
+                .max_by_key(|(width, height)| u64::from(*width) * u64::from(*height));
+            if largest_output.is_none() {
+                debug!("couldn't determine output resolution, images won't be resized");
+            }
+            largest_output?
+        }
+    };
+    Some(Fit {
+        width,
+        height,
+        mode: config.cache.resize_mode,
+    })
 }
 
 fn validate_wallpaper_file<P: AsRef<Path>>(path: P) -> Result<()> {
@@ -176,38 +830,94 @@ fn validate_wallpaper_file<P: AsRef<Path>>(path: P) -> Result<()> {
     heif::validate_file(path)
 }
 
-fn get_now_time() -> DateTime<Local> {
+fn get_now_time() -> Result<DateTime<Local>> {
     match env::var("TIMEWALL_OVERRIDE_TIME") {
-        Err(_) => Local::now(),
-        Result::Ok(time_str) => DateTime::from_str(&time_str).unwrap(),
+        Err(_) => Ok(Local::now()),
+        Result::Ok(time_str) => DateTime::from_str(&time_str)
+            .with_context(|| format!("invalid TIMEWALL_OVERRIDE_TIME '{time_str}'")),
     }
 }
 
+/// Determine which image of the wallpaper should be displayed at a given time, with the schedule
+/// offsets applied. The location is only requested for sun based schedules.
 fn current_image_index(
-    wallpaper: &Wallpaper,
-    config: &Config,
+    properties: &Properties,
+    schedule: &Schedule,
     user_appearance: Option<Appearance>,
+    now: DateTime<Local>,
+    get_location: impl FnOnce() -> Result<Coords>,
 ) -> Result<usize> {
-    let now = get_now_time();
-    match wallpaper.properties {
-        ref any_properties if user_appearance.is_some() => match any_properties.appearance() {
+    match properties {
+        any_properties if user_appearance.is_some() => match any_properties.appearance() {
             Some(appearance_props) => Ok(current_image_index_appearance(
                 appearance_props,
                 user_appearance.unwrap(),
             )),
             None => bail!("wallpaper missing appearance metadata"),
         },
-        Properties::Appearance(ref appearance_props) => {
+        Properties::Appearance(appearance_props) => {
             let appearance = resolve_appearance(user_appearance);
             Ok(current_image_index_appearance(appearance_props, appearance))
         }
-        Properties::H24(ref props) => current_image_index_h24(&props.time_info, now.time()),
-        Properties::Solar(ref props) => {
-            current_image_index_solar(&props.solar_info, &now, &try_get_location(config)?)
+        Properties::H24(props) => {
+            current_image_index_h24(&props.time_info, schedule.shift_time(now).time())
         }
+        Properties::Solar(props) => current_image_index_solar(
+            &schedule.offset_solar_items(&props.solar_info),
+            &now,
+            &get_location()?,
+        ),
     }
 }
 
+/// Get position of the image displayed at a given time of the day in the image order.
+fn position_at_time(
+    properties: &Properties,
+    schedule: &Schedule,
+    order: &[usize],
+    date: NaiveDate,
+    time: NaiveTime,
+    get_location: impl FnOnce() -> Result<Coords>,
+) -> Result<usize> {
+    let datetime = Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .with_context(|| format!("time {time} doesn't exist on {date}"))?;
+    let image_index = current_image_index(properties, schedule, None, datetime, get_location)?;
+    order
+        .iter()
+        .position(|index| *index == image_index)
+        .with_context(|| format!("image displayed at {time} isn't a part of the preview"))
+}
+
+/// Draw the frame with a given number in the terminal, or the current one if no number is given.
+fn show_in_terminal<P: AsRef<Path>>(path: P, frame: Option<usize>) -> Result<()> {
+    let config = Config::find()?;
+    let heif_ctx = heif::from_file(&path)?;
+    let properties = load_properties(&config, &path, &heif_ctx)?;
+    let image_index = match frame {
+        Some(frame) => frame
+            .checked_sub(1)
+            .and_then(|position| get_image_index_order(&properties).get(position).copied())
+            .with_context(|| format!("there is no frame {frame} in the wallpaper"))?,
+        None => current_image_index(&properties, &config.schedule, None, get_now_time()?, || {
+            try_get_location(&config)
+        })?,
+    };
+
+    let image = heif::get_rgb_image(&heif_ctx, image_index)?;
+    let terminal_image = TerminalImage::new(&image, Graphics::detect(), TerminalSize::query())?;
+    TerminalDisplay::default().show(&terminal_image)
+}
+
+/// Get number of the first frame displaying a given image, starting from 1.
+fn get_frame_number(image_order: &[usize], image_index: usize) -> usize {
+    image_order
+        .iter()
+        .position(|index| *index == image_index)
+        .map_or(0, |position| position + 1)
+}
+
 fn resolve_appearance(user_appearance: Option<Appearance>) -> Appearance {
     match user_appearance {
         Some(appearance) => appearance,
@@ -255,12 +965,16 @@ fn try_get_location(config: &Config) -> Result<Coords> {
     };
 
     maybe_location.with_context(|| {
+        let config_location = Config::find_path().map_or_else(
+            |_| "in the configuration directory".to_string(),
+            |path| format!("at {}", path.display()),
+        );
         format!(
             concat!(
                 "Using wallpapers with solar schedule requires your approximate location information. ",
-                "Please enable GeoClue 2 or provide the location manually in the configuration file at {}."
+                "Please enable GeoClue 2 or provide the location manually in the configuration file {}."
             ),
-            Config::find_path().unwrap().display()
+            config_location
         )
     })
 }
@@ -270,7 +984,7 @@ fn try_get_geoclue_location(geoclue_config: &Geoclue) -> Result<Coords> {
     let get_location = || geoclue::get_location(geoclue_timeout);
 
     if geoclue_config.cache_fallback {
-        let cache = CachedCall::find("location");
+        let cache = CachedCall::find("location")?;
         let location = cache.call_with_fallback(get_location)?;
         match location {
             CachedCallRetval::Fresh(value) => Ok(value),
@@ -283,3 +997,29 @@ fn try_get_geoclue_location(geoclue_config: &Geoclue) -> Result<Coords> {
         get_location()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case(0, 300, 300)]
+    #[case(1, 300, 5)]
+    #[case(2, 300, 10)]
+    #[case(4, 300, 40)]
+    #[case(7, 300, 300)]
+    #[case(100, 300, 300)]
+    #[case(1, 2, 2)]
+    fn test_get_retry_delay(
+        #[case] failed_attempts: u32,
+        #[case] update_interval_secs: u64,
+        #[case] expected_secs: u64,
+    ) {
+        assert_eq!(
+            get_retry_delay(failed_attempts, Duration::from_secs(update_interval_secs)),
+            Duration::from_secs(expected_secs)
+        );
+    }
+}
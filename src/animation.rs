@@ -0,0 +1,289 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveDate, NaiveTime, TimeDelta, TimeZone};
+use itertools::Itertools;
+use log::debug;
+
+use crate::{
+    config::Schedule,
+    geo::Coords,
+    image::{resize::scale_to_width, write_animation, write_image, Encoding, RgbImage},
+    schedule::{current_image_index_solar, get_image_index_order},
+    wallpaper::properties::Properties,
+};
+
+/// How long a single crossfade frame is displayed, in milliseconds.
+const CROSSFADE_FRAME_DELAY_MS: u16 = 50;
+/// Interval at which the sun position is checked when simulating a day.
+const SOLAR_SAMPLE_INTERVAL: TimeDelta = TimeDelta::minutes(5);
+
+/// How the rendered animation looks.
+#[derive(Clone, Copy, Debug)]
+pub struct AnimationOptions {
+    /// How long each image is displayed, in milliseconds.
+    pub delay_ms: u64,
+    /// Number of blended frames between consecutive images.
+    pub crossfade: u32,
+    /// Play in a loop, which also crossfades from the last image back to the first one.
+    pub repeat: bool,
+    /// Width to scale the images to, if different from the wallpaper.
+    pub width: Option<u32>,
+}
+
+/// Get indices of images in the order they are displayed during a given day.
+/// Sun based schedules are simulated for a given location, with the altitude offset applied,
+/// so images which are never displayed on that day are skipped. Without the location, or for
+/// other schedules, all images are returned in the schedule order.
+pub fn simulate_day(
+    properties: &Properties,
+    schedule: &Schedule,
+    date: NaiveDate,
+    coords: Option<&Coords>,
+) -> Result<Vec<usize>> {
+    let (Properties::Solar(props), Some(coords)) = (properties, coords) else {
+        return Ok(get_image_index_order(properties));
+    };
+
+    let solar_items = schedule.offset_solar_items(&props.solar_info);
+    let mut order = Vec::new();
+    let mut time = NaiveTime::MIN;
+    loop {
+        // Local times skipped by DST changes don't exist, so there's nothing to display.
+        if let Some(datetime) = Local.from_local_datetime(&date.and_time(time)).earliest() {
+            order.push(current_image_index_solar(&solar_items, &datetime, coords)?);
+        }
+        let (next_time, wrapped_seconds) = time.overflowing_add_signed(SOLAR_SAMPLE_INTERVAL);
+        if wrapped_seconds != 0 {
+            break;
+        }
+        time = next_time;
+    }
+
+    let mut order = order.into_iter().dedup().collect_vec();
+    // The day wraps around, so the night at its end is the same as the one at the beginning.
+    if order.len() > 1 && order.first() == order.last() {
+        order.pop();
+    }
+    debug!("simulated image order for {date}: {order:?}");
+    Ok(order)
+}
+
+/// Build animation frames showing images in a given order, each along with the time it's
+/// displayed for in milliseconds.
+pub fn animation_frames(
+    images: &[RgbImage],
+    order: &[usize],
+    options: &AnimationOptions,
+) -> Result<Vec<(RgbImage, u16)>> {
+    let delay_ms = u16::try_from(options.delay_ms).context("delay is too long for animation")?;
+    let scaled = order
+        .iter()
+        .unique()
+        .map(|&index| {
+            let image = images
+                .get(index)
+                .with_context(|| format!("there is no image with index {index} in HEIF"))?;
+            let image = match options.width {
+                Some(width) => scale_to_width(image, width),
+                None => image.clone(),
+            };
+            Ok((index, image))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    let mut frames = Vec::new();
+    let transitions = if options.repeat {
+        order.len()
+    } else {
+        order.len().saturating_sub(1)
+    };
+    for (position, index) in order.iter().enumerate() {
+        let image = &scaled[index];
+        frames.push((image.clone(), delay_ms));
+        if position < transitions {
+            let next = &scaled[&order[(position + 1) % order.len()]];
+            for step in 1..=options.crossfade {
+                let weight = f64::from(step) / f64::from(options.crossfade + 1);
+                frames.push((blend(image, next, weight), CROSSFADE_FRAME_DELAY_MS));
+            }
+        }
+    }
+    Ok(frames)
+}
+
+/// Write the animation frames. Paths with `.png` or `.apng` extension are written as a single
+/// animated PNG, anything else is treated as a directory for separate frames, named
+/// `frame_0001.png`, `frame_0002.png`... Frame delays are lost in the latter case.
+pub fn write_frames<P: AsRef<Path>>(
+    frames: &[(RgbImage, u16)],
+    repeat: bool,
+    output: P,
+) -> Result<()> {
+    let output = output.as_ref();
+    let encoding = Encoding::default();
+    if is_animation_path(output) {
+        debug!("writing {} frames to {}", frames.len(), output.display());
+        return write_animation(frames, encoding.compression, repeat, output);
+    }
+
+    fs::create_dir_all(output)
+        .with_context(|| format!("couldn't create directory {}", output.display()))?;
+    for (number, (frame, _)) in (1..).zip(frames) {
+        let path = output.join(format!("frame_{number:04}.{}", encoding.format.extension()));
+        debug!("writing frame to {}", path.display());
+        write_image(frame.view(), encoding, path)?;
+    }
+    Ok(())
+}
+
+fn is_animation_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("png") || extension.eq_ignore_ascii_case("apng")
+        })
+}
+
+/// Blend two images of the same size, `weight` being the share of the second one.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    reason = "blended values stay between the source values"
+)]
+fn blend(from: &RgbImage, to: &RgbImage, weight: f64) -> RgbImage {
+    let data = from
+        .data
+        .iter()
+        .zip(&to.data)
+        .map(|(&a, &b)| {
+            f64::from(a)
+                .mul_add(1.0 - weight, f64::from(b) * weight)
+                .round() as u8
+        })
+        .collect();
+    RgbImage {
+        width: from.width,
+        height: from.height,
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+    use crate::wallpaper::properties::{PropertiesH24, PropertiesSolar, SolarItem, TimeItem};
+
+    fn solid(value: u8) -> RgbImage {
+        RgbImage {
+            width: 1,
+            height: 1,
+            data: vec![value; 3],
+        }
+    }
+
+    fn options(crossfade: u32, repeat: bool) -> AnimationOptions {
+        AnimationOptions {
+            delay_ms: 1000,
+            crossfade,
+            repeat,
+            width: None,
+        }
+    }
+
+    #[rstest]
+    fn test_simulate_day_h24() {
+        let properties = Properties::H24(PropertiesH24 {
+            appearance: None,
+            time_info: vec![
+                TimeItem {
+                    index: 0,
+                    time: not_nan!(0.5),
+                },
+                TimeItem {
+                    index: 1,
+                    time: not_nan!(0.0),
+                },
+            ],
+        });
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+
+        assert_eq!(
+            simulate_day(&properties, &Schedule::default(), date, None).unwrap(),
+            vec![1, 0]
+        );
+    }
+
+    #[rstest]
+    #[case::with_location(Some(Coords { lat: 52.0, lon: 21.0 }), vec![0, 1, 2, 3])]
+    // It's winter there, the sun doesn't get high enough for the noon image.
+    #[case::southern_location(Some(Coords { lat: -33.9, lon: 151.2 }), vec![0, 1, 3])]
+    #[case::without_location(None, vec![0, 1, 2, 3])]
+    fn test_simulate_day_solar(#[case] coords: Option<Coords>, #[case] expected: Vec<usize>) {
+        let item = |index, altitude, azimuth| SolarItem {
+            index,
+            altitude: not_nan!(altitude),
+            azimuth: not_nan!(azimuth),
+        };
+        let properties = Properties::Solar(PropertiesSolar {
+            appearance: None,
+            solar_info: vec![
+                item(0, -30.0, 0.0),
+                item(1, 10.0, 90.0),
+                item(2, 60.0, 180.0),
+                item(3, 10.0, 270.0),
+            ],
+        });
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+
+        let mut order =
+            simulate_day(&properties, &Schedule::default(), date, coords.as_ref()).unwrap();
+
+        // Where the simulated day starts depends on the local timezone, only the cycle matters.
+        let first_position = order.iter().position(|index| *index == 0).unwrap();
+        order.rotate_left(first_position);
+        assert_eq!(order, expected);
+    }
+
+    #[rstest]
+    #[case::no_crossfade(0, false, vec![(0, 1000), (255, 1000)])]
+    #[case::crossfade(2, false, vec![(0, 1000), (85, 50), (170, 50), (255, 1000)])]
+    #[case::crossfade_repeat(
+        1,
+        true,
+        vec![(0, 1000), (128, 50), (255, 1000), (128, 50)]
+    )]
+    fn test_animation_frames(
+        #[case] crossfade: u32,
+        #[case] repeat: bool,
+        #[case] expected: Vec<(u8, u16)>,
+    ) {
+        let images = [solid(0), solid(255)];
+
+        let frames = animation_frames(&images, &[0, 1], &options(crossfade, repeat)).unwrap();
+
+        let frames = frames
+            .iter()
+            .map(|(image, delay)| (image.data[0], *delay))
+            .collect_vec();
+        assert_eq!(frames, expected);
+    }
+
+    #[rstest]
+    fn test_animation_frames_missing_image() {
+        let images = [solid(0)];
+
+        assert!(animation_frames(&images, &[0, 1], &options(0, false)).is_err());
+    }
+
+    #[rstest]
+    #[case("day.png", true)]
+    #[case("day.APNG", true)]
+    #[case("frames", false)]
+    #[case("day.gif", false)]
+    fn test_is_animation_path(#[case] path: &str, #[case] expected: bool) {
+        assert_eq!(is_animation_path(Path::new(path)), expected);
+    }
+}
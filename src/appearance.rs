@@ -1,17 +1,28 @@
-use std::thread;
+use std::{fmt::Display, thread};
 
 use anyhow::{Context, Result};
 use futures_lite::StreamExt;
 use log::debug;
+use serde::{Deserialize, Serialize};
 use zbus::proxy;
 use zbus::zvariant::OwnedValue;
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Deserialize, Serialize, Copy, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
 pub enum Appearance {
     Light,
     Dark,
 }
 
+impl Display for Appearance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Light => write!(f, "light"),
+            Self::Dark => write!(f, "dark"),
+        }
+    }
+}
+
 const PORTAL_DESTINATION: &str = "org.freedesktop.portal.Desktop";
 const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
 const COLOR_SCHEME_KEY: &str = "color-scheme";
@@ -1,15 +1,20 @@
 use std::{
-    collections::HashSet,
-    env, fs,
+    collections::{HashMap, HashSet},
+    env, fs, iter,
+    os::unix::fs::MetadataExt,
     path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{Context, Result};
 use directories::ProjectDirs;
 use log::debug;
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::constants::{APP_NAME, APP_QUALIFIER};
+use crate::image::{resize::Fit, Encoding};
+use crate::setter::backend::Backend;
 
 /// Abstraction over a cache directory. Manges multiple cache subdirectories accessed by
 /// a string key.
@@ -24,61 +29,291 @@ impl Cache {
     ///
     /// E.g. `Cache::find("wallpapers")` will create 'wallpapers' directory in timewall
     /// directory in user's main cache directory.
-    pub fn find(name: &str) -> Self {
-        Self::in_dir(get_cache_dir().join(name))
+    pub fn find(name: &str) -> Result<Self> {
+        Self::in_dir(get_cache_dir()?.join(name))
     }
 
     /// Load cache from a given directory. Create it if it doesn't exist.
-    fn in_dir<P: AsRef<Path>>(path: P) -> Self {
+    fn in_dir<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
 
         if !path.exists() {
-            fs::create_dir_all(path).expect("couldn't create cache directory");
+            fs::create_dir_all(path).context("couldn't create cache directory")?;
         }
         let entry_dirs = path
             .read_dir()
-            .unwrap()
+            .with_context(|| format!("couldn't read cache directory {}", path.display()))?
             .flatten()
-            .filter(|e| e.file_type().unwrap().is_dir())
+            .filter(|e| e.file_type().is_ok_and(|file_type| file_type.is_dir()))
             .flat_map(|e| e.file_name().into_string())
+            .filter(|name| !name.starts_with('.'))
             .collect();
 
-        Self {
+        Ok(Self {
             base_dir: path.to_path_buf(),
             entries: entry_dirs,
-        }
+        })
     }
 
-    /// Get path to the dir for a given key. Create the dir if it doesn't exist.
-    pub fn entry(&mut self, key: &String) -> PathBuf {
-        if self.entries.contains(key) {
-            self.get_entry(key)
-        } else {
-            self.add_entry(key)
+    /// Create or replace the cache dir for a given key, filling it using a given function.
+    ///
+    /// The dir is filled in a temporary location and moved into place only if the function
+    /// succeeds, so an interrupted fill never leaves a partial entry behind. An existing entry is
+    /// moved aside and removed only after the new one is in place.
+    pub fn fill_entry<F>(&mut self, key: &str, fill: F) -> Result<PathBuf>
+    where
+        F: FnOnce(&Path) -> Result<()>,
+    {
+        let temp_path = self.base_dir.join(format!(".{key}.{}.tmp", process::id()));
+        if temp_path.exists() {
+            fs::remove_dir_all(&temp_path)
+                .context("couldn't remove temporary cache entry directory")?;
+        }
+        fs::create_dir(&temp_path).context("couldn't create temporary cache entry directory")?;
+        if let Err(err) = fill(&temp_path) {
+            fs::remove_dir_all(&temp_path).ok();
+            return Err(err);
+        }
+
+        // Move the old entry aside instead of removing it first, so the entry never disappears.
+        let entry_path = self.get_entry(key);
+        let old_path = self
+            .base_dir
+            .join(format!(".{key}.old.{}.tmp", process::id()));
+        let has_old_entry = entry_path.is_dir();
+        if has_old_entry {
+            if let Err(err) = fs::rename(&entry_path, &old_path) {
+                fs::remove_dir_all(&temp_path).ok();
+                return Err(err).context("couldn't move cache entry directory aside");
+            }
+        }
+        if let Err(err) = fs::rename(&temp_path, &entry_path) {
+            if has_old_entry {
+                fs::rename(&old_path, &entry_path).ok();
+            }
+            fs::remove_dir_all(&temp_path).ok();
+            return Err(err).context("couldn't move cache entry into place");
+        }
+        if has_old_entry {
+            fs::remove_dir_all(&old_path).context("couldn't remove old cache entry directory")?;
         }
+        self.entries.insert(key.to_owned());
+        Ok(entry_path)
     }
 
-    /// Remove the cache dir for a given key.
-    pub fn remove_entry(&mut self, key: &str) {
+    /// Remove the cache dir for a given key, along with its manifest.
+    pub fn remove_entry(&mut self, key: &str) -> Result<()> {
         let entry_path = self.get_entry(key);
         if entry_path.is_dir() {
-            fs::remove_dir_all(entry_path).expect("couldn't remove cache entry directory");
+            fs::remove_dir_all(entry_path).context("couldn't remove cache entry directory")?;
         }
+        fs::remove_file(self.get_manifest_path(key)).ok();
         self.entries.remove(key);
+        Ok(())
     }
 
-    /// Create cache dir for a given key. Panics if the dir already exists.
-    fn add_entry(&mut self, key: &str) -> PathBuf {
-        let entry_path = self.base_dir.join(key);
-        fs::create_dir(&entry_path).expect("couldn't create cache entry directory");
-        self.entries.insert(key.to_owned());
-        entry_path
+    /// Get manifest of the entry with a given key, if it has one.
+    pub fn manifest(&self, key: &str) -> Option<EntryManifest> {
+        let manifest_str = fs::read_to_string(self.get_manifest_path(key)).ok()?;
+        serde_json::from_str(&manifest_str).ok()
+    }
+
+    /// Save manifest of the entry with a given key.
+    pub fn save_manifest(&self, key: &str, manifest: &EntryManifest) -> Result<()> {
+        let manifest_str =
+            serde_json::to_string(manifest).context("failed to serialize cache manifest")?;
+        fs::write(self.get_manifest_path(key), manifest_str)
+            .context("failed to write cache manifest")
+    }
+
+    /// Get total size of files in the entry with a given key, in bytes.
+    pub fn entry_size(&self, key: &str) -> u64 {
+        self.get_entry(key)
+            .read_dir()
+            .map(|dir| {
+                dir.flatten()
+                    .filter_map(|entry| entry.metadata().ok())
+                    .filter(fs::Metadata::is_file)
+                    .map(|metadata| metadata.len())
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Get time the entry with a given key was last modified, in seconds since the Unix epoch.
+    pub fn entry_modified(&self, key: &str) -> u64 {
+        fs::metadata(self.get_entry(key))
+            .and_then(|metadata| metadata.modified())
+            .map_or(0, unix_seconds)
+    }
+
+    /// Remove temporary files and dirs left behind by processes which were interrupted while
+    /// writing to the cache. Returns the number of removed files.
+    pub fn remove_stale_temp_files(&self) -> usize {
+        let entry_dirs = self.entries.iter().map(|key| self.get_entry(key));
+        iter::once(self.base_dir.clone())
+            .chain(entry_dirs)
+            .filter_map(|dir| dir.read_dir().ok())
+            .flat_map(Iterator::flatten)
+            .filter(|entry| is_stale_temp_file(&entry.file_name().to_string_lossy()))
+            .filter(|entry| {
+                let path = entry.path();
+                debug!("removing stale temporary file {}", path.display());
+                if path.is_dir() {
+                    fs::remove_dir_all(path).is_ok()
+                } else {
+                    fs::remove_file(path).is_ok()
+                }
+            })
+            .count()
     }
 
     /// Construct path to cache dir for a given key. Does not check whether the dir exists or not!
-    fn get_entry(&self, key: &str) -> PathBuf {
+    pub fn get_entry(&self, key: &str) -> PathBuf {
         self.base_dir.join(key)
     }
+
+    /// Construct path to the manifest of the entry with a given key. Manifest lays next to the dir.
+    fn get_manifest_path(&self, key: &str) -> PathBuf {
+        self.base_dir.join(format!("{key}.json"))
+    }
+}
+
+/// Metadata describing a cache entry.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct EntryManifest {
+    /// Path to the file the entry was created from.
+    pub source: PathBuf,
+    /// Hash of the source file.
+    pub hash: String,
+    /// Number of frames in the entry.
+    pub frames: usize,
+    /// Size the images were resized to, if any.
+    pub fit: Option<Fit>,
+    /// Encoding the images were written with.
+    pub encoding: Encoding,
+    /// Time the entry was last used, in seconds since the Unix epoch.
+    pub last_used: u64,
+}
+
+/// Get current time in seconds since the Unix epoch.
+pub fn now_unix_seconds() -> u64 {
+    unix_seconds(SystemTime::now())
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Check whether a file is a temporary file of a process which is no longer running.
+fn is_stale_temp_file(file_name: &str) -> bool {
+    get_temp_file_owner(file_name)
+        .is_some_and(|pid| !Path::new("/proc").join(pid.to_string()).exists())
+}
+
+/// Get PID of the process which created a temporary file with a given name.
+/// Temporary files are named `.{name}.{pid}.tmp` or `.{name}.{pid}-{counter}.tmp`.
+fn get_temp_file_owner(file_name: &str) -> Option<u32> {
+    let (_, owner) = file_name
+        .strip_prefix('.')?
+        .strip_suffix(".tmp")?
+        .rsplit_once('.')?;
+    owner.split('-').next()?.parse().ok()
+}
+
+/// Persistent index of file hashes. Allows skipping hashing of files which didn't change
+/// since they were last hashed, judging by their size, modification time and inode.
+#[derive(Debug)]
+pub struct HashIndex {
+    file_path: PathBuf,
+    entries: HashMap<PathBuf, IndexedHash>,
+}
+
+/// Hash of a file along with the file stats it was calculated for.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, Debug)]
+struct IndexedHash {
+    size: u64,
+    modified_seconds: i64,
+    modified_nanos: i64,
+    inode: u64,
+    hash: String,
+}
+
+impl IndexedHash {
+    fn matches(&self, metadata: &fs::Metadata) -> bool {
+        self.size == metadata.size()
+            && self.modified_seconds == metadata.mtime()
+            && self.modified_nanos == metadata.mtime_nsec()
+            && self.inode == metadata.ino()
+    }
+}
+
+impl HashIndex {
+    /// Find user's cache directory and load the index from there.
+    pub fn find() -> Result<Self> {
+        Ok(Self::load(get_cache_dir()?.join("hash_index.json")))
+    }
+
+    /// Load the index from a given file. Missing or malformed file results in an empty index.
+    fn load<P: AsRef<Path>>(file_path: P) -> Self {
+        let file_path = file_path.as_ref();
+        let entries = fs::read_to_string(file_path)
+            .ok()
+            .and_then(|index_str| serde_json::from_str(&index_str).ok())
+            .unwrap_or_default();
+
+        Self {
+            file_path: file_path.to_path_buf(),
+            entries,
+        }
+    }
+
+    /// Get hash of a file at a given path.
+    ///
+    /// If the file changed since it was last hashed, or it was never hashed, the hash is calculated
+    /// using a given function and saved in the index.
+    pub fn get_or_hash<P, F>(&mut self, path: P, hash: F) -> Result<String>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&Path) -> Result<String>,
+    {
+        let path = path.as_ref().canonicalize()?;
+        let metadata = fs::metadata(&path)?;
+        if let Some(indexed) = self.entries.get(&path) {
+            if indexed.matches(&metadata) {
+                return Ok(indexed.hash.clone());
+            }
+        }
+
+        debug!("{} not found in hash index, hashing it", path.display());
+        let file_hash = hash(&path)?;
+        self.entries.insert(
+            path,
+            IndexedHash {
+                size: metadata.size(),
+                modified_seconds: metadata.mtime(),
+                modified_nanos: metadata.mtime_nsec(),
+                inode: metadata.ino(),
+                hash: file_hash.clone(),
+            },
+        );
+        self.entries.retain(|path, _| path.exists());
+        if let Err(err) = self.save() {
+            debug!("couldn't save hash index: {err:#}");
+        }
+        Ok(file_hash)
+    }
+
+    fn save(&self) -> Result<()> {
+        let index_str =
+            serde_json::to_string(&self.entries).context("failed to serialize hash index")?;
+        let temp_path = self
+            .file_path
+            .with_extension(format!("{}.tmp", process::id()));
+        fs::write(&temp_path, index_str).context("failed to write hash index")?;
+        fs::rename(&temp_path, &self.file_path).context("failed to write hash index")
+    }
 }
 
 /// Abstraction over a symlink to the last used wallpaper.
@@ -88,22 +323,18 @@ pub struct LastWallpaper {
 
 impl LastWallpaper {
     /// Find user's cache directory and load instance from there.
-    pub fn find() -> Self {
-        Self::load(get_cache_dir().join("last_wall"))
+    pub fn find() -> Result<Self> {
+        Self::load(get_cache_dir()?.join("last_wall"))
     }
 
     /// Load instance from given link path.
-    fn load<P: AsRef<Path>>(link_path: P) -> Self {
+    fn load<P: AsRef<Path>>(link_path: P) -> Result<Self> {
         let link_path = link_path.as_ref();
-        let parent_dir = link_path.parent().unwrap();
-
-        if !parent_dir.exists() {
-            fs::create_dir_all(parent_dir).expect("couldn't create cache directory");
-        }
+        create_parent_dir(link_path)?;
 
-        Self {
+        Ok(Self {
             link_path: link_path.to_path_buf(),
-        }
+        })
     }
 
     /// Save path to the last wallpaper.
@@ -112,15 +343,19 @@ impl LastWallpaper {
         if fs::read_link(&self.link_path).is_ok() {
             fs::remove_file(&self.link_path).ok();
         }
-        std::os::unix::fs::symlink(path.as_ref().canonicalize().unwrap(), &self.link_path).ok();
+        if let Result::Ok(path) = path.as_ref().canonicalize() {
+            std::os::unix::fs::symlink(path, &self.link_path).ok();
+        }
     }
 
     /// Get path to the last used wallpaper, if it exists.
-    pub fn get(&self) -> Option<PathBuf> {
+    pub fn get(&self) -> Result<Option<PathBuf>> {
         if self.link_path.exists() {
-            Some(fs::read_link(&self.link_path).unwrap())
+            fs::read_link(&self.link_path)
+                .map(Some)
+                .context("couldn't read last wallpaper link")
         } else {
-            None
+            Ok(None)
         }
     }
 
@@ -132,14 +367,90 @@ impl LastWallpaper {
     }
 }
 
-fn get_cache_dir() -> PathBuf {
+/// Desktop wallpaper which was set before timewall, together with the backend which displays it.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
+pub struct SavedWallpaper {
+    pub path: PathBuf,
+    pub backend: Backend,
+}
+
+/// Abstraction over a file storing the desktop wallpaper which was set before timewall.
+pub struct PreviousWallpaper {
+    file_path: PathBuf,
+}
+
+impl PreviousWallpaper {
+    /// Find user's cache directory and load instance from there.
+    pub fn find() -> Result<Self> {
+        Self::load(get_cache_dir()?.join("previous_wall"))
+    }
+
+    /// Load instance from given file path.
+    fn load<P: AsRef<Path>>(file_path: P) -> Result<Self> {
+        let file_path = file_path.as_ref();
+        create_parent_dir(file_path)?;
+
+        Ok(Self {
+            file_path: file_path.to_path_buf(),
+        })
+    }
+
+    /// Save the previous wallpaper and its backend, unless one is already saved.
+    ///
+    /// Images from timewall cache are never saved, they are not the wallpaper to go back to.
+    pub fn save_if_missing<P: AsRef<Path>>(&self, path: P, backend: Backend) -> Result<()> {
+        let path = path.as_ref();
+        if self.file_path.exists() || self.is_in_cache(path) {
+            return Ok(());
+        }
+        let saved = SavedWallpaper {
+            path: path.to_path_buf(),
+            backend,
+        };
+        let saved_str =
+            serde_json::to_string(&saved).context("failed to serialize previous wallpaper")?;
+        fs::write(&self.file_path, saved_str).context("couldn't write previous wallpaper file")
+    }
+
+    /// Check whether a given path is inside the cache directory the previous wallpaper is saved in.
+    /// Both paths are canonicalized first, so symlinks and relative paths can't hide it.
+    fn is_in_cache(&self, path: &Path) -> bool {
+        let canonicalize = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.file_path
+            .parent()
+            .is_some_and(|cache_dir| canonicalize(path).starts_with(canonicalize(cache_dir)))
+    }
+
+    /// Get the previous wallpaper, if it was saved.
+    pub fn get(&self) -> Option<SavedWallpaper> {
+        fs::read_to_string(&self.file_path)
+            .ok()
+            .and_then(|saved_str| serde_json::from_str(&saved_str).ok())
+    }
+
+    /// Forget the previous wallpaper.
+    pub fn clear(&self) {
+        fs::remove_file(&self.file_path).ok();
+    }
+}
+
+fn get_cache_dir() -> Result<PathBuf> {
     if let Result::Ok(path_str) = env::var("TIMEWALL_CACHE_DIR") {
-        PathBuf::from(path_str)
+        Ok(PathBuf::from(path_str))
     } else {
-        match ProjectDirs::from(APP_QUALIFIER, "", APP_NAME) {
-            Some(app_dirs) => app_dirs.cache_dir().to_path_buf(),
-            None => panic!("couldn't determine user's home directory"),
+        ProjectDirs::from(APP_QUALIFIER, "", APP_NAME)
+            .map(|app_dirs| app_dirs.cache_dir().to_path_buf())
+            .context("couldn't determine user's home directory")
+    }
+}
+
+/// Create the directory a given file should be placed in, if it doesn't exist.
+fn create_parent_dir(file_path: &Path) -> Result<()> {
+    match file_path.parent() {
+        Some(parent_dir) if !parent_dir.exists() => {
+            fs::create_dir_all(parent_dir).context("couldn't create cache directory")
         }
+        _ => Ok(()),
     }
 }
 
@@ -161,21 +472,17 @@ pub struct CachedCall {
 }
 
 impl CachedCall {
-    pub fn find(name: &str) -> Self {
-        Self::load(get_cache_dir().join(format!("{name}.json")))
+    pub fn find(name: &str) -> Result<Self> {
+        Self::load(get_cache_dir()?.join(format!("{name}.json")))
     }
 
-    fn load<P: AsRef<Path>>(path: P) -> Self {
+    fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
-        let parent_dir = path.parent().unwrap();
+        create_parent_dir(path)?;
 
-        if !parent_dir.exists() {
-            fs::create_dir_all(parent_dir).expect("couldn't create cache directory");
-        }
-
-        Self {
+        Ok(Self {
             cache_file_path: path.to_path_buf(),
-        }
+        })
     }
 
     /// Call the given function and cache the result in a file.
@@ -233,7 +540,7 @@ mod tests {
     fn test_cache_in_dir_not_exists(tmp_dir: TempDir) {
         let expected_dir = tmp_dir.child("random_dir");
 
-        Cache::in_dir(&expected_dir);
+        Cache::in_dir(&expected_dir).unwrap();
 
         expected_dir.assert(predicate::path::is_dir());
     }
@@ -245,7 +552,7 @@ mod tests {
             tmp_dir.child(entry).create_dir_all().unwrap();
         }
 
-        let cache = Cache::in_dir(&tmp_dir);
+        let cache = Cache::in_dir(&tmp_dir).unwrap();
 
         assert_eq!(cache.entries, expected_entries);
     }
@@ -255,9 +562,12 @@ mod tests {
         let entry_name = String::from("random-entry");
         let expected_dir = tmp_dir.child(&entry_name);
 
-        let mut cache = Cache::in_dir(&tmp_dir);
+        let mut cache = Cache::in_dir(&tmp_dir).unwrap();
 
-        assert_eq!(cache.entry(&entry_name), expected_dir.path());
+        assert_eq!(
+            cache.fill_entry(&entry_name, |_| Ok(())).unwrap(),
+            expected_dir.path()
+        );
         expected_dir.assert(predicate::path::is_dir());
     }
 
@@ -267,12 +577,15 @@ mod tests {
         let expected_dir = tmp_dir.child(&entry_name);
         expected_dir.create_dir_all().unwrap();
 
-        let mut cache = Cache::in_dir(&tmp_dir);
+        let mut cache = Cache::in_dir(&tmp_dir).unwrap();
 
-        assert_eq!(cache.entry(&entry_name), expected_dir.path());
+        assert_eq!(
+            cache.fill_entry(&entry_name, |_| Ok(())).unwrap(),
+            expected_dir.path()
+        );
         expected_dir.assert(predicate::path::is_dir());
 
-        cache.remove_entry(&entry_name);
+        cache.remove_entry(&entry_name).unwrap();
         expected_dir.assert(predicate::path::missing());
     }
 
@@ -282,32 +595,257 @@ mod tests {
         let expected_dir = tmp_dir.child(&entry_name);
         expected_dir.create_dir_all().unwrap();
 
-        let mut cache = Cache::in_dir(&tmp_dir);
+        let mut cache = Cache::in_dir(&tmp_dir).unwrap();
 
         expected_dir.assert(predicate::path::is_dir());
-        cache.remove_entry(&entry_name);
+        cache.remove_entry(&entry_name).unwrap();
         expected_dir.assert(predicate::path::missing());
     }
 
+    #[rstest]
+    fn test_cache_manifest(tmp_dir: TempDir) {
+        let entry_name = String::from("some_entry");
+        let manifest = EntryManifest {
+            source: PathBuf::from("/some/wallpaper.heic"),
+            hash: String::from("a81fb8b5"),
+            frames: 16,
+            fit: None,
+            encoding: Encoding::default(),
+            last_used: 1_700_000_000,
+        };
+        let mut cache = Cache::in_dir(&tmp_dir).unwrap();
+        cache.fill_entry(&entry_name, |_| Ok(())).unwrap();
+
+        assert_eq!(cache.manifest(&entry_name), None);
+        cache.save_manifest(&entry_name, &manifest).unwrap();
+        assert_eq!(cache.manifest(&entry_name), Some(manifest));
+        assert!(!Cache::in_dir(&tmp_dir)
+            .unwrap()
+            .entries
+            .contains("some_entry.json"));
+
+        cache.remove_entry(&entry_name).unwrap();
+        tmp_dir
+            .child("some_entry.json")
+            .assert(predicate::path::missing());
+    }
+
+    #[rstest]
+    fn test_cache_entry_size(tmp_dir: TempDir) {
+        let entry_name = String::from("some_entry");
+        let entry_dir = tmp_dir.child(&entry_name);
+        entry_dir.child("0.png").write_binary(&[0; 100]).unwrap();
+        entry_dir.child("1.png").write_binary(&[0; 50]).unwrap();
+
+        let cache = Cache::in_dir(&tmp_dir).unwrap();
+
+        assert_eq!(cache.entry_size(&entry_name), 150);
+    }
+
+    #[rstest]
+    fn test_cache_fill_entry(tmp_dir: TempDir) {
+        let entry_name = String::from("some_entry");
+        let entry_dir = tmp_dir.child(&entry_name);
+        entry_dir.child("stale.png").touch().unwrap();
+
+        let mut cache = Cache::in_dir(&tmp_dir).unwrap();
+        let entry_path = cache
+            .fill_entry(&entry_name, |dir| {
+                fs::write(dir.join("0.png"), "image")?;
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(entry_path, entry_dir.path());
+        entry_dir.child("0.png").assert("image");
+        entry_dir
+            .child("stale.png")
+            .assert(predicate::path::missing());
+        assert_eq!(
+            Cache::in_dir(&tmp_dir).unwrap().entries,
+            HashSet::from([entry_name])
+        );
+        assert_eq!(tmp_dir.read_dir().unwrap().count(), 1);
+    }
+
+    #[rstest]
+    fn test_cache_fill_entry_failed(tmp_dir: TempDir) {
+        let entry_name = String::from("some_entry");
+
+        let mut cache = Cache::in_dir(&tmp_dir).unwrap();
+        let result = cache.fill_entry(&entry_name, |dir| {
+            fs::write(dir.join("0.png"), "image")?;
+            Err(anyhow!("interrupted"))
+        });
+
+        assert!(result.is_err());
+        assert!(cache.entries.is_empty());
+        assert_eq!(tmp_dir.read_dir().unwrap().count(), 0);
+    }
+
+    #[rstest]
+    fn test_cache_fill_entry_failed_keeps_old_entry(tmp_dir: TempDir) {
+        let entry_name = String::from("some_entry");
+        let entry_dir = tmp_dir.child(&entry_name);
+        entry_dir.child("0.png").write_str("old image").unwrap();
+
+        let mut cache = Cache::in_dir(&tmp_dir).unwrap();
+        let result = cache.fill_entry(&entry_name, |dir| {
+            fs::write(dir.join("0.png"), "image")?;
+            Err(anyhow!("interrupted"))
+        });
+
+        assert!(result.is_err());
+        entry_dir.child("0.png").assert("old image");
+        assert_eq!(tmp_dir.read_dir().unwrap().count(), 1);
+    }
+
+    #[rstest]
+    fn test_cache_remove_stale_temp_files(tmp_dir: TempDir) {
+        let entry_dir = tmp_dir.child("some_entry");
+        entry_dir.create_dir_all().unwrap();
+        let stale_file = entry_dir.child(format!(".0.png.{}-0.tmp", u32::MAX));
+        let own_file = entry_dir.child(format!(".1.png.{}-0.tmp", process::id()));
+        let stale_dir = tmp_dir.child(format!(".other_entry.{}.tmp", u32::MAX));
+        stale_file.touch().unwrap();
+        own_file.touch().unwrap();
+        stale_dir.create_dir_all().unwrap();
+
+        let cache = Cache::in_dir(&tmp_dir).unwrap();
+        assert!(!cache.entries.contains(".other_entry.4294967295.tmp"));
+
+        assert_eq!(cache.remove_stale_temp_files(), 2);
+        stale_file.assert(predicate::path::missing());
+        stale_dir.assert(predicate::path::missing());
+        own_file.assert(predicate::path::exists());
+    }
+
+    #[rstest]
+    #[case(".0.png.1234-5.tmp", Some(1234))]
+    #[case(".a81fb8b5-2560x1440-cover.1234.tmp", Some(1234))]
+    #[case(".a81fb8b5-2560x1440-cover.old.1234.tmp", Some(1234))]
+    #[case("0.png", None)]
+    #[case(".0.png.tmp", None)]
+    #[case("a81fb8b5.1234.tmp", None)]
+    fn test_get_temp_file_owner(#[case] file_name: &str, #[case] expected: Option<u32>) {
+        assert_eq!(get_temp_file_owner(file_name), expected);
+    }
+
     #[rstest]
     fn test_cache_entry_remove_not_exists(tmp_dir: TempDir) {
         let entry_name = String::from("some_entry");
         let expected_dir = tmp_dir.child(&entry_name);
 
-        let mut cache = Cache::in_dir(&tmp_dir);
+        let mut cache = Cache::in_dir(&tmp_dir).unwrap();
 
         expected_dir.assert(predicate::path::missing());
-        cache.remove_entry(&entry_name);
+        cache.remove_entry(&entry_name).unwrap();
         expected_dir.assert(predicate::path::missing());
     }
 
     #[rstest]
-    #[should_panic(expected = "couldn't create cache entry directory")]
     fn test_cache_entry_file_conflict(tmp_dir: TempDir) {
         let entry_name = String::from("some_entry");
         tmp_dir.child(&entry_name).touch().unwrap();
 
-        Cache::in_dir(&tmp_dir).entry(&entry_name);
+        let result = Cache::in_dir(&tmp_dir)
+            .unwrap()
+            .fill_entry(&entry_name, |_| Ok(()));
+
+        assert!(result.is_err());
+        assert_eq!(tmp_dir.read_dir().unwrap().count(), 1);
+    }
+
+    #[rstest]
+    fn test_hash_index(tmp_dir: TempDir) {
+        let file = tmp_dir.child("wallpaper.heic");
+        let index_file = tmp_dir.child("hash_index.json");
+        file.write_str("first").unwrap();
+        let hash_content = |path: &Path| Ok(fs::read_to_string(path)?);
+        let unexpected_hash = |_: &Path| -> Result<String> { panic!("file shouldn't be hashed") };
+
+        let mut index = HashIndex::load(&index_file);
+        assert_eq!(index.get_or_hash(&file, hash_content).unwrap(), "first");
+        assert_eq!(index.get_or_hash(&file, unexpected_hash).unwrap(), "first");
+
+        let mut index = HashIndex::load(&index_file);
+        assert_eq!(index.get_or_hash(&file, unexpected_hash).unwrap(), "first");
+
+        file.write_str("second").unwrap();
+        assert_eq!(index.get_or_hash(&file, hash_content).unwrap(), "second");
+    }
+
+    #[rstest]
+    fn test_hash_index_malformed(tmp_dir: TempDir) {
+        let file = tmp_dir.child("wallpaper.heic");
+        let index_file = tmp_dir.child("hash_index.json");
+        file.write_str("content").unwrap();
+        index_file.write_str("garbage").unwrap();
+
+        let mut index = HashIndex::load(&index_file);
+        assert_eq!(
+            index
+                .get_or_hash(&file, |_| Ok(String::from("hash")))
+                .unwrap(),
+            "hash"
+        );
+    }
+
+    #[rstest]
+    fn test_previous_wallpaper_save_if_missing(tmp_dir: TempDir) {
+        let file_path = tmp_dir.child("previous_wall");
+        let previous_wallpaper = PreviousWallpaper::load(&file_path).unwrap();
+        assert_eq!(previous_wallpaper.get(), None);
+
+        previous_wallpaper
+            .save_if_missing("/usr/share/backgrounds/first.png", Backend::Gnome)
+            .unwrap();
+        previous_wallpaper
+            .save_if_missing("/usr/share/backgrounds/second.png", Backend::Builtin)
+            .unwrap();
+        assert_eq!(
+            previous_wallpaper.get(),
+            Some(SavedWallpaper {
+                path: PathBuf::from("/usr/share/backgrounds/first.png"),
+                backend: Backend::Gnome,
+            })
+        );
+
+        previous_wallpaper.clear();
+        assert_eq!(previous_wallpaper.get(), None);
+        file_path.assert(predicate::path::missing());
+    }
+
+    #[rstest]
+    #[case::cache_dir_symlink(true, false)]
+    #[case::image_path_symlink(false, true)]
+    fn test_previous_wallpaper_skips_cached_image(
+        tmp_dir: TempDir,
+        #[case] link_cache_dir: bool,
+        #[case] link_image_path: bool,
+    ) {
+        let real_cache_dir = tmp_dir.child("cache");
+        let linked_cache_dir = tmp_dir.child("linked_cache");
+        let image = real_cache_dir.child("wallpapers/a81fb8b5/0.png");
+        image.touch().unwrap();
+        std::os::unix::fs::symlink(&real_cache_dir, &linked_cache_dir).unwrap();
+
+        let cache_dir = if link_cache_dir {
+            &linked_cache_dir
+        } else {
+            &real_cache_dir
+        };
+        let image_path = if link_image_path {
+            linked_cache_dir.join("wallpapers/a81fb8b5/0.png")
+        } else {
+            image.to_path_buf()
+        };
+        let previous_wallpaper = PreviousWallpaper::load(cache_dir.child("previous_wall")).unwrap();
+        previous_wallpaper
+            .save_if_missing(image_path, Backend::Gnome)
+            .unwrap();
+
+        assert_eq!(previous_wallpaper.get(), None);
     }
 
     #[rstest]
@@ -315,7 +853,7 @@ mod tests {
         let fake_cache_dir = tmp_dir.child("cache_dir");
         let link_path = fake_cache_dir.child("test_link");
 
-        LastWallpaper::load(&link_path);
+        LastWallpaper::load(&link_path).unwrap();
 
         fake_cache_dir.assert(predicate::path::exists());
     }
@@ -328,16 +866,16 @@ mod tests {
         target_path_1.touch().unwrap();
         target_path_2.touch().unwrap();
 
-        let last_wall = LastWallpaper::load(&link_path);
+        let last_wall = LastWallpaper::load(&link_path).unwrap();
         link_path.assert(predicate::path::missing());
-        assert_eq!(last_wall.get(), None);
+        assert_eq!(last_wall.get().unwrap(), None);
 
         last_wall.save(&target_path_1);
-        assert_eq!(last_wall.get(), Some(target_path_1.to_path_buf()));
+        assert_eq!(last_wall.get().unwrap(), Some(target_path_1.to_path_buf()));
 
         fs::remove_file(target_path_1).unwrap();
         last_wall.save(&target_path_2);
-        assert_eq!(last_wall.get(), Some(target_path_2.to_path_buf()));
+        assert_eq!(last_wall.get().unwrap(), Some(target_path_2.to_path_buf()));
     }
 
     #[rstest]
@@ -346,7 +884,7 @@ mod tests {
         let link_path = tmp_dir.child("test_link");
         target_path.touch().unwrap();
 
-        let last_wall = LastWallpaper::load(&link_path);
+        let last_wall = LastWallpaper::load(&link_path).unwrap();
 
         last_wall.save(&target_path);
         link_path.assert(predicate::path::exists());
@@ -371,7 +909,7 @@ mod tests {
     #[rstest]
     fn test_cached_call_ok(tmp_dir: TempDir) -> Result<()> {
         let cache_file = tmp_dir.child("cached_call_test");
-        let cached_call = CachedCall::load(&cache_file);
+        let cached_call = CachedCall::load(&cache_file).unwrap();
 
         let result = cached_call.call_with_fallback(|| Ok(TEST_STRUCT_1))?;
         assert_eq!(result, CachedCallRetval::Fresh(TEST_STRUCT_1));
@@ -385,7 +923,7 @@ mod tests {
     #[rstest]
     fn test_cached_call_err(tmp_dir: TempDir) {
         let cache_file = tmp_dir.child("cached_call_test");
-        let cached_call = CachedCall::load(&cache_file);
+        let cached_call = CachedCall::load(&cache_file).unwrap();
 
         let result = cached_call.call_with_fallback(test_fun_err);
         assert!(result.is_err());
@@ -394,7 +932,7 @@ mod tests {
     #[rstest]
     fn test_cached_call_fallback(tmp_dir: TempDir) -> Result<()> {
         let cache_file = tmp_dir.child("cached_call_test");
-        let cached_call = CachedCall::load(&cache_file);
+        let cached_call = CachedCall::load(&cache_file).unwrap();
 
         let result = cached_call.call_with_fallback(|| Ok(TEST_STRUCT_1))?;
         assert_eq!(result, CachedCallRetval::Fresh(TEST_STRUCT_1));
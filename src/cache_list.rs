@@ -0,0 +1,127 @@
+use std::fmt::Display;
+
+use chrono::{Local, TimeZone};
+
+use crate::loader::{CacheVerification, CachedWallpaper, EntryState, WallpaperLoader};
+
+const BYTES_IN_MB: f64 = 1024.0 * 1024.0;
+
+/// Listing of the wallpapers in the loader cache.
+#[derive(Debug)]
+pub struct CacheListing {
+    cached_wallpapers: Vec<CachedWallpaper>,
+}
+
+impl CacheListing {
+    pub fn new(loader: &WallpaperLoader) -> Self {
+        Self {
+            cached_wallpapers: loader.cached_wallpapers(),
+        }
+    }
+}
+
+impl Display for CacheListing {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.cached_wallpapers.is_empty() {
+            return writeln!(f, "Cache is empty.");
+        }
+
+        let key_width = self
+            .cached_wallpapers
+            .iter()
+            .map(|cached| cached.key.len())
+            .max()
+            .unwrap_or(0);
+        writeln!(
+            f,
+            "{:<key_width$}  Frames  {:>10}  {:<16}  Source",
+            "Hash", "Size", "Last used"
+        )?;
+        for cached in &self.cached_wallpapers {
+            let (frames, source) = cached.manifest.as_ref().map_or_else(
+                || ("-".to_owned(), "unknown".to_owned()),
+                |manifest| {
+                    (
+                        manifest.frames.to_string(),
+                        manifest.source.display().to_string(),
+                    )
+                },
+            );
+            writeln!(
+                f,
+                "{:<key_width$}  {frames:>6}  {:>10}  {:<16}  {source}",
+                cached.key,
+                format_size(cached.size),
+                format_time(cached.last_used),
+            )?;
+        }
+
+        let total_size = self
+            .cached_wallpapers
+            .iter()
+            .map(|cached| cached.size)
+            .sum();
+        writeln!(
+            f,
+            "Total: {} in {} entries",
+            format_size(total_size),
+            self.cached_wallpapers.len()
+        )
+    }
+}
+
+impl Display for CacheVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.entries.is_empty() {
+            writeln!(f, "Cache is empty.")?;
+        }
+        for (key, state) in &self.entries {
+            match state {
+                EntryState::Intact => writeln!(f, "{key}: ok")?,
+                EntryState::Repaired(problem) => writeln!(f, "{key}: repaired ({problem})")?,
+                EntryState::Removed(problem) => writeln!(f, "{key}: removed ({problem})")?,
+            }
+        }
+        if !self.entries.is_empty() {
+            let count = |predicate: fn(&EntryState) -> bool| {
+                self.entries
+                    .iter()
+                    .filter(|(_, state)| predicate(state))
+                    .count()
+            };
+            writeln!(
+                f,
+                "Verified {} entries: {} repaired, {} removed",
+                self.entries.len(),
+                count(|state| matches!(state, EntryState::Repaired(_))),
+                count(|state| matches!(state, EntryState::Removed(_))),
+            )?;
+        }
+        if self.removed_temp_files > 0 {
+            writeln!(
+                f,
+                "Removed {} stale temporary files",
+                self.removed_temp_files
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[allow(
+    clippy::cast_precision_loss,
+    reason = "sizes are displayed with limited precision anyway"
+)]
+fn format_size(size: u64) -> String {
+    format!("{:.1} MB", size as f64 / BYTES_IN_MB)
+}
+
+fn format_time(unix_seconds: u64) -> String {
+    i64::try_from(unix_seconds)
+        .ok()
+        .and_then(|seconds| Local.timestamp_opt(seconds, 0).single())
+        .map_or_else(
+            || "unknown".to_owned(),
+            |time| time.format("%Y-%m-%d %H:%M").to_string(),
+        )
+}
@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
 use clap::{Parser, Subcommand, ValueEnum};
 
 /// All-in-one tool for Apple dynamic HEIF wallpapers on GNU/Linux.
@@ -19,10 +20,21 @@ pub struct Args {
 pub enum Action {
     /// Print out wallpaper info
     Info {
-        /// Path to HEIF wallpaper file
-        file: PathBuf,
+        /// Paths to HEIF wallpaper files or directories containing them
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = CliInfoFormat::Text)]
+        format: CliInfoFormat,
+        /// Draw the current frame in the terminal
+        #[arg(short, long, action, conflicts_with = "format")]
+        show: bool,
+        /// Draw the frame with a given number instead of the current one
+        #[arg(long, requires = "show")]
+        frame: Option<usize>,
     },
     /// Quickly cycle through all images in the wallpaper
+    #[command(group = clap::ArgGroup::new("date_target").args(["output", "realistic"]).multiple(true))]
     Preview {
         /// Path to HEIF wallpaper file
         file: PathBuf,
@@ -32,6 +44,50 @@ pub enum Action {
         /// Repeat the preview in a loop until killed
         #[arg(short, long, action)]
         repeat: bool,
+        /// Draw the images in the terminal instead of setting the wallpaper
+        #[arg(short, long, action, conflicts_with = "output")]
+        terminal: bool,
+        /// Render the preview to a file instead of setting the wallpaper
+        ///
+        /// Paths ending with .png or .apng are written as animated PNG, anything else is
+        /// a directory for numbered frames.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Number of blended frames between consecutive images in the rendered preview
+        #[arg(long, default_value_t = 0, requires = "output")]
+        crossfade: u32,
+        /// Width of the rendered preview in pixels [default: width of the wallpaper]
+        #[arg(short, long, requires = "output", value_parser = clap::value_parser!(u32).range(1..))]
+        width: Option<u32>,
+        /// Follow the real schedule on an accelerated clock, starting at the midnight of --date
+        ///
+        /// Images change when they would during the day, instead of being shown one after
+        /// another with an equal delay.
+        #[arg(long, action, conflicts_with = "output")]
+        realistic: bool,
+        /// How many times faster than the real one the realistic preview clock runs
+        #[arg(long, default_value = "3600x", value_parser = parse_speed, requires = "realistic")]
+        speed: f64,
+        /// First frame to preview, given as a frame number or a time of the day as HH:MM
+        ///
+        /// If it's after the last frame, the preview wraps over midnight.
+        #[arg(long, value_parser = parse_frame_bound)]
+        from: Option<CliFrameBound>,
+        /// Last frame to preview, given as a frame number or a time of the day as HH:MM
+        #[arg(long, value_parser = parse_frame_bound)]
+        to: Option<CliFrameBound>,
+        /// Comma separated numbers of frames to preview, e.g. 5,6,7
+        #[arg(long, value_delimiter = ',', conflicts_with_all = ["from", "to", "realistic"])]
+        frames: Option<Vec<usize>>,
+        /// Preview the frames in reverse order
+        #[arg(long, action, conflicts_with = "realistic")]
+        reverse: bool,
+        /// Go back and forth between the first and the last frame
+        #[arg(long, action, conflicts_with = "realistic")]
+        bounce: bool,
+        /// Date to simulate the schedule for, as YYYY-MM-DD [default: today]
+        #[arg(long, requires = "date_target")]
+        date: Option<NaiveDate>,
     },
     /// Extract all images and metadata from HEIF wallpaper to a directory
     Unpack {
@@ -39,6 +95,31 @@ pub enum Action {
         file: PathBuf,
         /// Path to output directory
         output: PathBuf,
+        /// Format of the unpacked images
+        #[arg(short, long, value_enum, default_value_t = CliImageFormat::Png)]
+        format: CliImageFormat,
+    },
+    /// Render thumbnails of all frames in the wallpaper
+    ///
+    /// By default, the thumbnails are arranged into a single contact sheet image, in the order
+    /// in which they are displayed during the day.
+    Thumbnails {
+        /// Path to HEIF wallpaper file
+        file: PathBuf,
+        /// Path to output image, or directory if --separate is given
+        output: PathBuf,
+        /// Width of a single thumbnail in pixels
+        #[arg(short, long, default_value_t = 256, value_parser = clap::value_parser!(u32).range(16..))]
+        width: u32,
+        /// Number of thumbnails in a single row of the contact sheet
+        #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+        columns: Option<u32>,
+        /// Write each thumbnail to a separate file in the output directory
+        #[arg(short, long, action)]
+        separate: bool,
+        /// Format of the written images
+        #[arg(short, long, value_enum, default_value_t = CliImageFormat::Png)]
+        format: CliImageFormat,
     },
     /// Set the wallpaper
     Set {
@@ -50,18 +131,97 @@ pub enum Action {
         /// Use light or dark variant
         #[arg(short, long, value_enum)]
         appearance: Option<CliAppearance>,
+        /// Set the image for a given time instead of now
+        ///
+        /// Accepts RFC 3339 timestamps, YYYY-MM-DD HH:MM[:SS] in the local timezone,
+        /// or HH:MM[:SS] for today.
+        #[arg(long, value_parser = parse_datetime, conflicts_with = "daemon")]
+        at: Option<DateTime<Local>>,
     },
     /// Try to unset the wallpaper
     ///
-    /// This will only work if the wallpaper is set using a custom, long-running command.
-    /// In this case, unsetting will terminate the process. Otherwise it will do nothing.
+    /// This will terminate the processes of long-running setter commands, and restore the desktop
+    /// wallpaper which was set before timewall, if it's known.
     Unset,
+    /// Show how the wallpaper setting method is chosen in the current session
+    Doctor,
+    /// Check wallpapers for inconsistent metadata
+    ///
+    /// Exits with non-zero status if any errors are found.
+    Lint {
+        /// Paths to HEIF wallpaper files or directories containing them
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
     /// Clear the wallpaper cache
     Clear {
         /// Clear all - do not skip the currently set wallpaper
         #[arg(short, long, action)]
         all: bool,
     },
+    /// Inspect the wallpaper cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+}
+
+/// Parse date and time given either as RFC 3339, as `YYYY-MM-DD HH:MM[:SS]` in the local
+/// timezone, or as `HH:MM[:SS]` today.
+fn parse_datetime(value: &str) -> Result<DateTime<Local>, String> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.with_timezone(&Local));
+    }
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            ["%H:%M:%S", "%H:%M"]
+                .iter()
+                .find_map(|format| NaiveTime::parse_from_str(value, format).ok())
+                .map(|time| Local::now().date_naive().and_time(time))
+        })
+        .ok_or_else(|| {
+            format!("invalid time '{value}', expected e.g. '2024-06-21 18:30' or '18:30'")
+        })?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| format!("time '{value}' doesn't exist in the local timezone"))
+}
+
+/// Parse frame given either by its number or by time of the day, as `HH:MM[:SS]`.
+fn parse_frame_bound(value: &str) -> Result<CliFrameBound, String> {
+    if let Ok(number) = value.parse::<usize>() {
+        return Ok(CliFrameBound::Number(number));
+    }
+    ["%H:%M:%S", "%H:%M"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(value, format).ok())
+        .map(CliFrameBound::Time)
+        .ok_or_else(|| format!("invalid frame '{value}', expected a number or time, e.g. '18:30'"))
+}
+
+/// Parse clock speed given as a multiplier, with optional `x` suffix, e.g. `3600x`.
+fn parse_speed(value: &str) -> Result<f64, String> {
+    let speed = value
+        .strip_suffix('x')
+        .unwrap_or(value)
+        .parse::<f64>()
+        .map_err(|_| format!("invalid speed '{value}', expected e.g. '3600x'"))?;
+    if speed.is_finite() && speed > 0.0 {
+        Ok(speed)
+    } else {
+        Err(format!("speed must be positive, got '{value}'"))
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheAction {
+    /// List cached wallpapers
+    List,
+    /// Check cached wallpapers and unpack the broken ones again
+    Verify,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
@@ -69,3 +229,100 @@ pub enum CliAppearance {
     Light,
     Dark,
 }
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CliFrameBound {
+    Number(usize),
+    Time(NaiveTime),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+pub enum CliInfoFormat {
+    Text,
+    Json,
+    Toml,
+    Table,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+pub enum CliImageFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Ppm,
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case("2024-06-21 18:30", "2024-06-21 18:30:00")]
+    #[case("2024-06-21 18:30:15", "2024-06-21 18:30:15")]
+    fn test_parse_datetime_local(#[case] value: &str, #[case] expected: &str) {
+        let expected = NaiveDateTime::parse_from_str(expected, "%Y-%m-%d %H:%M:%S").unwrap();
+
+        assert_eq!(parse_datetime(value).unwrap().naive_local(), expected);
+    }
+
+    #[rstest]
+    fn test_parse_datetime_rfc3339() {
+        let expected = DateTime::parse_from_rfc3339("2024-06-21T18:30:00+02:00").unwrap();
+
+        assert_eq!(
+            parse_datetime("2024-06-21T18:30:00+02:00").unwrap(),
+            expected
+        );
+    }
+
+    #[rstest]
+    fn test_parse_datetime_time_only() {
+        let parsed = parse_datetime("18:30").unwrap();
+
+        assert_eq!(parsed.time(), NaiveTime::from_hms_opt(18, 30, 0).unwrap());
+        assert_eq!(parsed.date_naive(), Local::now().date_naive());
+    }
+
+    #[rstest]
+    #[case("tomorrow")]
+    #[case("2024-13-01 12:00")]
+    #[case("25:00")]
+    fn test_parse_datetime_invalid(#[case] value: &str) {
+        assert!(parse_datetime(value).is_err());
+    }
+
+    #[rstest]
+    #[case("5", CliFrameBound::Number(5))]
+    #[case("18:30", CliFrameBound::Time(NaiveTime::from_hms_opt(18, 30, 0).unwrap()))]
+    #[case("06:15:30", CliFrameBound::Time(NaiveTime::from_hms_opt(6, 15, 30).unwrap()))]
+    fn test_parse_frame_bound(#[case] value: &str, #[case] expected: CliFrameBound) {
+        assert_eq!(parse_frame_bound(value).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("dusk")]
+    #[case("-1")]
+    #[case("24:00")]
+    fn test_parse_frame_bound_invalid(#[case] value: &str) {
+        assert!(parse_frame_bound(value).is_err());
+    }
+
+    #[rstest]
+    #[case("3600x", 3600.0)]
+    #[case("60", 60.0)]
+    #[case("0.5x", 0.5)]
+    fn test_parse_speed(#[case] value: &str, #[case] expected: f64) {
+        assert!((parse_speed(value).unwrap() - expected).abs() < f64::EPSILON);
+    }
+
+    #[rstest]
+    #[case("fast")]
+    #[case("0x")]
+    #[case("-2x")]
+    #[case("infx")]
+    fn test_parse_speed_invalid(#[case] value: &str) {
+        assert!(parse_speed(value).is_err());
+    }
+}
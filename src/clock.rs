@@ -0,0 +1,126 @@
+use std::{iter, time::Duration};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeDelta, TimeZone};
+
+/// Time by which the virtual clock advances in a single step.
+const STEP: TimeDelta = TimeDelta::minutes(1);
+const STEPS_PER_DAY: usize = 24 * 60;
+
+/// How the preview paces the images.
+#[derive(Clone, Copy, Debug)]
+pub enum PreviewPacing {
+    /// Images are shown in the schedule order, each for the same time.
+    Even(Duration),
+    /// Images are shown when the schedule says so, on an accelerated clock.
+    Realistic(VirtualClock),
+}
+
+/// Clock running faster than the real one, for previewing how the wallpaper changes during a day.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct VirtualClock {
+    start: DateTime<Local>,
+    steps: usize,
+    speed: f64,
+}
+
+impl VirtualClock {
+    /// Create a clock starting at the midnight of a given day, running `speed` times faster than
+    /// the real one.
+    pub fn starting_on(date: NaiveDate, speed: f64) -> Self {
+        let midnight = date.and_time(NaiveTime::MIN);
+        // Midnight can be skipped by DST change in some timezones.
+        let start = Local
+            .from_local_datetime(&midnight)
+            .earliest()
+            .unwrap_or_else(|| Local.from_utc_datetime(&midnight));
+        Self {
+            start,
+            steps: STEPS_PER_DAY,
+            speed,
+        }
+    }
+
+    /// Limit the clock to a window of the day. The window ends at midnight if no end is given,
+    /// and wraps over midnight if it ends before it starts.
+    pub fn with_window(self, from: Option<NaiveTime>, to: Option<NaiveTime>) -> Self {
+        let from = from.unwrap_or(NaiveTime::MIN);
+        let to = to.unwrap_or(NaiveTime::MIN);
+        let minutes = (to - from)
+            .num_minutes()
+            .rem_euclid(TimeDelta::days(1).num_minutes());
+        let steps = usize::try_from(minutes / STEP.num_minutes()).unwrap_or_default();
+        Self {
+            start: self.start + (from - NaiveTime::MIN),
+            steps: if steps == 0 { STEPS_PER_DAY } else { steps },
+            ..self
+        }
+    }
+
+    /// Times from the clock start until the end of its window, one step apart.
+    pub fn ticks(&self) -> impl Iterator<Item = DateTime<Local>> {
+        iter::successors(Some(self.start), |time| Some(*time + STEP)).take(self.steps)
+    }
+
+    /// Real time between the steps.
+    pub fn step_duration(&self) -> Duration {
+        Duration::from_secs_f64(STEP.as_seconds_f64() / self.speed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    fn test_virtual_clock_ticks() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let clock = VirtualClock::starting_on(date, 60.0);
+
+        let day = clock.ticks().collect::<Vec<_>>();
+
+        assert_eq!(day.len(), 1440);
+        assert_eq!(day[0].naive_local(), date.and_hms_opt(0, 0, 0).unwrap());
+        assert_eq!(day[1].naive_local(), date.and_hms_opt(0, 1, 0).unwrap());
+        assert_eq!(
+            day[1439].naive_local(),
+            date.and_hms_opt(23, 59, 0).unwrap()
+        );
+    }
+
+    #[rstest]
+    #[case::evening(Some("18:00"), Some("20:00"), "18:00", 120)]
+    #[case::over_midnight(Some("23:00"), Some("01:30"), "23:00", 150)]
+    #[case::until_midnight(Some("22:00"), None, "22:00", 120)]
+    #[case::from_midnight(None, Some("06:00"), "00:00", 360)]
+    #[case::whole_day(Some("12:00"), Some("12:00"), "12:00", 1440)]
+    fn test_virtual_clock_window(
+        #[case] from: Option<&str>,
+        #[case] to: Option<&str>,
+        #[case] expected_start: &str,
+        #[case] expected_ticks: usize,
+    ) {
+        let time = |value| NaiveTime::parse_from_str(value, "%H:%M").unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+
+        let clock = VirtualClock::starting_on(date, 60.0).with_window(from.map(time), to.map(time));
+
+        let ticks = clock.ticks().collect::<Vec<_>>();
+        assert_eq!(ticks[0].time(), time(expected_start));
+        assert_eq!(ticks.len(), expected_ticks);
+    }
+
+    #[rstest]
+    #[case(1.0, Duration::from_secs(60))]
+    #[case(60.0, Duration::from_secs(1))]
+    #[case(3600.0, Duration::from_secs_f64(1.0 / 60.0))]
+    fn test_virtual_clock_step_duration(#[case] speed: f64, #[case] expected: Duration) {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+
+        assert_eq!(
+            VirtualClock::starting_on(date, speed).step_duration(),
+            expected
+        );
+    }
+}
@@ -1,14 +1,27 @@
 use std::{
-    env, fs,
+    collections::HashMap,
+    env, fmt, fs,
+    marker::PhantomData,
     path::{Path, PathBuf},
 };
 
 use anyhow::{anyhow, bail, Context, Ok, Result};
+use chrono::{DateTime, Local};
 use directories::ProjectDirs;
-use serde::{Deserialize, Serialize};
+use ordered_float::NotNan;
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::appearance::Appearance;
 use crate::constants::{APP_NAME, APP_QUALIFIER};
 use crate::geo::Coords;
+use crate::image::{resize::FitMode, Encoding, ImageFormat};
+use crate::outputs::parse_resolution;
+use crate::overrides::ScheduleOverride;
+use crate::schedule::time::TimeOffset;
+use crate::setter::backend::Backend;
+use crate::wallpaper::properties::SolarItem;
 
 const CONFIG_FILE_NAME: &str = "config.toml";
 
@@ -28,24 +41,90 @@ const DEFAULT_CONFIG_FILE_CONTENT: &str = "\
 # lon = 17.02
 
 # Uncomment and adjust the following section to use a custom wallpaper setter command.
+# `%f` is replaced with the image path, see the README for other placeholders.
 # The example uses `awww`: https://codeberg.org/LGFae/awww
-# [setter]
+# [[setter]]
 # command = ['awww', 'img', '%f']
 # overlap = 0
 # quiet = true
 
+# Alternatively, choose wallpaper setting backends to try in order.
+# Run `timewall doctor` to see which backends are detected automatically.
+# [[setter]]
+# backends = ['hyprpaper', 'swaybg', 'builtin']
+
+# Multiple setters can be defined, all of them are run on each wallpaper change.
+# Commands can be marked as long-running if they keep running to display the wallpaper,
+# or as not long-running to wait for them to finish.
+# Setters can be limited to a given appearance or to a connected output.
+# [[setter]]
+# command = ['swaybg', '--output', '%o', '--image', '%f']
+# long_running = true
+# appearance = 'dark'
+# output = 'DP-1'
+
+# Commands to run when the displayed image, the appearance or the wallpaper file changes.
+# The same placeholders as in setter commands can be used.
+# [hooks]
+# on_change = ['notify-send', 'timewall', 'Frame %n of %N']
+# on_appearance_change = ['sh', '-c', 'echo %a > /tmp/appearance']
+# on_wallpaper_switch = ['notify-send', 'timewall', 'Switched to %w']
+
+# Restore the wallpaper which was set before timewall on `timewall unset` and daemon exit
+# [restore]
+# enable = true
+
+# Resize cached images to the resolution of the largest connected output, or a given size
+# like '2560x1440'. Mode can be 'cover', 'contain' or 'center'.
+# Format can be 'png', 'jpeg', 'webp' or 'ppm'. Quality from 1 to 100 applies to JPEG,
+# compression level from 0 to 9 applies to PNG.
+# [cache]
+# resize = 'output'
+# resize_mode = 'cover'
+# format = 'png'
+# quality = 90
+# compression = 6
+# Unpack only the images which are needed, instead of all of them upfront
+# lazy = false
+# Remove least recently used wallpapers when the cache grows larger than this
+# max_size_mb = 1024
+
 # Change how often the wallpaper is updated in daemon mode
 # [daemon]
 # update_interval_seconds = 300
+
+# Run time-based wallpapers behind or ahead of the wall clock, and shift sun-based ones
+# to show low sun images earlier in the evening
+# [schedule]
+# time_offset = '-2h'
+# solar_altitude_offset = 3.0
+
+# Fix the schedule of a single wallpaper, given by its path or hash from `timewall cache list`.
+# Frames are numbered in the order they are displayed, as in `timewall info`.
+# [wallpaper.'/path/to/wallpaper.heic']
+# time = [{ image = 0, time = '07:00' }, { image = 1, time = '21:00' }]
+# remap = [{ frame = 2, image = 3 }]
+# drop = [5]
+# shift = '-1h'
 ";
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Setter {
-    pub command: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_command")]
+    pub command: Option<Vec<String>>,
+    #[serde(default)]
+    pub backends: Vec<Backend>,
     #[serde(default = "Setter::overlap_default_value")]
     pub overlap: u64,
     #[serde(default = "Setter::quiet_default_value")]
     pub quiet: bool,
+    /// Whether the command keeps running to display the wallpaper.
+    /// If not given, the command is treated as long-running if it's still running after timeout.
+    pub long_running: Option<bool>,
+    #[serde(default = "Setter::timeout_default_value")]
+    pub timeout: u64,
+    pub appearance: Option<Appearance>,
+    pub output: Option<String>,
 }
 
 impl Setter {
@@ -56,6 +135,25 @@ impl Setter {
     const fn quiet_default_value() -> bool {
         true
     }
+
+    const fn timeout_default_value() -> u64 {
+        10_000
+    }
+}
+
+impl Default for Setter {
+    fn default() -> Self {
+        Self {
+            command: None,
+            backends: Vec::new(),
+            overlap: Self::overlap_default_value(),
+            quiet: Self::quiet_default_value(),
+            long_running: None,
+            timeout: Self::timeout_default_value(),
+            appearance: None,
+            output: None,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Copy, Debug)]
@@ -112,6 +210,186 @@ impl Default for Geoclue {
     }
 }
 
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Hooks {
+    #[serde(default, deserialize_with = "deserialize_command")]
+    pub on_change: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "deserialize_command")]
+    pub on_appearance_change: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "deserialize_command")]
+    pub on_wallpaper_switch: Option<Vec<String>>,
+    #[serde(default = "Hooks::quiet_default_value")]
+    pub quiet: bool,
+    #[serde(default = "Hooks::timeout_default_value")]
+    pub timeout: u64,
+}
+
+impl Hooks {
+    const fn quiet_default_value() -> bool {
+        true
+    }
+
+    const fn timeout_default_value() -> u64 {
+        10_000
+    }
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Self {
+            on_change: None,
+            on_appearance_change: None,
+            on_wallpaper_switch: None,
+            quiet: Self::quiet_default_value(),
+            timeout: Self::timeout_default_value(),
+        }
+    }
+}
+
+/// Size to resize the cached images to.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub enum ResizeTarget {
+    /// Resolution of the largest connected output.
+    Output,
+    /// Explicitly given resolution.
+    Size(u32, u32),
+}
+
+impl TryFrom<String> for ResizeTarget {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        if value == "output" {
+            return Ok(Self::Output);
+        }
+        let (width, height) = parse_resolution(&value)
+            .filter(|(width, height)| *width > 0 && *height > 0)
+            .ok_or_else(|| anyhow!("expected 'output' or size like '2560x1440', got '{value}'"))?;
+        Ok(Self::Size(width, height))
+    }
+}
+
+impl From<ResizeTarget> for String {
+    fn from(value: ResizeTarget) -> Self {
+        match value {
+            ResizeTarget::Output => Self::from("output"),
+            ResizeTarget::Size(width, height) => format!("{width}x{height}"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct Cache {
+    pub resize: Option<ResizeTarget>,
+    #[serde(default)]
+    pub resize_mode: FitMode,
+    #[serde(default)]
+    pub format: ImageFormat,
+    #[serde(
+        default = "Cache::quality_default_value",
+        deserialize_with = "deserialize_in_range::<_, 1, 100>"
+    )]
+    pub quality: u8,
+    #[serde(
+        default = "Cache::compression_default_value",
+        deserialize_with = "deserialize_in_range::<_, 0, 9>"
+    )]
+    pub compression: u8,
+    #[serde(default)]
+    pub lazy: bool,
+    pub max_size_mb: Option<u64>,
+}
+
+impl Cache {
+    const fn quality_default_value() -> u8 {
+        Encoding::DEFAULT_QUALITY
+    }
+
+    const fn compression_default_value() -> u8 {
+        Encoding::DEFAULT_COMPRESSION
+    }
+
+    /// Get maximum size of the cache in bytes, if it's limited.
+    pub fn max_size(&self) -> Option<u64> {
+        self.max_size_mb
+            .map(|size| size.saturating_mul(1024 * 1024))
+    }
+
+    /// Get encoding of the cached images.
+    pub const fn encoding(&self) -> Encoding {
+        Encoding {
+            format: self.format,
+            quality: self.quality,
+            compression: self.compression,
+        }
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self {
+            resize: None,
+            resize_mode: FitMode::default(),
+            format: ImageFormat::default(),
+            quality: Self::quality_default_value(),
+            compression: Self::compression_default_value(),
+            lazy: false,
+            max_size_mb: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct Restore {
+    #[serde(default = "Restore::enable_default_value")]
+    pub enable: bool,
+}
+
+impl Restore {
+    const fn enable_default_value() -> bool {
+        true
+    }
+}
+
+impl Default for Restore {
+    fn default() -> Self {
+        Self {
+            enable: Self::enable_default_value(),
+        }
+    }
+}
+
+/// Offsets applied to all wallpaper schedules.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Schedule {
+    /// Offset of time based schedules. Negative values make them run behind the wall clock.
+    #[serde(default)]
+    pub time_offset: TimeOffset,
+    /// Offset added to sun altitudes of sun based schedules, in degrees. Positive values make
+    /// low sun images appear earlier in the evening and stay later in the morning.
+    #[serde(default)]
+    pub solar_altitude_offset: NotNan<f64>,
+}
+
+impl Schedule {
+    /// Get the time at which time based schedules are evaluated.
+    pub fn shift_time(&self, time: DateTime<Local>) -> DateTime<Local> {
+        time + self.time_offset.0
+    }
+
+    /// Get solar items with the altitude offset applied.
+    pub fn offset_solar_items(&self, solar_items: &[SolarItem]) -> Vec<SolarItem> {
+        solar_items
+            .iter()
+            .map(|item| SolarItem {
+                altitude: item.altitude + self.solar_altitude_offset,
+                ..item.clone()
+            })
+            .collect()
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Default)]
 pub struct Config {
     #[serde(default)]
@@ -119,7 +397,19 @@ pub struct Config {
     #[serde(default)]
     pub geoclue: Geoclue,
     pub location: Option<Coords>,
-    pub setter: Option<Setter>,
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
+    pub setter: Vec<Setter>,
+    #[serde(default)]
+    pub hooks: Hooks,
+    #[serde(default)]
+    pub restore: Restore,
+    #[serde(default)]
+    pub cache: Cache,
+    #[serde(default)]
+    pub schedule: Schedule,
+    /// Schedule overrides of single wallpapers, keyed by their path or hash.
+    #[serde(default)]
+    pub wallpaper: HashMap<String, ScheduleOverride>,
 }
 
 impl Config {
@@ -172,8 +462,349 @@ impl Config {
         Ok(())
     }
 
+    /// Find the schedule override of a wallpaper given either by its path or by its hash.
+    /// The hash is only computed if no override matches the path.
+    pub fn find_wallpaper_override<P, F>(
+        &self,
+        path: P,
+        hash: F,
+    ) -> Result<Option<&ScheduleOverride>>
+    where
+        P: AsRef<Path>,
+        F: FnOnce() -> Result<String>,
+    {
+        if self.wallpaper.is_empty() {
+            return Ok(None);
+        }
+        let path = path.as_ref().canonicalize()?;
+        let by_path = self.wallpaper.iter().find(|(key, _)| {
+            Path::new(key)
+                .canonicalize()
+                .is_ok_and(|key_path| key_path == path)
+        });
+        if let Some((_, schedule_override)) = by_path {
+            return Ok(Some(schedule_override));
+        }
+        Ok(self.wallpaper.get(&hash()?))
+    }
+
     pub fn try_get_location(&self) -> Result<Coords> {
         self.location
             .ok_or_else(|| anyhow!("location not set in the configuration"))
     }
 }
+
+/// Deserialize either a single value or a list of values into a vector.
+/// This allows both `[setter]` table and `[[setter]]` array of tables in the config.
+///
+/// The shape is checked explicitly, rather than with an untagged enum, so that errors from within
+/// the values are shown to the user.
+fn deserialize_one_or_many<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct OneOrManyVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for OneOrManyVisitor<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a table or an array of tables")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> std::result::Result<Vec<T>, A::Error> {
+            T::deserialize(MapAccessDeserializer::new(map)).map(|value| vec![value])
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> std::result::Result<Vec<T>, A::Error> {
+            Vec::deserialize(SeqAccessDeserializer::new(seq))
+        }
+    }
+
+    deserializer.deserialize_any(OneOrManyVisitor(PhantomData))
+}
+
+/// Deserialize a command, failing if it's empty.
+fn deserialize_command<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let command = Vec::<String>::deserialize(deserializer)?;
+    if command.is_empty() {
+        return Err(serde::de::Error::custom("command can't be empty"));
+    }
+    std::result::Result::Ok(Some(command))
+}
+
+/// Deserialize a number, failing if it's not within a given inclusive range.
+fn deserialize_in_range<'de, D, const MIN: u8, const MAX: u8>(
+    deserializer: D,
+) -> std::result::Result<u8, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = u8::deserialize(deserializer)?;
+    if !(MIN..=MAX).contains(&value) {
+        return Err(serde::de::Error::custom(format!(
+            "expected value from {MIN} to {MAX}, got {value}"
+        )));
+    }
+    std::result::Result::Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone};
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    fn test_config_single_setter() {
+        let config: Config = toml::from_str(
+            r"
+            [setter]
+            command = ['feh', '--bg-fill', '%f']
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.setter.len(), 1);
+        assert_eq!(
+            config.setter[0].command,
+            Some(vec!["feh".into(), "--bg-fill".into(), "%f".into()])
+        );
+        assert_eq!(config.setter[0].long_running, None);
+    }
+
+    #[rstest]
+    fn test_config_setter_without_long_running() {
+        // Custom commands used to always be treated as possibly long-running,
+        // so configs like this one don't mark them.
+        let config: Config = toml::from_str(
+            r"
+            [setter]
+            command = ['swaybg', '--mode', 'fill', '--image', '%f']
+            quiet = true
+            overlap = 500
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.setter.len(), 1);
+        assert_eq!(config.setter[0].long_running, None);
+        assert_eq!(config.setter[0].overlap, 500);
+    }
+
+    #[rstest]
+    #[case("[setter]\ncommand = []")]
+    #[case("[[setter]]\ncommand = ['feh', '%f']\n[[setter]]\ncommand = []")]
+    #[case("[hooks]\non_change = []")]
+    fn test_config_empty_command(#[case] config: &str) {
+        let error = toml::from_str::<Config>(config).unwrap_err();
+
+        assert!(
+            error.to_string().contains("command can't be empty"),
+            "{error}"
+        );
+    }
+
+    #[rstest]
+    #[case("[setter]\nbackends = ['bogus']", "unknown variant `bogus`")]
+    #[case("[[setter]]\nbackends = ['bogus']", "unknown variant `bogus`")]
+    #[case("[setter]\noverlap = 'long'", "invalid type: string \"long\"")]
+    #[case("setter = 'feh'", "expected a table or an array of tables")]
+    fn test_config_invalid_setter(#[case] config: &str, #[case] expected_error: &str) {
+        let error = toml::from_str::<Config>(config).unwrap_err();
+
+        assert!(error.to_string().contains(expected_error), "{error}");
+    }
+
+    #[rstest]
+    fn test_config_multiple_setters() {
+        let config: Config = toml::from_str(
+            r"
+            [[setter]]
+            command = ['swaybg', '--image', '%f']
+            long_running = true
+            output = 'DP-1'
+
+            [[setter]]
+            command = ['cp', '%f', '/tmp/lockscreen.png']
+            appearance = 'dark'
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.setter.len(), 2);
+        assert_eq!(config.setter[0].long_running, Some(true));
+        assert_eq!(config.setter[0].output, Some("DP-1".into()));
+        assert_eq!(config.setter[1].appearance, Some(Appearance::Dark));
+    }
+
+    #[rstest]
+    fn test_config_no_setter() {
+        let config: Config = toml::from_str("").unwrap();
+        assert!(config.setter.is_empty());
+    }
+
+    #[rstest]
+    fn test_config_wallpaper_override() {
+        let config: Config = toml::from_str(
+            r"
+            [wallpaper.dcbcd5f96ccdbdd]
+            time = [{ image = 1, time = '06:30' }, { image = 0, time = '18:00' }]
+            drop = [2]
+            shift = '-1h'
+            ",
+        )
+        .unwrap();
+
+        let schedule_override = config
+            .find_wallpaper_override("Cargo.toml", || Ok("dcbcd5f96ccdbdd".to_string()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(schedule_override.time.as_ref().unwrap().len(), 2);
+        assert_eq!(schedule_override.drop, vec![2]);
+        assert_eq!(String::from(schedule_override.shift), "-1h");
+    }
+
+    #[rstest]
+    fn test_config_wallpaper_override_by_path() {
+        let config: Config = toml::from_str(
+            r"
+            [wallpaper.'./Cargo.toml']
+            schedule = 'appearance'
+            ",
+        )
+        .unwrap();
+
+        let schedule_override = config
+            .find_wallpaper_override("Cargo.toml", || panic!("hash shouldn't be needed"))
+            .unwrap();
+        assert!(schedule_override.is_some());
+        assert!(config
+            .find_wallpaper_override("Cargo.lock", || Ok("other".to_string()))
+            .unwrap()
+            .is_none());
+    }
+
+    #[rstest]
+    #[case("time = [{ image = 0, time = 'noon' }]")]
+    #[case("shift = '2 hours'")]
+    #[case("schedule = 'lunar'")]
+    fn test_config_wallpaper_override_invalid(#[case] override_section: &str) {
+        let result = toml::from_str::<Config>(&format!("[wallpaper.abc]\n{override_section}"));
+
+        assert!(result.is_err());
+    }
+
+    #[rstest]
+    fn test_config_schedule() {
+        let config: Config = toml::from_str(
+            r"
+            [schedule]
+            time_offset = '-2h'
+            solar_altitude_offset = 3.0
+            ",
+        )
+        .unwrap();
+        let time = Local.with_ymd_and_hms(2024, 6, 21, 1, 30, 0).unwrap();
+        let item = SolarItem {
+            index: 0,
+            altitude: not_nan!(-5.0),
+            azimuth: not_nan!(270.0),
+        };
+
+        assert_eq!(
+            config.schedule.shift_time(time).naive_local(),
+            NaiveDate::from_ymd_opt(2024, 6, 20)
+                .unwrap()
+                .and_hms_opt(23, 30, 0)
+                .unwrap()
+        );
+        assert_eq!(
+            config.schedule.offset_solar_items(&[item])[0].altitude,
+            not_nan!(-2.0)
+        );
+    }
+
+    #[rstest]
+    fn test_config_schedule_default() {
+        let config: Config = toml::from_str("").unwrap();
+
+        assert_eq!(config.schedule, Schedule::default());
+        assert_eq!(config.schedule.time_offset.0, chrono::TimeDelta::zero());
+    }
+
+    #[rstest]
+    #[case("time_offset = '2 hours'")]
+    #[case("solar_altitude_offset = 'high'")]
+    fn test_config_schedule_invalid(#[case] schedule_section: &str) {
+        let result = toml::from_str::<Config>(&format!("[schedule]\n{schedule_section}"));
+
+        assert!(result.is_err());
+    }
+
+    #[rstest]
+    #[case("resize = 'output'", Some(ResizeTarget::Output), FitMode::Cover)]
+    #[case(
+        "resize = '2560x1440'\nresize_mode = 'contain'",
+        Some(ResizeTarget::Size(2560, 1440)),
+        FitMode::Contain
+    )]
+    #[case("", None, FitMode::Cover)]
+    fn test_config_cache(
+        #[case] cache_section: &str,
+        #[case] expected_resize: Option<ResizeTarget>,
+        #[case] expected_mode: FitMode,
+    ) {
+        let config: Config = toml::from_str(&format!("[cache]\n{cache_section}")).unwrap();
+
+        assert_eq!(config.cache.resize, expected_resize);
+        assert_eq!(config.cache.resize_mode, expected_mode);
+    }
+
+    #[rstest]
+    #[case("resize = 'big'")]
+    #[case("resize = '0x1080'")]
+    fn test_config_cache_invalid_resize(#[case] cache_section: &str) {
+        let result = toml::from_str::<Config>(&format!("[cache]\n{cache_section}"));
+
+        assert!(result.is_err());
+    }
+
+    #[rstest]
+    #[case("", Encoding::default())]
+    #[case(
+        "format = 'jpeg'\nquality = 75",
+        Encoding { format: ImageFormat::Jpeg, quality: 75, compression: 6 }
+    )]
+    #[case(
+        "format = 'png'\ncompression = 0",
+        Encoding { format: ImageFormat::Png, quality: 90, compression: 0 }
+    )]
+    #[case(
+        "format = 'webp'",
+        Encoding { format: ImageFormat::Webp, quality: 90, compression: 6 }
+    )]
+    fn test_config_cache_encoding(#[case] cache_section: &str, #[case] expected: Encoding) {
+        let config: Config = toml::from_str(&format!("[cache]\n{cache_section}")).unwrap();
+
+        assert_eq!(config.cache.encoding(), expected);
+    }
+
+    #[rstest]
+    #[case("format = 'gif'")]
+    #[case("quality = 0")]
+    #[case("quality = 101")]
+    #[case("compression = 10")]
+    fn test_config_cache_invalid_encoding(#[case] cache_section: &str) {
+        let result = toml::from_str::<Config>(&format!("[cache]\n{cache_section}"));
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt::Display;
+
+use itertools::Itertools;
+
+use crate::config::Setter;
+use crate::setter::backend::{detect_backends, Backend, Candidate, Session};
+
+/// Explanation of how the wallpaper setting method is chosen in the current session.
+#[derive(Debug)]
+pub struct DoctorReport<'s> {
+    session: Session,
+    detected: Vec<(Candidate, bool)>,
+    setter_configs: &'s [Setter],
+}
+
+impl<'s> DoctorReport<'s> {
+    pub fn new(setter_configs: &'s [Setter]) -> Self {
+        let session = Session::probe();
+        let detected = detect_backends(&session)
+            .into_iter()
+            .map(|candidate| {
+                let is_available = candidate.backend.is_available();
+                (candidate, is_available)
+            })
+            .collect_vec();
+
+        Self {
+            session,
+            detected,
+            setter_configs,
+        }
+    }
+
+    fn available_detected(&self) -> Vec<Backend> {
+        self.detected
+            .iter()
+            .filter(|(_, is_available)| *is_available)
+            .map(|(candidate, _)| candidate.backend)
+            .collect_vec()
+    }
+
+    fn describe_setter(&self, setter_config: &Setter) -> String {
+        let description = if let Some(ref command) = setter_config.command {
+            format!("Using custom command: {}", command.join(" "))
+        } else if setter_config.backends.is_empty() {
+            format!(
+                "Using detected backends, in order: {}",
+                self.available_detected().iter().join(", ")
+            )
+        } else {
+            format!(
+                "Using configured backends, in order: {}",
+                setter_config.backends.iter().join(", ")
+            )
+        };
+        let filters = [
+            setter_config
+                .appearance
+                .map(|appearance| format!("{appearance} appearance")),
+            setter_config
+                .output
+                .as_ref()
+                .map(|output| format!("output {output}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect_vec();
+
+        if filters.is_empty() {
+            description
+        } else {
+            format!("{description} (only {})", filters.join(", "))
+        }
+    }
+}
+
+impl Display for DoctorReport<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Session:")?;
+        for line in self.session.to_string().lines() {
+            writeln!(f, "  {line}")?;
+        }
+
+        writeln!(f, "Detected backends:")?;
+        for (idx, (candidate, is_available)) in self.detected.iter().enumerate() {
+            let availability = if *is_available { "" } else { ", not installed" };
+            writeln!(
+                f,
+                "  {}. {} ({}{availability})",
+                idx + 1,
+                candidate.backend,
+                candidate.reason,
+            )?;
+        }
+
+        match self.setter_configs {
+            [] => writeln!(f, "{}", self.describe_setter(&Setter::default()))?,
+            [setter_config] => writeln!(f, "{}", self.describe_setter(setter_config))?,
+            setter_configs => {
+                for (idx, setter_config) in setter_configs.iter().enumerate() {
+                    writeln!(
+                        f,
+                        "Setter {}: {}",
+                        idx + 1,
+                        self.describe_setter(setter_config)
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,129 +1,142 @@
 use std::{
     cmp::min,
-    fs::File,
-    io::{BufWriter, Write},
-    path::Path,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::channel,
+    },
 };
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use libheif_rs::{HeifContext, Image};
 use log::debug;
 use threadpool::ThreadPool;
 
 use super::read;
+use crate::image::{self, resize::Fit, Encoding, RgbImage, RgbView};
 
-const CHANNELS: usize = 3;
-
-/// Unpack all images from given HEIF into PNG files in specified directory.
+/// Unpack all images from given HEIF into image files in specified directory.
 /// Unpacked images will be named by their indices, starting from 0: '0.png', '1.png'...
-pub fn unpack_images<P: AsRef<Path>>(heif_ctx: &HeifContext, dest_dir_path: P) -> Result<()> {
+/// Extension depends on the encoding format. If fit is given, images are resized to the target size.
+pub fn unpack_images<P: AsRef<Path>>(
+    heif_ctx: &HeifContext,
+    dest_dir_path: P,
+    fit: Option<Fit>,
+    encoding: Encoding,
+) -> Result<()> {
     let dest_dir_path = dest_dir_path.as_ref();
     let images = read::get_images(heif_ctx)?;
 
-    let n_threads = min(num_cpus::get(), images.len());
+    let n_images = images.len();
+    let n_threads = min(num_cpus::get(), n_images);
     let thread_pool = ThreadPool::new(n_threads);
     debug!("unpacking using {n_threads} threads");
 
+    let (result_tx, result_rx) = channel();
     for (i, image) in images.into_iter().enumerate() {
-        let unpacked_image_path = dest_dir_path.join(format!("{i}.png"));
+        let unpacked_image_path =
+            dest_dir_path.join(format!("{i}.{}", encoding.format.extension()));
+        let result_tx = result_tx.clone();
         thread_pool.execute(move || {
             debug!("writing image to {}", unpacked_image_path.display());
-            write_image_as_png(&image, &unpacked_image_path).unwrap();
+            let result = write_image(&image, fit, encoding, &unpacked_image_path)
+                .with_context(|| format!("couldn't write {}", unpacked_image_path.display()));
+            result_tx.send((i, result)).ok();
         });
     }
-    thread_pool.join();
+    drop(result_tx);
 
-    Ok(())
+    let mut results = result_rx.iter().collect::<Vec<_>>();
+    if results.len() < n_images {
+        bail!("image writing failed unexpectedly");
+    }
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().try_for_each(|(_, result)| result)
 }
 
-/// Write HEIF image as PNG at the specified path.
-pub fn write_image_as_png<P: AsRef<Path>>(image: &Image, path: P) -> Result<()> {
-    let output = File::create(&path)?;
-    let output_writer = BufWriter::new(output);
+/// Unpack a single image with a given index from HEIF into specified directory.
+/// The image is named the same way as by `unpack_images`. It's first written under a temporary name
+/// and then renamed, so an image file with the final name is always complete.
+pub fn unpack_image<P: AsRef<Path>>(
+    heif_ctx: &HeifContext,
+    index: usize,
+    dest_dir_path: P,
+    fit: Option<Fit>,
+    encoding: Encoding,
+) -> Result<PathBuf> {
+    static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
-    let image_plane = image.planes().interleaved.unwrap();
-    let line_length = image_plane.width as usize * CHANNELS;
+    let dest_dir_path = dest_dir_path.as_ref();
+    let image_name = format!("{index}.{}", encoding.format.extension());
+    let image_path = dest_dir_path.join(&image_name);
+    let tmp_path = dest_dir_path.join(format!(
+        ".{image_name}.{}-{}.tmp",
+        process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let image = read::get_image(heif_ctx, index)?;
+    debug!("writing image to {}", image_path.display());
+    write_image(&image, fit, encoding, &tmp_path)?;
+    fs::rename(&tmp_path, &image_path)
+        .with_context(|| format!("couldn't move image to {}", image_path.display()))?;
+
+    Ok(image_path)
+}
 
-    let mut png_encoder = png::Encoder::new(output_writer, image_plane.width, image_plane.height);
-    png_encoder.set_color(png::ColorType::Rgb);
-    png_encoder.set_depth(png::BitDepth::Eight);
-    let mut png_writer = png_encoder.write_header()?.into_stream_writer()?;
+/// Decode all images from given HEIF into in-memory RGB images.
+pub fn get_rgb_images(heif_ctx: &HeifContext) -> Result<Vec<RgbImage>> {
+    Ok(read::get_images(heif_ctx)?
+        .iter()
+        .map(to_rgb_image)
+        .collect())
+}
 
-    write_from_padded_data(
-        &mut png_writer,
+/// Decode a single image with a given index from HEIF into in-memory RGB image.
+pub fn get_rgb_image(heif_ctx: &HeifContext, index: usize) -> Result<RgbImage> {
+    Ok(to_rgb_image(&read::get_image(heif_ctx, index)?))
+}
+
+fn to_rgb_image(image: &Image) -> RgbImage {
+    let image_plane = image.planes().interleaved.unwrap();
+    RgbImage::from_padded(
+        image_plane.width,
+        image_plane.height,
         image_plane.data,
         image_plane.stride,
-        line_length,
-    )?;
-
-    Ok(())
+    )
 }
 
-/// Write potentially padded image data, removing line padding if it's present.
-/// Read up on "image stride" if you don't get what's going on here.
-fn write_from_padded_data<W: Write>(
-    writer: &mut W,
-    data: &[u8],
-    data_stride: usize,
-    line_length: usize,
+/// Write HEIF image at the specified path, optionally fitting it into a target size.
+pub fn write_image<P: AsRef<Path>>(
+    image: &Image,
+    fit: Option<Fit>,
+    encoding: Encoding,
+    path: P,
 ) -> Result<()> {
-    if data_stride == line_length {
-        // If stride is equal to line line length, then there's no padding.
-        // We can just write everything.
-        debug!("image lines not padded");
-        writer.write_all(data)?;
+    let image_plane = image.planes().interleaved.unwrap();
+    let view = RgbView::new(
+        image_plane.width,
+        image_plane.height,
+        image_plane.data,
+        image_plane.stride,
+    );
+
+    if let Some(fit) = fit {
+        let fitted = fit.apply(&RgbImage::from_padded(
+            image_plane.width,
+            image_plane.height,
+            image_plane.data,
+            image_plane.stride,
+        ));
+        debug!(
+            "resized image from {}x{} to {fit}",
+            image_plane.width, image_plane.height
+        );
+        image::write_image(fitted.view(), encoding, path)
     } else {
-        // Otherwise, we have to write line by line, removing the padding in the process.
-        debug!("image lines padded");
-        for data_line in data.chunks(data_stride) {
-            writer.write_all(&data_line[..line_length])?;
-        }
-    }
-
-    Ok(())
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    pub fn test_write_from_padded_data_no_padding() {
-        #[rustfmt::skip]
-        let data: &[u8] = &[
-            1, 1, 1, 2, 2, 2,
-            3, 3, 3, 4, 4, 4,
-            5, 5, 5, 6, 6, 6,
-        ];
-        let data_stride = 6;
-        let line_length = 6;
-
-        let mut result: Vec<u8> = Vec::new();
-        write_from_padded_data(&mut result, data, data_stride, line_length).unwrap();
-
-        assert_eq!(result, data);
-    }
-
-    #[test]
-    pub fn test_write_from_padded_data_with_padding() {
-        #[rustfmt::skip]
-        let data: &[u8] = &[
-            1, 1, 1, 2, 2, 2, 0, 0, 0,
-            3, 3, 3, 4, 4, 4, 0, 0, 0,
-            5, 5, 5, 6, 6, 6, 0, 0, 0,
-        ];
-        let data_stride = 9;
-        let line_length = 6;
-        #[rustfmt::skip]
-        let expected: &[u8] = &[
-            1, 1, 1, 2, 2, 2,
-            3, 3, 3, 4, 4, 4,
-            5, 5, 5, 6, 6, 6,
-        ];
-
-        let mut result: Vec<u8> = Vec::new();
-        write_from_padded_data(&mut result, data, data_stride, line_length).unwrap();
-
-        assert_eq!(result, expected);
+        image::write_image(view, encoding, path)
     }
 }
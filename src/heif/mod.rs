@@ -2,5 +2,5 @@ mod context;
 mod convert;
 mod read;
 pub use context::from_file;
-pub use convert::unpack_images;
+pub use convert::{get_rgb_image, get_rgb_images, unpack_image, unpack_images};
 pub use read::{get_xmp_metadata, validate_file};
@@ -53,3 +53,15 @@ pub fn get_images(heif_ctx: &HeifContext) -> Result<Vec<Image>> {
         .collect::<Result<Vec<_>, HeifError>>()
         .context("couldn't extract some images from HEIF")
 }
+
+/// Decode a single top level image with a given index from HEIF.
+pub fn get_image(heif_ctx: &HeifContext, index: usize) -> Result<Image> {
+    let image_id = *heif_ctx
+        .image_ids()
+        .get(index)
+        .ok_or_else(|| anyhow!("there is no image with index {index} in HEIF"))?;
+    let image_handle = heif_ctx.image_handle(image_id)?;
+    LibHeif::new()
+        .decode(&image_handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+        .with_context(|| format!("couldn't extract image {index} from HEIF"))
+}
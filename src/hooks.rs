@@ -0,0 +1,181 @@
+use std::env;
+use std::fmt::Display;
+use std::path::Path;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use log::debug;
+
+use crate::appearance::Appearance;
+use crate::config::Hooks;
+use crate::setter::placeholders::Placeholders;
+use crate::setter::{spawn_command, wait_with_timeout};
+
+/// Event which can trigger a hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    /// Displayed image changed.
+    Change,
+    /// System or user appearance changed.
+    AppearanceChange,
+    /// Different wallpaper file is used.
+    WallpaperSwitch,
+}
+
+impl HookEvent {
+    const fn command(self, hooks_config: &Hooks) -> Option<&Vec<String>> {
+        match self {
+            Self::Change => hooks_config.on_change.as_ref(),
+            Self::AppearanceChange => hooks_config.on_appearance_change.as_ref(),
+            Self::WallpaperSwitch => hooks_config.on_wallpaper_switch.as_ref(),
+        }
+    }
+}
+
+impl Display for HookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Change => write!(f, "on_change"),
+            Self::AppearanceChange => write!(f, "on_appearance_change"),
+            Self::WallpaperSwitch => write!(f, "on_wallpaper_switch"),
+        }
+    }
+}
+
+/// Get events which happened between two wallpaper changes.
+///
+/// Appearance change and wallpaper switch are reported only if the previous state is known.
+pub fn get_hook_events(
+    previous_appearance: Option<Appearance>,
+    previous_wallpaper: Option<&Path>,
+    placeholders: &Placeholders,
+) -> Vec<HookEvent> {
+    let mut events = vec![HookEvent::Change];
+    if previous_appearance.is_some_and(|appearance| appearance != placeholders.appearance) {
+        events.push(HookEvent::AppearanceChange);
+    }
+    if previous_wallpaper.is_some_and(|previous| !is_same_file(previous, &placeholders.wallpaper)) {
+        events.push(HookEvent::WallpaperSwitch);
+    }
+    events
+}
+
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Run hooks configured for given events in the background.
+///
+/// Hooks which don't finish within the configured timeout are killed.
+/// Returned handles can be joined to wait for all hooks to finish.
+pub fn run_hooks(
+    hooks_config: &Hooks,
+    events: &[HookEvent],
+    placeholders: &Placeholders,
+) -> Vec<JoinHandle<()>> {
+    events
+        .iter()
+        .filter_map(|event| {
+            let command = placeholders.expand_command(event.command(hooks_config)?);
+            if env::var("TIMEWALL_DRY_RUN").is_ok() {
+                println!("Hook {event}: {}", command.join(" "));
+                return None;
+            }
+
+            let event = *event;
+            let placeholders = placeholders.clone();
+            let quiet = hooks_config.quiet;
+            let timeout = Duration::from_millis(hooks_config.timeout);
+            Some(thread::spawn(move || {
+                if let Err(err) = run_hook(&command, &placeholders, quiet, timeout) {
+                    log::error!("{event} hook failed: {err:#}");
+                }
+            }))
+        })
+        .collect()
+}
+
+fn run_hook(
+    command: &[String],
+    placeholders: &Placeholders,
+    quiet: bool,
+    timeout: Duration,
+) -> Result<()> {
+    debug!("running hook: {}", command.join(" "));
+    let mut process = spawn_command(command, placeholders, quiet)?;
+    match wait_with_timeout(&mut process, timeout)? {
+        Some(status) if status.success() => Ok(()),
+        Some(status) => bail!("`{}` exited with {status}", command.join(" ")),
+        None => {
+            let _ = process.kill();
+            let _ = process.wait();
+            bail!(
+                "`{}` did not finish within {} ms",
+                command.join(" "),
+                timeout.as_millis()
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rstest::*;
+
+    use super::*;
+
+    fn placeholders(appearance: Appearance, wallpaper: &str) -> Placeholders {
+        Placeholders {
+            image: PathBuf::from("0.png"),
+            image_index: 0,
+            frame_number: 1,
+            frame_count: 2,
+            schedule_type: "time",
+            appearance,
+            wallpaper: PathBuf::from(wallpaper),
+            output: None,
+        }
+    }
+
+    #[rstest]
+    #[case(None, None, vec![HookEvent::Change])]
+    #[case(Some(Appearance::Light), Some("a.heic"), vec![HookEvent::Change])]
+    #[case(Some(Appearance::Dark), Some("a.heic"), vec![HookEvent::Change, HookEvent::AppearanceChange])]
+    #[case(Some(Appearance::Light), Some("b.heic"), vec![HookEvent::Change, HookEvent::WallpaperSwitch])]
+    #[case(
+        Some(Appearance::Dark),
+        Some("b.heic"),
+        vec![HookEvent::Change, HookEvent::AppearanceChange, HookEvent::WallpaperSwitch]
+    )]
+    fn test_get_hook_events(
+        #[case] previous_appearance: Option<Appearance>,
+        #[case] previous_wallpaper: Option<&str>,
+        #[case] expected: Vec<HookEvent>,
+    ) {
+        assert_eq!(
+            get_hook_events(
+                previous_appearance,
+                previous_wallpaper.map(Path::new),
+                &placeholders(Appearance::Light, "a.heic")
+            ),
+            expected
+        );
+    }
+
+    #[rstest]
+    fn test_run_hook_timeout() {
+        let result = run_hook(
+            &["sleep".to_owned(), "5".to_owned()],
+            &placeholders(Appearance::Light, "a.heic"),
+            true,
+            Duration::from_millis(50),
+        );
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use super::RgbImage;
+
+/// Size of a single glyph in pixels.
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+/// Horizontal space taken by a single character, including the spacing after it.
+const ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+/// Get rows of a glyph for a given character. Each row is a bitmask with the leftmost pixel in
+/// the highest of the five lowest bits. Lowercase letters are drawn as uppercase. Only characters
+/// needed for frame labels are included, everything else is drawn as blank space.
+const fn glyph(c: char) -> [u8; GLYPH_HEIGHT as usize] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '°' => [0x0C, 0x12, 0x12, 0x0C, 0x00, 0x00, 0x00],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'D' => [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        _ => [0x00; GLYPH_HEIGHT as usize],
+    }
+}
+
+/// Get width of a text drawn with a given scale, in pixels.
+#[allow(
+    clippy::cast_possible_truncation,
+    reason = "labels are a few dozen characters long"
+)]
+pub fn text_width(text: &str, scale: u32) -> u32 {
+    let chars = text.chars().count() as u32;
+    (chars * ADVANCE).saturating_sub(1) * scale
+}
+
+/// Get height of a text drawn with a given scale, in pixels.
+pub const fn text_height(scale: u32) -> u32 {
+    GLYPH_HEIGHT * scale
+}
+
+/// Draw a single line of text with its top left corner at given coordinates. Each font pixel is
+/// drawn as a `scale` by `scale` square. Parts not fitting in the image are skipped.
+pub fn draw_text(image: &mut RgbImage, x: u32, y: u32, text: &str, scale: u32, color: [u8; 3]) {
+    for (char_x, c) in (0..).map(|i| x + i * ADVANCE * scale).zip(text.chars()) {
+        for (row_y, row) in (0..).map(|i| y + i * scale).zip(glyph(c)) {
+            for column in (0..GLYPH_WIDTH).filter(|column| row & (0x10 >> column) != 0) {
+                fill_rect(image, char_x + column * scale, row_y, scale, scale, |_| {
+                    color
+                });
+            }
+        }
+    }
+}
+
+/// Replace pixels in a rectangle with the result of a given function. Parts not fitting in the
+/// image are skipped.
+pub fn fill_rect<F: Fn([u8; 3]) -> [u8; 3]>(
+    image: &mut RgbImage,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    fill: F,
+) {
+    let line_length = image.line_length();
+    for pixel_y in y..(y + height).min(image.height) {
+        for pixel_x in x..(x + width).min(image.width) {
+            let start = pixel_y as usize * line_length + pixel_x as usize * RgbImage::CHANNELS;
+            let pixel = &mut image.data[start..start + RgbImage::CHANNELS];
+            pixel.copy_from_slice(&fill([pixel[0], pixel[1], pixel[2]]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case("", 1, 0)]
+    #[case("1", 1, 5)]
+    #[case("12:00", 1, 29)]
+    #[case("12:00", 2, 58)]
+    fn test_text_width(#[case] text: &str, #[case] scale: u32, #[case] expected: u32) {
+        assert_eq!(text_width(text, scale), expected);
+    }
+
+    #[rstest]
+    fn test_draw_text() {
+        let mut image = RgbImage::new(8, 8);
+
+        draw_text(&mut image, 1, 1, "-", 1, [255, 255, 255]);
+
+        let lit = (0..8)
+            .flat_map(|y| (0..8).map(move |x| (x, y)))
+            .filter(|&(x, y)| image.view().pixel(x, y) == [255, 255, 255])
+            .collect::<Vec<_>>();
+        assert_eq!(lit, vec![(1, 4), (2, 4), (3, 4), (4, 4), (5, 4)]);
+    }
+
+    #[rstest]
+    fn test_draw_text_clipped() {
+        let mut image = RgbImage::new(3, 3);
+
+        draw_text(&mut image, 0, 0, "888", 2, [255, 255, 255]);
+
+        assert_eq!(image.view().pixel(2, 0), [255, 255, 255]);
+    }
+
+    #[rstest]
+    fn test_fill_rect() {
+        let mut image = RgbImage::new(2, 2);
+
+        fill_rect(&mut image, 1, 0, 5, 5, |[r, g, b]| [r + 1, g + 2, b + 3]);
+
+        assert_eq!(image.data, vec![0, 0, 0, 1, 2, 3, 0, 0, 0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,103 @@
+use std::io::Write;
+
+use anyhow::{ensure, Context, Result};
+use jpeg_encoder::{ColorType, Encoder};
+
+use super::RgbView;
+
+/// Write the image as baseline JPEG with a given quality, from 1 to 100.
+pub fn write<W: Write>(image: RgbView, quality: u8, writer: &mut W) -> Result<()> {
+    ensure!(
+        image.width > 0 && image.height > 0,
+        "can't write an empty image as JPEG"
+    );
+    let width = u16::try_from(image.width).context("image is too wide for JPEG")?;
+    let height = u16::try_from(image.height).context("image is too tall for JPEG")?;
+
+    Encoder::new(writer, quality)
+        .encode(&image.to_image().data, width, height, ColorType::Rgb)
+        .context("failed to encode JPEG")
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+    use crate::image::RgbImage;
+
+    /// Smooth image with a different gradient in each channel.
+    #[allow(clippy::cast_possible_truncation, reason = "test images are tiny")]
+    fn gradient(width: u32, height: u32) -> RgbImage {
+        let mut image = RgbImage::new(width, height);
+        for (index, pixel) in image.data.chunks_exact_mut(3).enumerate() {
+            let (x, y) = (index as u32 % width, index as u32 / width);
+            pixel[0] = (x * 255 / width) as u8;
+            pixel[1] = (y * 255 / height) as u8;
+            pixel[2] = ((x + y) * 127 / (width + height)) as u8;
+        }
+        image
+    }
+
+    /// Peak signal to noise ratio between two images of the same size, in dB.
+    #[allow(clippy::cast_precision_loss, reason = "test images are tiny")]
+    fn psnr(expected: &[u8], actual: &[u8]) -> f64 {
+        let squared_error: f64 = expected
+            .iter()
+            .zip(actual)
+            .map(|(&a, &b)| (f64::from(a) - f64::from(b)).powi(2))
+            .sum();
+        let mse = squared_error / expected.len() as f64;
+        10.0 * (255.0 * 255.0 / mse.max(f64::EPSILON)).log10()
+    }
+
+    #[rstest]
+    #[case(1, 1)]
+    #[case(7, 5)]
+    #[case(16, 16)]
+    #[case(17, 9)]
+    #[case(50, 33)]
+    fn test_write_decodes(#[case] width: u32, #[case] height: u32) {
+        let image = gradient(width, height);
+
+        let mut result = Vec::new();
+        write(image.view(), 90, &mut result).unwrap();
+
+        let mut decoder = jpeg_decoder::Decoder::new(result.as_slice());
+        let pixels = decoder.decode().unwrap();
+        let info = decoder.info().unwrap();
+        assert_eq!(
+            (u32::from(info.width), u32::from(info.height)),
+            (width, height)
+        );
+        assert_eq!(info.pixel_format, jpeg_decoder::PixelFormat::RGB24);
+        assert!(psnr(&image.data, &pixels) > 30.0);
+    }
+
+    #[rstest]
+    fn test_write_padded_view() {
+        let image = gradient(10, 4);
+        let padded = image
+            .data
+            .chunks_exact(image.line_length())
+            .flat_map(|line| line.iter().copied().chain([0; 6]))
+            .collect::<Vec<_>>();
+        let view = RgbView::new(10, 4, &padded, image.line_length() + 6);
+
+        let mut expected = Vec::new();
+        write(image.view(), 90, &mut expected).unwrap();
+        let mut result = Vec::new();
+        write(view, 90, &mut result).unwrap();
+
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case(0, 10)]
+    #[case(70_000, 1)]
+    fn test_write_invalid_size(#[case] width: u32, #[case] height: u32) {
+        let image = RgbImage::new(width, height);
+
+        assert!(write(image.view(), 90, &mut Vec::new()).is_err());
+    }
+}
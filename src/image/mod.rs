@@ -0,0 +1,252 @@
+pub mod font;
+pub mod jpeg;
+pub mod png;
+pub mod ppm;
+pub mod resize;
+pub mod webp;
+
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Format in which images are written.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+    Webp,
+    Ppm,
+}
+
+impl ImageFormat {
+    pub const ALL: [Self; 4] = [Self::Png, Self::Jpeg, Self::Webp, Self::Ppm];
+
+    /// Extension of files in this format.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+            Self::Ppm => "ppm",
+        }
+    }
+}
+
+impl Display for ImageFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Png => write!(f, "png"),
+            Self::Jpeg => write!(f, "jpeg"),
+            Self::Webp => write!(f, "webp"),
+            Self::Ppm => write!(f, "ppm"),
+        }
+    }
+}
+
+/// Image format along with its encoding settings.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Encoding {
+    pub format: ImageFormat,
+    /// JPEG quality, from 1 to 100.
+    pub quality: u8,
+    /// PNG compression level, from 0 (none) to 9 (best).
+    pub compression: u8,
+}
+
+impl Encoding {
+    pub const DEFAULT_QUALITY: u8 = 90;
+    pub const DEFAULT_COMPRESSION: u8 = 6;
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self {
+            format: ImageFormat::default(),
+            quality: Self::DEFAULT_QUALITY,
+            compression: Self::DEFAULT_COMPRESSION,
+        }
+    }
+}
+
+/// In-memory image with 8-bit RGB pixels, stored line by line without any padding.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RgbImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl RgbImage {
+    pub const CHANNELS: usize = 3;
+
+    /// Create a black image of a given size.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0; width as usize * height as usize * Self::CHANNELS],
+        }
+    }
+
+    /// Create image from potentially padded lines of pixels.
+    pub fn from_padded(width: u32, height: u32, data: &[u8], stride: usize) -> Self {
+        RgbView::new(width, height, data, stride).to_image()
+    }
+
+    /// Length of a single line of pixels in bytes.
+    pub const fn line_length(&self) -> usize {
+        self.width as usize * Self::CHANNELS
+    }
+
+    /// Borrow the image as a view.
+    pub fn view(&self) -> RgbView<'_> {
+        RgbView::new(self.width, self.height, &self.data, self.line_length())
+    }
+}
+
+/// Borrowed 8-bit RGB image data, potentially with padded lines.
+/// Read up on "image stride" if you don't get what's going on here.
+#[derive(Clone, Copy, Debug)]
+pub struct RgbView<'a> {
+    pub width: u32,
+    pub height: u32,
+    data: &'a [u8],
+    stride: usize,
+}
+
+impl<'a> RgbView<'a> {
+    pub const fn new(width: u32, height: u32, data: &'a [u8], stride: usize) -> Self {
+        Self {
+            width,
+            height,
+            data,
+            stride,
+        }
+    }
+
+    /// Length of a single line of pixels in bytes, without padding.
+    pub const fn line_length(&self) -> usize {
+        self.width as usize * RgbImage::CHANNELS
+    }
+
+    /// Get a line of pixels without padding.
+    pub fn line(&self, y: u32) -> &'a [u8] {
+        let start = y as usize * self.stride;
+        &self.data[start..start + self.line_length()]
+    }
+
+    /// Get a pixel at given coordinates.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        let start = y as usize * self.stride + x as usize * RgbImage::CHANNELS;
+        [self.data[start], self.data[start + 1], self.data[start + 2]]
+    }
+
+    /// Copy the data into an owned image.
+    pub fn to_image(self) -> RgbImage {
+        let data = (0..self.height)
+            .flat_map(|y| self.line(y))
+            .copied()
+            .collect();
+        RgbImage {
+            width: self.width,
+            height: self.height,
+            data,
+        }
+    }
+}
+
+/// Write the image at a given path, using a given encoding.
+pub fn write_image<P: AsRef<Path>>(image: RgbView, encoding: Encoding, path: P) -> Result<()> {
+    let path = path.as_ref();
+    let output = File::create(path)
+        .with_context(|| format!("couldn't create image file {}", path.display()))?;
+    let mut writer = BufWriter::new(output);
+
+    match encoding.format {
+        ImageFormat::Png => png::write(image, encoding.compression, &mut writer)?,
+        ImageFormat::Jpeg => jpeg::write(image, encoding.quality, &mut writer)?,
+        ImageFormat::Webp => webp::write(image, &mut writer)?,
+        ImageFormat::Ppm => ppm::write(image, &mut writer)?,
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Write frames as an animated PNG at a given path. Each frame is given along with the time it's
+/// displayed for, in milliseconds.
+pub fn write_animation<P: AsRef<Path>>(
+    frames: &[(RgbImage, u16)],
+    compression: u8,
+    repeat: bool,
+    path: P,
+) -> Result<()> {
+    let path = path.as_ref();
+    let output = File::create(path)
+        .with_context(|| format!("couldn't create animation file {}", path.display()))?;
+    let mut writer = BufWriter::new(output);
+    png::write_animated(frames, compression, repeat, &mut writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    fn test_from_padded_no_padding() {
+        #[rustfmt::skip]
+        let data: &[u8] = &[
+            1, 1, 1, 2, 2, 2,
+            3, 3, 3, 4, 4, 4,
+        ];
+
+        let image = RgbImage::from_padded(2, 2, data, 6);
+
+        assert_eq!(image.data, data);
+    }
+
+    #[rstest]
+    fn test_from_padded_with_padding() {
+        #[rustfmt::skip]
+        let data: &[u8] = &[
+            1, 1, 1, 2, 2, 2, 0, 0,
+            3, 3, 3, 4, 4, 4, 0, 0,
+        ];
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            1, 1, 1, 2, 2, 2,
+            3, 3, 3, 4, 4, 4,
+        ];
+
+        let image = RgbImage::from_padded(2, 2, data, 8);
+
+        assert_eq!(image.data, expected);
+    }
+
+    #[rstest]
+    fn test_view_pixel() {
+        #[rustfmt::skip]
+        let data: &[u8] = &[
+            1, 1, 1, 2, 2, 2, 0, 0,
+            3, 3, 3, 4, 4, 4, 0, 0,
+        ];
+
+        let view = RgbView::new(2, 2, data, 8);
+
+        assert_eq!(view.pixel(1, 1), [4, 4, 4]);
+        assert_eq!(view.line(1), &[3, 3, 3, 4, 4, 4]);
+    }
+}
@@ -0,0 +1,136 @@
+use std::io::Write;
+
+use anyhow::{bail, Context, Result};
+use log::debug;
+use png::DeflateCompression;
+
+use super::{RgbImage, RgbView};
+
+/// Write the image as 8-bit RGB PNG, with a given compression level from 0 to 9.
+pub fn write<W: Write>(image: RgbView, compression: u8, writer: &mut W) -> Result<()> {
+    let png_encoder = new_encoder(writer, image.width, image.height, compression);
+    let mut png_writer = png_encoder.write_header()?;
+    let mut stream_writer = png_writer.stream_writer()?;
+
+    write_from_padded_data(
+        &mut stream_writer,
+        image.data,
+        image.stride,
+        image.line_length(),
+    )?;
+    stream_writer.finish()?;
+
+    Ok(())
+}
+
+/// Write frames as 8-bit RGB animated PNG, with a given compression level from 0 to 9.
+/// Each frame is given along with the time it's displayed for, in milliseconds.
+/// All frames must have the same size. The animation is played in a loop if `repeat` is set,
+/// otherwise just once.
+pub fn write_animated<W: Write>(
+    frames: &[(RgbImage, u16)],
+    compression: u8,
+    repeat: bool,
+    writer: &mut W,
+) -> Result<()> {
+    let Some((first, _)) = frames.first() else {
+        bail!("animation has no frames");
+    };
+    let mut png_encoder = new_encoder(writer, first.width, first.height, compression);
+    let num_frames = u32::try_from(frames.len()).context("too many animation frames")?;
+    png_encoder.set_animated(num_frames, u32::from(!repeat))?;
+    let mut png_writer = png_encoder.write_header()?;
+
+    for (frame, delay_ms) in frames {
+        png_writer.set_frame_delay(*delay_ms, 1000)?;
+        png_writer.write_image_data(&frame.data)?;
+    }
+    png_writer.finish()?;
+
+    Ok(())
+}
+
+fn new_encoder<W: Write>(
+    writer: W,
+    width: u32,
+    height: u32,
+    compression: u8,
+) -> png::Encoder<'static, W> {
+    let mut png_encoder = png::Encoder::new(writer, width, height);
+    png_encoder.set_color(png::ColorType::Rgb);
+    png_encoder.set_depth(png::BitDepth::Eight);
+    png_encoder.set_deflate_compression(match compression {
+        0 => DeflateCompression::NoCompression,
+        level => DeflateCompression::Level(level.min(9)),
+    });
+    png_encoder
+}
+
+/// Write potentially padded image data, removing line padding if it's present.
+/// Read up on "image stride" if you don't get what's going on here.
+fn write_from_padded_data<W: Write>(
+    writer: &mut W,
+    data: &[u8],
+    data_stride: usize,
+    line_length: usize,
+) -> Result<()> {
+    if data_stride == line_length {
+        // If stride is equal to line line length, then there's no padding.
+        // We can just write everything.
+        debug!("image lines not padded");
+        writer.write_all(data)?;
+    } else {
+        // Otherwise, we have to write line by line, removing the padding in the process.
+        debug!("image lines padded");
+        for data_line in data.chunks(data_stride) {
+            writer.write_all(&data_line[..line_length])?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_write_from_padded_data_no_padding() {
+        #[rustfmt::skip]
+        let data: &[u8] = &[
+            1, 1, 1, 2, 2, 2,
+            3, 3, 3, 4, 4, 4,
+            5, 5, 5, 6, 6, 6,
+        ];
+        let data_stride = 6;
+        let line_length = 6;
+
+        let mut result: Vec<u8> = Vec::new();
+        write_from_padded_data(&mut result, data, data_stride, line_length).unwrap();
+
+        assert_eq!(result, data);
+    }
+
+    #[test]
+    pub fn test_write_from_padded_data_with_padding() {
+        #[rustfmt::skip]
+        let data: &[u8] = &[
+            1, 1, 1, 2, 2, 2, 0, 0, 0,
+            3, 3, 3, 4, 4, 4, 0, 0, 0,
+            5, 5, 5, 6, 6, 6, 0, 0, 0,
+        ];
+        let data_stride = 9;
+        let line_length = 6;
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            1, 1, 1, 2, 2, 2,
+            3, 3, 3, 4, 4, 4,
+            5, 5, 5, 6, 6, 6,
+        ];
+
+        let mut result: Vec<u8> = Vec::new();
+        write_from_padded_data(&mut result, data, data_stride, line_length).unwrap();
+
+        assert_eq!(result, expected);
+    }
+}
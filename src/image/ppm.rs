@@ -0,0 +1,36 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::RgbView;
+
+/// Write the image as binary PPM, i.e. raw pixels with a short header.
+pub fn write<W: Write>(image: RgbView, writer: &mut W) -> Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", image.width, image.height)?;
+    for y in 0..image.height {
+        writer.write_all(image.line(y))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+    use crate::image::RgbImage;
+
+    #[rstest]
+    fn test_write() {
+        let image = RgbImage {
+            width: 2,
+            height: 1,
+            data: vec![1, 2, 3, 4, 5, 6],
+        };
+
+        let mut result = Vec::new();
+        write(image.view(), &mut result).unwrap();
+
+        assert_eq!(result, b"P6\n2 1\n255\n\x01\x02\x03\x04\x05\x06");
+    }
+}
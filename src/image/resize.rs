@@ -0,0 +1,343 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use super::RgbImage;
+
+/// Radius of the Lanczos filter used for resampling.
+const LANCZOS_RADIUS: f64 = 3.0;
+
+/// How an image is fitted into the target size.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FitMode {
+    /// Scale to cover the whole target, cropping what doesn't fit.
+    #[default]
+    Cover,
+    /// Scale to fit entirely in the target, filling the rest with black.
+    Contain,
+    /// Don't scale, just crop or pad around the center.
+    Center,
+}
+
+impl Display for FitMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cover => write!(f, "cover"),
+            Self::Contain => write!(f, "contain"),
+            Self::Center => write!(f, "center"),
+        }
+    }
+}
+
+/// Target size and the way of fitting an image into it.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Fit {
+    pub width: u32,
+    pub height: u32,
+    pub mode: FitMode,
+}
+
+impl Display for Fit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}-{}", self.width, self.height, self.mode)
+    }
+}
+
+impl Fit {
+    /// Fit the image into the target size.
+    pub fn apply(&self, image: &RgbImage) -> RgbImage {
+        let (scaled_width, scaled_height) = self.scaled_size(image.width, image.height);
+        let scaled = resize(image, scaled_width, scaled_height);
+        place_centered(&scaled, self.width, self.height)
+    }
+
+    /// Get size of the image after scaling, before cropping or padding.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "scaled sizes are positive and comparable to the target size"
+    )]
+    fn scaled_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let width_ratio = f64::from(self.width) / f64::from(width);
+        let height_ratio = f64::from(self.height) / f64::from(height);
+        let ratio = match self.mode {
+            FitMode::Cover => width_ratio.max(height_ratio),
+            FitMode::Contain => width_ratio.min(height_ratio),
+            FitMode::Center => return (width, height),
+        };
+        let scale = |size: u32| ((f64::from(size) * ratio).round() as u32).max(1);
+        (scale(width), scale(height))
+    }
+}
+
+/// Resize the image to a given size using Lanczos resampling.
+pub fn resize(image: &RgbImage, width: u32, height: u32) -> RgbImage {
+    if image.width == width && image.height == height {
+        return image.clone();
+    }
+    let horizontal = resize_horizontal(image, width);
+    resize_vertical(&horizontal, height)
+}
+
+/// Scale the image to a given width, keeping its aspect ratio.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    reason = "scaled height is positive and comparable to the target width"
+)]
+pub fn scale_to_width(image: &RgbImage, width: u32) -> RgbImage {
+    let height = (f64::from(image.height) * f64::from(width) / f64::from(image.width)).round();
+    resize(image, width, (height as u32).max(1))
+}
+
+/// Crop or pad the image around its center to a given size. Padding is black.
+pub fn place_centered(image: &RgbImage, width: u32, height: u32) -> RgbImage {
+    if image.width == width && image.height == height {
+        return image.clone();
+    }
+
+    let mut placed = RgbImage::new(width, height);
+    let (src_x, dst_x, copy_width) = centered_span(image.width, width);
+    let (src_y, dst_y, copy_height) = centered_span(image.height, height);
+    let copy_length = copy_width as usize * RgbImage::CHANNELS;
+    for line in 0..copy_height as usize {
+        let src_start =
+            (src_y as usize + line) * image.line_length() + src_x as usize * RgbImage::CHANNELS;
+        let dst_start =
+            (dst_y as usize + line) * placed.line_length() + dst_x as usize * RgbImage::CHANNELS;
+        placed.data[dst_start..dst_start + copy_length]
+            .copy_from_slice(&image.data[src_start..src_start + copy_length]);
+    }
+    placed
+}
+
+/// Get source offset, destination offset and length of the centered part of a line.
+const fn centered_span(src_len: u32, dst_len: u32) -> (u32, u32, u32) {
+    if src_len > dst_len {
+        ((src_len - dst_len) / 2, 0, dst_len)
+    } else {
+        (0, (dst_len - src_len) / 2, src_len)
+    }
+}
+
+/// Source pixels contributing to a single destination pixel, with their weights.
+struct Contribution {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss,
+    reason = "pixel positions are far below the float precision limits"
+)]
+fn compute_contributions(src_len: u32, dst_len: u32) -> Vec<Contribution> {
+    let scale = f64::from(src_len) / f64::from(dst_len);
+    // When downscaling, the filter has to be stretched to avoid aliasing.
+    let filter_scale = scale.max(1.0);
+    let support = LANCZOS_RADIUS * filter_scale;
+
+    (0..dst_len)
+        .map(|dst_index| {
+            let center = (f64::from(dst_index) + 0.5) * scale;
+            let start = (center - support).floor().max(0.0) as usize;
+            let end = ((center + support).ceil() as usize).min(src_len as usize);
+            let weights = (start..end)
+                .map(|src_index| lanczos((src_index as f64 + 0.5 - center) / filter_scale))
+                .collect::<Vec<_>>();
+            let weights_sum: f64 = weights.iter().sum();
+            Contribution {
+                start,
+                weights: weights
+                    .iter()
+                    .map(|weight| (weight / weights_sum) as f32)
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+fn lanczos(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else if x.abs() < LANCZOS_RADIUS {
+        sinc(x) * sinc(x / LANCZOS_RADIUS)
+    } else {
+        0.0
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    let x = x * std::f64::consts::PI;
+    x.sin() / x
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    reason = "value is clamped to the u8 range"
+)]
+const fn to_channel_value(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+fn resize_horizontal(image: &RgbImage, width: u32) -> RgbImage {
+    if image.width == width {
+        return image.clone();
+    }
+
+    let contributions = compute_contributions(image.width, width);
+    let mut resized = RgbImage::new(width, image.height);
+    let resized_line_length = resized.line_length();
+    for (src_line, dst_line) in image
+        .data
+        .chunks_exact(image.line_length())
+        .zip(resized.data.chunks_exact_mut(resized_line_length))
+    {
+        for (contribution, dst_pixel) in contributions
+            .iter()
+            .zip(dst_line.chunks_exact_mut(RgbImage::CHANNELS))
+        {
+            let mut sums = [0f32; RgbImage::CHANNELS];
+            for (offset, weight) in contribution.weights.iter().enumerate() {
+                let src_start = (contribution.start + offset) * RgbImage::CHANNELS;
+                for (sum, value) in sums
+                    .iter_mut()
+                    .zip(&src_line[src_start..src_start + RgbImage::CHANNELS])
+                {
+                    *sum += f32::from(*value) * weight;
+                }
+            }
+            for (dst_value, sum) in dst_pixel.iter_mut().zip(sums) {
+                *dst_value = to_channel_value(sum);
+            }
+        }
+    }
+    resized
+}
+
+fn resize_vertical(image: &RgbImage, height: u32) -> RgbImage {
+    if image.height == height {
+        return image.clone();
+    }
+
+    let contributions = compute_contributions(image.height, height);
+    let mut resized = RgbImage::new(image.width, height);
+    let line_length = image.line_length();
+    let mut sums = vec![0f32; line_length];
+    for (contribution, dst_line) in contributions
+        .iter()
+        .zip(resized.data.chunks_exact_mut(line_length))
+    {
+        sums.fill(0.0);
+        for (offset, weight) in contribution.weights.iter().enumerate() {
+            let src_start = (contribution.start + offset) * line_length;
+            for (sum, value) in sums
+                .iter_mut()
+                .zip(&image.data[src_start..src_start + line_length])
+            {
+                *sum += f32::from(*value) * weight;
+            }
+        }
+        for (dst_value, sum) in dst_line.iter_mut().zip(&sums) {
+            *dst_value = to_channel_value(*sum);
+        }
+    }
+    resized
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    fn uniform_image(width: u32, height: u32, color: [u8; 3]) -> RgbImage {
+        RgbImage {
+            width,
+            height,
+            data: color.repeat(width as usize * height as usize),
+        }
+    }
+
+    #[rstest]
+    #[case(FitMode::Cover, (6000, 4000), (1920, 1080), (1920, 1280))]
+    #[case(FitMode::Cover, (4000, 6000), (1920, 1080), (1920, 2880))]
+    #[case(FitMode::Contain, (6000, 4000), (1920, 1080), (1620, 1080))]
+    #[case(FitMode::Contain, (1000, 1000), (1920, 1080), (1080, 1080))]
+    #[case(FitMode::Center, (6000, 4000), (1920, 1080), (6000, 4000))]
+    fn test_fit_scaled_size(
+        #[case] mode: FitMode,
+        #[case] size: (u32, u32),
+        #[case] target: (u32, u32),
+        #[case] expected: (u32, u32),
+    ) {
+        let fit = Fit {
+            width: target.0,
+            height: target.1,
+            mode,
+        };
+        assert_eq!(fit.scaled_size(size.0, size.1), expected);
+    }
+
+    #[rstest]
+    #[case(FitMode::Cover)]
+    #[case(FitMode::Contain)]
+    #[case(FitMode::Center)]
+    fn test_fit_apply_size(#[case] mode: FitMode) {
+        let fit = Fit {
+            width: 16,
+            height: 9,
+            mode,
+        };
+        let fitted = fit.apply(&uniform_image(30, 20, [10, 20, 30]));
+        assert_eq!((fitted.width, fitted.height), (16, 9));
+        assert_eq!(fitted.data.len(), 16 * 9 * 3);
+    }
+
+    #[rstest]
+    #[case(64, 48, 16, 12)]
+    #[case(16, 12, 64, 48)]
+    #[case(7, 5, 3, 11)]
+    fn test_resize_keeps_uniform_color(
+        #[case] width: u32,
+        #[case] height: u32,
+        #[case] new_width: u32,
+        #[case] new_height: u32,
+    ) {
+        let color = [200, 100, 50];
+        let resized = resize(&uniform_image(width, height, color), new_width, new_height);
+        assert_eq!(resized, uniform_image(new_width, new_height, color));
+    }
+
+    #[rstest]
+    fn test_compute_contributions_normalized() {
+        for contribution in compute_contributions(100, 7) {
+            let sum: f32 = contribution.weights.iter().sum();
+            assert!((sum - 1.0).abs() < 1e-4);
+        }
+    }
+
+    #[rstest]
+    fn test_place_centered_crop() {
+        #[rustfmt::skip]
+        let image = RgbImage {
+            width: 4,
+            height: 1,
+            data: vec![
+                1, 1, 1, 2, 2, 2, 3, 3, 3, 4, 4, 4,
+            ],
+        };
+        let placed = place_centered(&image, 2, 1);
+        assert_eq!(placed.data, vec![2, 2, 2, 3, 3, 3]);
+    }
+
+    #[rstest]
+    fn test_place_centered_pad() {
+        let image = uniform_image(1, 1, [9, 9, 9]);
+        let placed = place_centered(&image, 3, 1);
+        assert_eq!(placed.data, vec![0, 0, 0, 9, 9, 9, 0, 0, 0]);
+    }
+}
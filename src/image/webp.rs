@@ -0,0 +1,97 @@
+use std::io::Write;
+
+use anyhow::{ensure, Context, Result};
+use image_webp::{ColorType, WebPEncoder};
+
+use super::RgbView;
+
+/// Maximum width and height of a lossless WebP image.
+const MAX_SIZE: u32 = 1 << 14;
+
+/// Write the image as lossless WebP.
+pub fn write<W: Write>(image: RgbView, writer: &mut W) -> Result<()> {
+    ensure!(
+        image.width > 0 && image.height > 0,
+        "can't write an empty image as WebP"
+    );
+    ensure!(
+        image.width <= MAX_SIZE && image.height <= MAX_SIZE,
+        "image is too large for WebP, maximum size is {MAX_SIZE}x{MAX_SIZE}"
+    );
+
+    WebPEncoder::new(writer)
+        .encode(
+            &image.to_image().data,
+            image.width,
+            image.height,
+            ColorType::Rgb8,
+        )
+        .context("failed to encode WebP")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image_webp::WebPDecoder;
+    use rstest::*;
+
+    use super::*;
+    use crate::image::RgbImage;
+
+    /// Image with a repeating pattern of distinct colors.
+    fn pattern(width: u32, height: u32) -> RgbImage {
+        let mut image = RgbImage::new(width, height);
+        for (index, value) in image.data.iter_mut().enumerate() {
+            *value = u8::try_from(index * 37 % 251).unwrap();
+        }
+        image
+    }
+
+    #[rstest]
+    #[case(1, 1)]
+    #[case(7, 5)]
+    #[case(16, 16)]
+    #[case(17, 9)]
+    #[case(50, 33)]
+    fn test_write_decodes(#[case] width: u32, #[case] height: u32) {
+        let image = pattern(width, height);
+
+        let mut result = Vec::new();
+        write(image.view(), &mut result).unwrap();
+
+        let mut decoder = WebPDecoder::new(Cursor::new(result)).unwrap();
+        assert_eq!(decoder.dimensions(), (width, height));
+        assert!(!decoder.has_alpha());
+        let mut pixels = vec![0; decoder.output_buffer_size().unwrap()];
+        decoder.read_image(&mut pixels).unwrap();
+        assert_eq!(pixels, image.data);
+    }
+
+    #[rstest]
+    fn test_write_padded_view() {
+        let image = pattern(10, 4);
+        let padded = image
+            .data
+            .chunks_exact(image.line_length())
+            .flat_map(|line| line.iter().copied().chain([0; 6]))
+            .collect::<Vec<_>>();
+        let view = RgbView::new(10, 4, &padded, image.line_length() + 6);
+
+        let mut expected = Vec::new();
+        write(image.view(), &mut expected).unwrap();
+        let mut result = Vec::new();
+        write(view, &mut result).unwrap();
+
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case(0, 10)]
+    #[case(MAX_SIZE + 1, 1)]
+    fn test_write_invalid_size(#[case] width: u32, #[case] height: u32) {
+        let image = RgbImage::new(width, height);
+
+        assert!(write(image.view(), &mut Vec::new()).is_err());
+    }
+}
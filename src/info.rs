@@ -1,10 +1,17 @@
 use std::{
+    cmp::min,
     fmt::Display,
     fs,
     path::{Path, PathBuf},
+    slice,
+    sync::mpsc::channel,
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use itertools::Itertools;
+use log::debug;
+use serde::Serialize;
+use threadpool::ThreadPool;
 
 use crate::{
     heif,
@@ -15,6 +22,18 @@ use crate::{
     },
 };
 
+/// Format of the printed wallpaper info.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum InfoFormat {
+    /// Human readable text.
+    #[default]
+    Text,
+    Json,
+    Toml,
+    /// Compact table with a single line per wallpaper.
+    Table,
+}
+
 #[derive(Debug)]
 pub struct ImageInfo {
     file: PathBuf,
@@ -43,12 +62,249 @@ impl ImageInfo {
     }
 
     pub const fn schedule_type(&self) -> &str {
-        match self.properties {
-            Properties::H24(..) => "time",
-            Properties::Solar(..) => "solar",
-            Properties::Appearance(..) => "appearance",
+        self.properties.schedule_type()
+    }
+
+    /// Format the info in a given format.
+    pub fn format(&self, format: InfoFormat) -> Result<String> {
+        match format {
+            InfoFormat::Text => Ok(self.to_string()),
+            InfoFormat::Json => serde_json::to_string_pretty(&self.report())
+                .map(|json| json + "\n")
+                .context("failed to serialize info to JSON"),
+            InfoFormat::Toml => {
+                toml::to_string(&self.report()).context("failed to serialize info to TOML")
+            }
+            InfoFormat::Table => Ok(InfoTable(slice::from_ref(self)).to_string()),
         }
     }
+
+    /// Get a serializable report of the info.
+    fn report(&self) -> InfoReport<'_> {
+        let schedule = match self.properties {
+            Properties::H24(ref props) => Some(
+                sort_time_items(&props.time_info)
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, item)| {
+                        let day_fraction = item.time.into_inner();
+                        ScheduleEntry::Time {
+                            frame: idx + 1,
+                            image: item.index,
+                            day_fraction,
+                            time: day_fraction_to_time(day_fraction).to_string(),
+                        }
+                    })
+                    .collect(),
+            ),
+            Properties::Solar(ref props) => Some(
+                sort_solar_items(&props.solar_info)
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, item)| ScheduleEntry::Solar {
+                        frame: idx + 1,
+                        image: item.index,
+                        azimuth: item.azimuth.into_inner(),
+                        altitude: item.altitude.into_inner(),
+                    })
+                    .collect(),
+            ),
+            Properties::Appearance(_) => None,
+        };
+
+        InfoReport {
+            file: &self.file,
+            size: self.size,
+            width: self.width,
+            height: self.height,
+            schedule_type: self.schedule_type(),
+            images: self.images,
+            frames: self.properties.num_frames(),
+            schedule,
+            appearance: self.properties.appearance().map(|props| AppearanceReport {
+                light: props.light,
+                dark: props.dark,
+            }),
+        }
+    }
+}
+
+/// Read info of multiple wallpapers in parallel. Results are in the same order as the paths.
+pub fn read_all(paths: &[PathBuf]) -> Vec<Result<ImageInfo>> {
+    if paths.is_empty() {
+        return Vec::new();
+    }
+    let n_threads = min(num_cpus::get(), paths.len());
+    let thread_pool = ThreadPool::new(n_threads);
+    debug!("reading info using {n_threads} threads");
+
+    let (result_tx, result_rx) = channel();
+    for (i, path) in paths.iter().cloned().enumerate() {
+        let result_tx = result_tx.clone();
+        thread_pool.execute(move || {
+            let result = ImageInfo::from_image(&path)
+                .with_context(|| format!("couldn't read {}", path.display()));
+            result_tx.send((i, result)).ok();
+        });
+    }
+    drop(result_tx);
+
+    let mut results = result_rx.iter().collect::<Vec<_>>();
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Format info of multiple wallpapers in a given format.
+pub fn format_all(infos: &[ImageInfo], format: InfoFormat) -> Result<String> {
+    match format {
+        InfoFormat::Text => Ok(infos.iter().map(ToString::to_string).join("\n")),
+        InfoFormat::Json => {
+            let reports = infos.iter().map(ImageInfo::report).collect::<Vec<_>>();
+            serde_json::to_string_pretty(&reports)
+                .map(|json| json + "\n")
+                .context("failed to serialize info to JSON")
+        }
+        InfoFormat::Toml => {
+            let reports = InfoReports {
+                wallpapers: infos.iter().map(ImageInfo::report).collect(),
+            };
+            toml::to_string(&reports).context("failed to serialize info to TOML")
+        }
+        InfoFormat::Table => Ok(InfoTable(infos).to_string()),
+    }
+}
+
+/// Find wallpapers in given directories, recursively. Files which aren't supported HEIFs are
+/// skipped. Paths which aren't directories are returned as they are.
+pub fn find_wallpapers<P: AsRef<Path>>(paths: &[P]) -> Vec<PathBuf> {
+    let mut wallpapers = Vec::new();
+    for path in paths {
+        let path = path.as_ref();
+        if path.is_dir() {
+            find_wallpapers_in_dir(path, &mut wallpapers);
+        } else {
+            wallpapers.push(path.to_path_buf());
+        }
+    }
+    wallpapers
+}
+
+fn find_wallpapers_in_dir(dir_path: &Path, wallpapers: &mut Vec<PathBuf>) {
+    let Ok(dir_entries) = dir_path.read_dir() else {
+        debug!("couldn't read directory {}, skipping", dir_path.display());
+        return;
+    };
+    let mut paths = dir_entries
+        .flatten()
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            find_wallpapers_in_dir(&path, wallpapers);
+        } else if let Err(err) = heif::validate_file(&path) {
+            debug!("skipping {}: {err:#}", path.display());
+        } else {
+            wallpapers.push(path);
+        }
+    }
+}
+
+/// Compact table with info of multiple wallpapers, a single line per wallpaper.
+struct InfoTable<'a>(&'a [ImageInfo]);
+
+impl Display for InfoTable<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let names = self
+            .0
+            .iter()
+            .map(|info| {
+                info.file.file_name().map_or_else(
+                    || info.file.display().to_string(),
+                    |name| name.to_string_lossy().into_owned(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let name_width = names.iter().map(String::len).max().unwrap_or(0).max(4);
+
+        writeln!(
+            f,
+            "{:<name_width$}  Schedule    Resolution  Frames  Appearance  {:>10}",
+            "Name", "Size"
+        )?;
+        for (info, name) in self.0.iter().zip(names) {
+            writeln!(
+                f,
+                "{name:<name_width$}  {:<10}  {:<10}  {:>6}  {:<10}  {:>10}",
+                info.schedule_type(),
+                format!("{}x{}", info.width, info.height),
+                info.properties.num_frames(),
+                if info.properties.appearance().is_some() {
+                    "yes"
+                } else {
+                    "no"
+                },
+                format_size(info.size),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[allow(
+    clippy::cast_precision_loss,
+    reason = "sizes are displayed with limited precision anyway"
+)]
+fn format_size(size: u64) -> String {
+    format!("{:.1} MB", size as f64 / (1024.0 * 1024.0))
+}
+
+/// Machine readable info of multiple wallpapers. TOML document can't be an array.
+#[derive(Serialize)]
+struct InfoReports<'a> {
+    wallpapers: Vec<InfoReport<'a>>,
+}
+
+/// Machine readable form of `ImageInfo`.
+#[derive(Serialize)]
+struct InfoReport<'a> {
+    file: &'a Path,
+    size: u64,
+    width: u32,
+    height: u32,
+    schedule_type: &'a str,
+    images: usize,
+    frames: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schedule: Option<Vec<ScheduleEntry>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    appearance: Option<AppearanceReport>,
+}
+
+/// Images used for light and dark appearance.
+#[derive(Serialize)]
+struct AppearanceReport {
+    light: i32,
+    dark: i32,
+}
+
+/// Single frame of the wallpaper schedule, in the order of display.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ScheduleEntry {
+    Time {
+        frame: usize,
+        image: usize,
+        day_fraction: f64,
+        time: String,
+    },
+    Solar {
+        frame: usize,
+        image: usize,
+        azimuth: f64,
+        altitude: f64,
+    },
 }
 
 impl Display for ImageInfo {
@@ -0,0 +1,419 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use ordered_float::NotNan;
+
+use crate::{
+    heif,
+    schedule::time::day_fraction_to_time,
+    wallpaper::{
+        metadata::get_apple_desktop_metadata_from_heif,
+        properties::{Properties, PropertiesAppearance, PropertiesH24, PropertiesSolar},
+    },
+};
+
+/// How serious a problem found in the wallpaper is.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    /// The wallpaper works, but probably not as its author intended.
+    Warning,
+    /// The wallpaper can't be displayed correctly.
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Single problem found in the wallpaper metadata.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Problem {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Problem {
+    fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+}
+
+/// All problems found in a single wallpaper.
+#[derive(Debug)]
+pub struct LintReport {
+    file: PathBuf,
+    problems: Vec<Problem>,
+}
+
+impl LintReport {
+    /// Check the wallpaper at a given path.
+    pub fn from_image<P: AsRef<Path>>(image_path: P) -> Result<Self> {
+        let image_path = image_path.as_ref();
+        let heif_ctx = heif::from_file(image_path)?;
+        let num_images = heif_ctx.image_ids().len();
+        let properties = get_apple_desktop_metadata_from_heif(&heif_ctx)
+            .and_then(|metadata| Properties::from_apple_desktop(&metadata));
+
+        let problems = match properties {
+            Ok(properties) => lint_properties(&properties, num_images),
+            Err(err) => vec![Problem::error(format!(
+                "invalid wallpaper metadata: {err:#}"
+            ))],
+        };
+
+        Ok(Self {
+            file: image_path.to_path_buf(),
+            problems,
+        })
+    }
+
+    /// Count the problems with a given severity.
+    pub fn count(&self, severity: Severity) -> usize {
+        self.problems
+            .iter()
+            .filter(|problem| problem.severity == severity)
+            .count()
+    }
+}
+
+impl Display for LintReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.problems.is_empty() {
+            return writeln!(f, "{}: ok", self.file.display());
+        }
+        writeln!(f, "{}:", self.file.display())?;
+        for problem in &self.problems {
+            writeln!(f, "  {}: {}", problem.severity, problem.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check wallpaper properties for consistency with each other and with the number of images
+/// actually present in the file. Errors are sorted before warnings.
+pub fn lint_properties(properties: &Properties, num_images: usize) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    if num_images == 0 {
+        problems.push(Problem::error("wallpaper contains no images"));
+    }
+
+    let (appearance, used_images) = match properties {
+        Properties::H24(props) => {
+            lint_h24(props, num_images, &mut problems);
+            (
+                props.appearance.as_ref(),
+                props.time_info.iter().map(|item| item.index).collect(),
+            )
+        }
+        Properties::Solar(props) => {
+            lint_solar(props, num_images, &mut problems);
+            (
+                props.appearance.as_ref(),
+                props.solar_info.iter().map(|item| item.index).collect(),
+            )
+        }
+        Properties::Appearance(props) => (Some(props), BTreeSet::new()),
+    };
+
+    let mut used_images = used_images;
+    if let Some(appearance) = appearance {
+        lint_appearance(appearance, num_images, &mut problems);
+        used_images.extend(
+            [appearance.light, appearance.dark]
+                .into_iter()
+                .filter_map(|index| usize::try_from(index).ok()),
+        );
+    }
+    for index in (0..num_images).filter(|index| !used_images.contains(index)) {
+        problems.push(Problem::warning(format!("image {index} is never used")));
+    }
+
+    problems.sort_by_key(|problem| std::cmp::Reverse(problem.severity));
+    problems
+}
+
+fn lint_h24(props: &PropertiesH24, num_images: usize, problems: &mut Vec<Problem>) {
+    if props.time_info.is_empty() {
+        problems.push(Problem::error("schedule has no frames"));
+        return;
+    }
+
+    let mut seen_times = HashMap::new();
+    let mut first_half = false;
+    let mut second_half = false;
+    for (frame, item) in (1..).zip(&props.time_info) {
+        lint_image_index(frame, item.index, num_images, problems);
+
+        let time = item.time.into_inner();
+        if !(0.0..=1.0).contains(&time) {
+            problems.push(Problem::error(format!(
+                "frame {frame} has time {time} outside of the day (expected 0.0 to 1.0)"
+            )));
+            continue;
+        }
+        if time < 0.5 {
+            first_half = true;
+        } else {
+            second_half = true;
+        }
+        if let Some(other_frame) = seen_times.insert(item.time, frame) {
+            problems.push(Problem::warning(format!(
+                "frames {other_frame} and {frame} have the same time {}",
+                day_fraction_to_time(time)
+            )));
+        }
+    }
+
+    if !first_half {
+        problems.push(Problem::warning("no frames in the first half of the day"));
+    }
+    if !second_half {
+        problems.push(Problem::warning("no frames in the second half of the day"));
+    }
+}
+
+fn lint_solar(props: &PropertiesSolar, num_images: usize, problems: &mut Vec<Problem>) {
+    if props.solar_info.is_empty() {
+        problems.push(Problem::error("schedule has no frames"));
+        return;
+    }
+
+    let mut seen_positions: HashMap<(NotNan<f64>, NotNan<f64>), usize> = HashMap::new();
+    let mut before_noon = false;
+    let mut after_noon = false;
+    for (frame, item) in (1..).zip(&props.solar_info) {
+        lint_image_index(frame, item.index, num_images, problems);
+
+        let altitude = item.altitude.into_inner();
+        let azimuth = item.azimuth.into_inner();
+        if !(-90.0..=90.0).contains(&altitude) {
+            problems.push(Problem::error(format!(
+                "frame {frame} has sun altitude {altitude}° (expected -90° to 90°)"
+            )));
+        }
+        if !(0.0..=360.0).contains(&azimuth) {
+            problems.push(Problem::error(format!(
+                "frame {frame} has sun azimuth {azimuth}° (expected 0° to 360°)"
+            )));
+            continue;
+        }
+        if azimuth < 180.0 {
+            before_noon = true;
+        } else {
+            after_noon = true;
+        }
+        if let Some(other_frame) = seen_positions.insert((item.altitude, item.azimuth), frame) {
+            problems.push(Problem::warning(format!(
+                "frames {other_frame} and {frame} have the same sun position"
+            )));
+        }
+    }
+
+    if !before_noon {
+        problems.push(Problem::warning("no frames before solar noon"));
+    }
+    if !after_noon {
+        problems.push(Problem::warning("no frames after solar noon"));
+    }
+}
+
+fn lint_appearance(
+    appearance: &PropertiesAppearance,
+    num_images: usize,
+    problems: &mut Vec<Problem>,
+) {
+    for (name, index) in [("light", appearance.light), ("dark", appearance.dark)] {
+        if usize::try_from(index).map_or(true, |index| index >= num_images) {
+            problems.push(Problem::error(format!(
+                "{name} appearance uses image {index}, but the wallpaper has {num_images} images"
+            )));
+        }
+    }
+}
+
+fn lint_image_index(frame: usize, index: usize, num_images: usize, problems: &mut Vec<Problem>) {
+    if index >= num_images {
+        problems.push(Problem::error(format!(
+            "frame {frame} uses image {index}, but the wallpaper has {num_images} images"
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallpaper::properties::{SolarItem, TimeItem};
+    use rstest::*;
+
+    fn time_properties(times: &[(usize, f64)]) -> Properties {
+        Properties::H24(PropertiesH24 {
+            appearance: None,
+            time_info: times
+                .iter()
+                .map(|&(index, time)| TimeItem {
+                    index,
+                    time: not_nan!(time),
+                })
+                .collect(),
+        })
+    }
+
+    fn solar_properties(positions: &[(usize, f64, f64)]) -> Properties {
+        Properties::Solar(PropertiesSolar {
+            appearance: None,
+            solar_info: positions
+                .iter()
+                .map(|&(index, altitude, azimuth)| SolarItem {
+                    index,
+                    altitude: not_nan!(altitude),
+                    azimuth: not_nan!(azimuth),
+                })
+                .collect(),
+        })
+    }
+
+    fn messages(problems: &[Problem]) -> Vec<(Severity, &str)> {
+        problems
+            .iter()
+            .map(|problem| (problem.severity, problem.message.as_str()))
+            .collect()
+    }
+
+    #[rstest]
+    #[case::time(time_properties(&[(0, 0.25), (1, 0.75)]))]
+    #[case::solar(solar_properties(&[(0, 30.0, 100.0), (1, -10.0, 270.0)]))]
+    #[case::appearance(Properties::Appearance(PropertiesAppearance { dark: 1, light: 0 }))]
+    fn test_lint_properties_ok(#[case] properties: Properties) {
+        assert_eq!(lint_properties(&properties, 2), vec![]);
+    }
+
+    #[rstest]
+    #[case::time(time_properties(&[]))]
+    #[case::solar(solar_properties(&[]))]
+    fn test_lint_properties_empty_schedule(#[case] properties: Properties) {
+        let problems = lint_properties(&properties, 1);
+        assert_eq!(
+            messages(&problems),
+            vec![
+                (Severity::Error, "schedule has no frames"),
+                (Severity::Warning, "image 0 is never used"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_properties_no_images() {
+        let problems = lint_properties(&time_properties(&[(0, 0.25), (0, 0.75)]), 0);
+        assert_eq!(
+            messages(&problems),
+            vec![
+                (Severity::Error, "wallpaper contains no images"),
+                (
+                    Severity::Error,
+                    "frame 1 uses image 0, but the wallpaper has 0 images"
+                ),
+                (
+                    Severity::Error,
+                    "frame 2 uses image 0, but the wallpaper has 0 images"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_properties_time_problems() {
+        let properties = time_properties(&[(0, 0.1), (3, 0.2), (1, 0.1), (1, 1.5)]);
+        let problems = lint_properties(&properties, 3);
+        assert_eq!(
+            messages(&problems),
+            vec![
+                (
+                    Severity::Error,
+                    "frame 2 uses image 3, but the wallpaper has 3 images"
+                ),
+                (
+                    Severity::Error,
+                    "frame 4 has time 1.5 outside of the day (expected 0.0 to 1.0)"
+                ),
+                (
+                    Severity::Warning,
+                    "frames 1 and 3 have the same time 02:24:00"
+                ),
+                (Severity::Warning, "no frames in the second half of the day"),
+                (Severity::Warning, "image 2 is never used"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_properties_solar_problems() {
+        let properties = solar_properties(&[
+            (0, 95.0, 180.0),
+            (1, 10.0, 400.0),
+            (0, 20.0, 200.0),
+            (1, 20.0, 200.0),
+        ]);
+        let problems = lint_properties(&properties, 2);
+        assert_eq!(
+            messages(&problems),
+            vec![
+                (
+                    Severity::Error,
+                    "frame 1 has sun altitude 95° (expected -90° to 90°)"
+                ),
+                (
+                    Severity::Error,
+                    "frame 2 has sun azimuth 400° (expected 0° to 360°)"
+                ),
+                (
+                    Severity::Warning,
+                    "frames 3 and 4 have the same sun position"
+                ),
+                (Severity::Warning, "no frames before solar noon"),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::negative(PropertiesAppearance { dark: -1, light: 0 }, "dark appearance uses image -1, but the wallpaper has 2 images")]
+    #[case::too_big(PropertiesAppearance { dark: 1, light: 2 }, "light appearance uses image 2, but the wallpaper has 2 images")]
+    fn test_lint_properties_appearance_out_of_range(
+        #[case] appearance: PropertiesAppearance,
+        #[case] expected: &str,
+    ) {
+        let properties = Properties::H24(PropertiesH24 {
+            appearance: Some(appearance),
+            time_info: vec![
+                TimeItem {
+                    index: 0,
+                    time: not_nan!(0.25),
+                },
+                TimeItem {
+                    index: 1,
+                    time: not_nan!(0.75),
+                },
+            ],
+        });
+        let problems = lint_properties(&properties, 2);
+        assert_eq!(messages(&problems), vec![(Severity::Error, expected)]);
+    }
+}
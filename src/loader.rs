@@ -1,66 +1,506 @@
 use std::{fs, hash::Hasher, io::Read, path::Path};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use log::{debug, warn};
 
-use crate::wallpaper::Wallpaper;
+use crate::cache::{now_unix_seconds, EntryManifest, HashIndex, LastWallpaper};
+use crate::image::{resize::Fit, Encoding, ImageFormat};
+use crate::wallpaper::{unpack_properties_only, ImageSource, Wallpaper};
 use crate::{cache::Cache, wallpaper::unpack};
 
+/// How often the last use time of a cache entry is updated, in seconds.
+pub const LAST_USED_RESOLUTION: u64 = 24 * 60 * 60;
+
 /// Unpacks HEIF files and loads them into `Wallpaper` structs, while transparently caching them.
 #[derive(Debug)]
 pub struct WallpaperLoader {
     cache: Cache,
+    hash_index: HashIndex,
+    fit: Option<Fit>,
+    encoding: Encoding,
+    lazy: bool,
+    max_size: Option<u64>,
+}
+
+/// Result of verifying the loader cache.
+#[derive(Debug)]
+pub struct CacheVerification {
+    /// Keys of all checked entries along with their state.
+    pub entries: Vec<(String, EntryState)>,
+    /// Number of removed temporary files left behind by interrupted unpacking.
+    pub removed_temp_files: usize,
+}
+
+/// State of a verified cache entry.
+#[derive(Debug, PartialEq, Eq)]
+pub enum EntryState {
+    /// Entry is complete.
+    Intact,
+    /// Entry was broken in a described way and was unpacked again.
+    Repaired(String),
+    /// Entry was broken in a described way and couldn't be unpacked again, so it was removed.
+    Removed(String),
+}
+
+/// Wallpaper stored in the loader cache.
+#[derive(Debug)]
+pub struct CachedWallpaper {
+    /// Key of the cache entry.
+    pub key: String,
+    /// Manifest of the entry, missing for entries created by older versions.
+    pub manifest: Option<EntryManifest>,
+    /// Size of the entry in bytes.
+    pub size: u64,
+    /// Time the entry was last used, in seconds since the Unix epoch.
+    pub last_used: u64,
 }
 
 impl WallpaperLoader {
-    pub fn new() -> Self {
-        Self {
-            cache: Cache::find("wallpapers"),
-        }
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            cache: Cache::find("wallpapers")?,
+            hash_index: HashIndex::find()?,
+            fit: None,
+            encoding: Encoding::default(),
+            lazy: false,
+            max_size: None,
+        })
+    }
+
+    /// Resize the unpacked images to a given target size.
+    pub const fn with_fit(mut self, fit: Option<Fit>) -> Self {
+        self.fit = fit;
+        self
+    }
+
+    /// Write the unpacked images using a given encoding.
+    pub const fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Don't unpack the images upfront, only when they are needed.
+    pub const fn with_lazy(mut self, lazy: bool) -> Self {
+        self.lazy = lazy;
+        self
+    }
+
+    /// Limit the total size of the cache, in bytes.
+    pub const fn with_max_size(mut self, max_size: Option<u64>) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Get hash of a given wallpaper file, identifying it in the cache.
+    pub fn hash<P: AsRef<Path>>(&mut self, path: P) -> Result<String> {
+        self.hash_index
+            .get_or_hash(&path, |path| hash_file(path))
+            .context("wallpaper hashing failed")
     }
 
     /// Load given file into `Wallpaper` struct.
     ///
     /// Each loaded file is persistently cached and will be loaded from cache if requested again.
-    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Wallpaper {
-        let hash = hash_file(&path).expect("wallpaper hashing failed");
-        let cache_dir = self.cache.entry(&hash);
-        if cache_dir.read_dir().unwrap().next().is_none() {
-            unpack(&path, &cache_dir).expect("wallpaper unpacking failed");
+    /// Resized images are cached separately for each target size, as are images in different
+    /// formats. In the lazy mode only the properties are unpacked upfront, images are unpacked
+    /// when they are first requested from the wallpaper.
+    ///
+    /// The entry manifest is only updated when the entry changes or its last use time is older than
+    /// `LAST_USED_RESOLUTION`. The cache is shrunk to its size limit at the same time, by removing
+    /// least recently used wallpapers.
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<Wallpaper> {
+        let hash = self.hash(&path)?;
+        let cache_key = get_cache_key(&hash, self.fit, self.encoding);
+        let source = ImageSource {
+            wall_path: path.as_ref().to_path_buf(),
+            fit: self.fit,
+            encoding: self.encoding,
+        };
+
+        let cached_wallpaper = self
+            .cache
+            .entries
+            .contains(&cache_key)
+            .then(|| Wallpaper::load(self.cache.get_entry(&cache_key), Some(source.clone())));
+        let (wallpaper, mut changed) = match cached_wallpaper {
+            Some(Ok(wallpaper)) => (wallpaper, false),
+            Some(Err(err)) => {
+                warn!("cache entry {cache_key} is broken, unpacking again: {err:#}");
+                let wallpaper = self
+                    .unpack_entry(&cache_key, &source)
+                    .context("wallpaper unpacking failed")?;
+                (wallpaper, true)
+            }
+            None => {
+                let wallpaper = self
+                    .unpack_entry(&cache_key, &source)
+                    .context("wallpaper unpacking failed")?;
+                (wallpaper, true)
+            }
+        };
+        if !self.lazy {
+            // Cache may have been partially unpacked in the lazy mode before.
+            changed |= !wallpaper.missing_images().is_empty();
+            wallpaper
+                .unpack_missing()
+                .context("wallpaper unpacking failed")?;
+        }
+
+        let manifest = EntryManifest {
+            source: path
+                .as_ref()
+                .canonicalize()
+                .unwrap_or_else(|_| path.as_ref().into()),
+            hash,
+            frames: wallpaper.properties.num_frames(),
+            fit: self.fit,
+            encoding: self.encoding,
+            last_used: now_unix_seconds(),
+        };
+        if changed || is_manifest_outdated(self.cache.manifest(&cache_key).as_ref(), &manifest) {
+            if let Err(err) = self.cache.save_manifest(&cache_key, &manifest) {
+                debug!("couldn't save cache manifest: {err:#}");
+            }
+            if let Some(max_size) = self.max_size {
+                if let Err(err) = self.evict(max_size, &manifest.hash) {
+                    warn!("couldn't shrink the cache to its size limit: {err:#}");
+                }
+            }
+        }
+
+        Ok(wallpaper)
+    }
+
+    /// Unpack a wallpaper into the cache entry with a given key, replacing what's already there.
+    fn unpack_entry(&mut self, cache_key: &str, source: &ImageSource) -> Result<Wallpaper> {
+        let lazy = self.lazy;
+        let cache_dir = self.cache.fill_entry(cache_key, |dir| {
+            if lazy {
+                unpack_properties_only(&source.wall_path, dir)
+            } else {
+                unpack(&source.wall_path, dir, source.fit, source.encoding)
+            }
+        })?;
+        Wallpaper::load(cache_dir, Some(source.clone()))
+    }
+
+    /// Check all wallpapers in the cache, unpacking the broken ones again.
+    ///
+    /// Wallpapers which can't be unpacked again, because their source file is gone or was changed,
+    /// are removed from the cache. Temporary files left behind by interrupted unpacking are
+    /// removed as well.
+    pub fn verify_cache(&mut self) -> Result<CacheVerification> {
+        let removed_temp_files = self.cache.remove_stale_temp_files();
+        let mut keys = self.cache.entries.iter().cloned().collect::<Vec<_>>();
+        keys.sort();
+
+        let mut entries = Vec::with_capacity(keys.len());
+        for key in keys {
+            let state = match self.check_entry(&key) {
+                Ok(()) => EntryState::Intact,
+                Err(problem) => match self.repair_entry(&key) {
+                    Ok(()) => EntryState::Repaired(format!("{problem:#}")),
+                    Err(err) => {
+                        self.cache.remove_entry(&key)?;
+                        EntryState::Removed(format!("{problem:#}; {err:#}"))
+                    }
+                },
+            };
+            entries.push((key, state));
+        }
+
+        Ok(CacheVerification {
+            entries,
+            removed_temp_files,
+        })
+    }
+
+    /// Check whether the cache entry with a given key has valid properties and all images.
+    /// In the lazy mode, images may be missing.
+    fn check_entry(&self, cache_key: &str) -> Result<()> {
+        let wallpaper = Wallpaper::load(self.cache.get_entry(cache_key), None)?;
+        let broken_images = wallpaper
+            .images
+            .iter()
+            .enumerate()
+            .filter(|(_, path)| {
+                fs::metadata(path).map_or(!self.lazy, |metadata| metadata.len() == 0)
+            })
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        if !broken_images.is_empty() {
+            bail!("images {broken_images:?} are missing or empty");
+        }
+        Ok(())
+    }
+
+    /// Unpack the cache entry with a given key again from the source in its manifest.
+    fn repair_entry(&mut self, cache_key: &str) -> Result<()> {
+        let manifest = self
+            .cache
+            .manifest(cache_key)
+            .context("entry has no manifest, its source is unknown")?;
+        if hash_file(&manifest.source).ok().as_ref() != Some(&manifest.hash) {
+            bail!(
+                "source file {} is missing or has changed",
+                manifest.source.display()
+            );
+        }
+        let source = ImageSource {
+            wall_path: manifest.source,
+            fit: manifest.fit,
+            encoding: manifest.encoding,
+        };
+        self.unpack_entry(cache_key, &source)?;
+        Ok(())
+    }
+
+    /// Get all wallpapers in the cache, most recently used first.
+    pub fn cached_wallpapers(&self) -> Vec<CachedWallpaper> {
+        let mut cached_wallpapers = self
+            .cache
+            .entries
+            .iter()
+            .map(|key| {
+                let manifest = self.cache.manifest(key);
+                let last_used = manifest.as_ref().map_or_else(
+                    || self.cache.entry_modified(key),
+                    |manifest| manifest.last_used,
+                );
+                CachedWallpaper {
+                    key: key.clone(),
+                    size: self.cache.entry_size(key),
+                    manifest,
+                    last_used,
+                }
+            })
+            .collect::<Vec<_>>();
+        cached_wallpapers.sort_by(|a, b| {
+            b.last_used
+                .cmp(&a.last_used)
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        cached_wallpapers
+    }
+
+    /// Remove least recently used wallpapers until the cache fits in a given size.
+    /// Wallpaper with a given hash and the last used wallpaper are never removed.
+    fn evict(&mut self, max_size: u64, current_hash: &str) -> Result<()> {
+        let last_wall_path = LastWallpaper::find()?
+            .get()?
+            .and_then(|path| path.canonicalize().ok());
+        let cached_wallpapers = self.cached_wallpapers();
+        let protected = cached_wallpapers
+            .iter()
+            .filter(|cached| {
+                get_entry_hash(&cached.key) == current_hash
+                    || cached.manifest.as_ref().map(|manifest| &manifest.source)
+                        == last_wall_path.as_ref()
+            })
+            .map(|cached| cached.key.clone())
+            .collect::<Vec<_>>();
+
+        for key in select_evicted(&cached_wallpapers, max_size, &protected) {
+            debug!("cache size limit exceeded, removing {key}");
+            self.cache.remove_entry(&key)?;
         }
-        Wallpaper::load(&cache_dir).expect("malformed wallpaper cache")
+        Ok(())
     }
 
     /// Clear the wallpaper loader cache. Optionally skips one wallpaper with a given pahth.
-    pub fn clear_cache<P: AsRef<Path>>(&mut self, skip_wall_path: Option<P>) {
+    /// All resized variants of the skipped wallpaper are skipped as well.
+    pub fn clear_cache<P: AsRef<Path>>(&mut self, skip_wall_path: Option<P>) -> Result<()> {
         let mut entries_to_clear = self.cache.entries.clone();
 
         if let Some(skip_wall_path) = skip_wall_path {
-            let skip_wall_hash = hash_file(&skip_wall_path).expect("wallpaper hashing failed");
-            entries_to_clear.remove(&skip_wall_hash);
+            let skip_wall_hash = self
+                .hash_index
+                .get_or_hash(&skip_wall_path, |path| hash_file(path))
+                .context("wallpaper hashing failed")?;
+            entries_to_clear.retain(|entry| get_entry_hash(entry) != skip_wall_hash);
         }
 
         for hash in entries_to_clear {
-            self.cache.remove_entry(&hash);
+            self.cache.remove_entry(&hash)?;
+        }
+        Ok(())
+    }
+}
+
+/// Get cache key for a wallpaper with a given hash, resized using a given fit and written using
+/// a given encoding. PNG compression level doesn't affect the images, so it's not a part of the key.
+fn get_cache_key(hash: &str, fit: Option<Fit>, encoding: Encoding) -> String {
+    let fit_suffix = fit.map(|fit| format!("-{fit}")).unwrap_or_default();
+    let format_suffix = match encoding.format {
+        ImageFormat::Png => String::new(),
+        ImageFormat::Jpeg => format!("-jpeg{}", encoding.quality),
+        format => format!("-{format}"),
+    };
+    format!("{hash}{fit_suffix}{format_suffix}")
+}
+
+/// Check whether a saved entry manifest differs from the current one in anything but the last use
+/// time, or its last use time is at least `LAST_USED_RESOLUTION` old.
+fn is_manifest_outdated(saved: Option<&EntryManifest>, current: &EntryManifest) -> bool {
+    saved.is_none_or(|saved| {
+        let same_entry = EntryManifest {
+            last_used: current.last_used,
+            ..saved.clone()
+        } == *current;
+        !same_entry || current.last_used.saturating_sub(saved.last_used) >= LAST_USED_RESOLUTION
+    })
+}
+
+/// Select wallpapers to remove from the cache so it fits in a given size, least recently used
+/// first. Wallpapers with protected keys are never selected.
+fn select_evicted(
+    cached_wallpapers: &[CachedWallpaper],
+    max_size: u64,
+    protected: &[String],
+) -> Vec<String> {
+    let mut total_size: u64 = cached_wallpapers.iter().map(|cached| cached.size).sum();
+    let mut by_last_use = cached_wallpapers
+        .iter()
+        .filter(|cached| !protected.contains(&cached.key))
+        .collect::<Vec<_>>();
+    by_last_use.sort_by_key(|cached| cached.last_used);
+
+    let mut evicted = Vec::new();
+    for cached in by_last_use {
+        if total_size <= max_size {
+            break;
         }
+        total_size -= cached.size;
+        evicted.push(cached.key.clone());
     }
+    evicted
+}
+
+/// Get hash of the wallpaper from a cache key.
+fn get_entry_hash(cache_key: &str) -> &str {
+    cache_key
+        .split_once('-')
+        .map_or(cache_key, |(hash, _)| hash)
 }
 
 fn hash_file<P: AsRef<Path>>(path: P) -> Result<String> {
-    const BUFFER_LEN: usize = 1024;
-    let mut buffer = [0u8; BUFFER_LEN];
+    const BUFFER_LEN: usize = 1024 * 1024;
+    let mut buffer = vec![0u8; BUFFER_LEN];
 
     let mut file = fs::File::open(&path)?;
     let mut hasher = seahash::SeaHasher::new();
 
     loop {
         let read_count = file.read(&mut buffer)?;
-        hasher.write(&buffer[..read_count]);
-
-        if read_count != BUFFER_LEN {
+        if read_count == 0 {
             break;
         }
+        hasher.write(&buffer[..read_count]);
     }
 
     let hash_bytes = hasher.finish();
     Ok(format!("{hash_bytes:x}"))
 }
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+    use crate::image::resize::FitMode;
+
+    #[rstest]
+    fn test_get_cache_key() {
+        let fit = Fit {
+            width: 2560,
+            height: 1440,
+            mode: FitMode::Cover,
+        };
+        let png = Encoding::default();
+        assert_eq!(get_cache_key("a81fb8b5", None, png), "a81fb8b5");
+        assert_eq!(
+            get_cache_key("a81fb8b5", Some(fit), png),
+            "a81fb8b5-2560x1440-cover"
+        );
+    }
+
+    #[rstest]
+    #[case(ImageFormat::Png, "a81fb8b5")]
+    #[case(ImageFormat::Jpeg, "a81fb8b5-jpeg80")]
+    #[case(ImageFormat::Webp, "a81fb8b5-webp")]
+    #[case(ImageFormat::Ppm, "a81fb8b5-ppm")]
+    fn test_get_cache_key_format(#[case] format: ImageFormat, #[case] expected: &str) {
+        let encoding = Encoding {
+            format,
+            quality: 80,
+            compression: 9,
+        };
+        assert_eq!(get_cache_key("a81fb8b5", None, encoding), expected);
+    }
+
+    fn cached(key: &str, size: u64, last_used: u64) -> CachedWallpaper {
+        CachedWallpaper {
+            key: key.to_owned(),
+            manifest: None,
+            size,
+            last_used,
+        }
+    }
+
+    #[rstest]
+    #[case(300, &[], &[])]
+    #[case(250, &[], &["old"])]
+    #[case(150, &[], &["old", "middle"])]
+    #[case(150, &["old"], &["middle", "new"])]
+    #[case(0, &["old", "middle", "new"], &[])]
+    fn test_select_evicted(
+        #[case] max_size: u64,
+        #[case] protected: &[&str],
+        #[case] expected: &[&str],
+    ) {
+        let cached_wallpapers = [
+            cached("new", 100, 30),
+            cached("old", 100, 10),
+            cached("middle", 100, 20),
+        ];
+        let protected = protected
+            .iter()
+            .map(|&key| key.to_owned())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            select_evicted(&cached_wallpapers, max_size, &protected),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("a81fb8b5", "a81fb8b5")]
+    #[case("a81fb8b5-2560x1440-cover", "a81fb8b5")]
+    #[case("a81fb8b5-2560x1440-cover-jpeg90", "a81fb8b5")]
+    fn test_get_entry_hash(#[case] cache_key: &str, #[case] expected: &str) {
+        assert_eq!(get_entry_hash(cache_key), expected);
+    }
+
+    fn manifest(hash: &str, last_used: u64) -> EntryManifest {
+        EntryManifest {
+            source: "/walls/mojave.heic".into(),
+            hash: hash.to_owned(),
+            frames: 16,
+            fit: None,
+            encoding: Encoding::default(),
+            last_used,
+        }
+    }
+
+    #[rstest]
+    #[case(None, true)]
+    #[case(Some(manifest("a81fb8b5", 100_000)), false)]
+    #[case(Some(manifest("a81fb8b5", 100_000 - LAST_USED_RESOLUTION + 1)), false)]
+    #[case(Some(manifest("a81fb8b5", 100_000 - LAST_USED_RESOLUTION)), true)]
+    #[case(Some(manifest("3c2de9f0", 100_000)), true)]
+    fn test_is_manifest_outdated(#[case] saved: Option<EntryManifest>, #[case] expected: bool) {
+        let current = manifest("a81fb8b5", 100_000);
+        assert_eq!(is_manifest_outdated(saved.as_ref(), &current), expected);
+    }
+}
@@ -1,31 +1,47 @@
 #[macro_use]
 mod macros;
 mod actions;
+mod animation;
 mod appearance;
 mod cache;
+mod cache_list;
 mod cli;
+mod clock;
 mod config;
 mod constants;
+mod doctor;
 mod geo;
 mod geoclue;
 mod heif;
+mod hooks;
+mod image;
 mod info;
+mod lint;
 mod loader;
+mod outputs;
+mod overrides;
 mod pidfile;
 mod schedule;
+mod selection;
 mod setter;
 mod signals;
+mod terminal;
+mod thumbnails;
 mod wallpaper;
 
 use std::sync::mpsc::channel;
+use std::time::Duration;
 
 use anyhow::Result;
+use chrono::{Local, NaiveDate};
 use clap::Parser;
 use signal_hook::{
-    consts::signal::{SIGINT, SIGQUIT, SIGTERM},
+    consts::signal::{SIGCHLD, SIGINT, SIGQUIT, SIGTERM},
     iterator::Signals,
 };
-use signals::{start_appearance_change_handler, start_signal_handler, WakeEvent};
+use signals::{
+    start_appearance_change_handler, start_child_exit_handler, start_signal_handler, WakeEvent,
+};
 
 impl From<cli::CliAppearance> for appearance::Appearance {
     fn from(cli: cli::CliAppearance) -> Self {
@@ -36,6 +52,52 @@ impl From<cli::CliAppearance> for appearance::Appearance {
     }
 }
 
+impl From<cli::CliImageFormat> for image::ImageFormat {
+    fn from(cli: cli::CliImageFormat) -> Self {
+        match cli {
+            cli::CliImageFormat::Png => Self::Png,
+            cli::CliImageFormat::Jpeg => Self::Jpeg,
+            cli::CliImageFormat::Webp => Self::Webp,
+            cli::CliImageFormat::Ppm => Self::Ppm,
+        }
+    }
+}
+
+impl From<cli::CliFrameBound> for selection::FrameBound {
+    fn from(cli: cli::CliFrameBound) -> Self {
+        match cli {
+            cli::CliFrameBound::Number(number) => Self::Number(number),
+            cli::CliFrameBound::Time(time) => Self::Time(time),
+        }
+    }
+}
+
+impl From<cli::CliInfoFormat> for info::InfoFormat {
+    fn from(cli: cli::CliInfoFormat) -> Self {
+        match cli {
+            cli::CliInfoFormat::Text => Self::Text,
+            cli::CliInfoFormat::Json => Self::Json,
+            cli::CliInfoFormat::Toml => Self::Toml,
+            cli::CliInfoFormat::Table => Self::Table,
+        }
+    }
+}
+
+/// Get pacing of the preview, realistic one starting at the midnight of a given day or today.
+fn preview_pacing(
+    realistic: bool,
+    delay: u64,
+    speed: f64,
+    date: Option<NaiveDate>,
+) -> clock::PreviewPacing {
+    if realistic {
+        let date = date.unwrap_or_else(|| Local::now().date_naive());
+        clock::PreviewPacing::Realistic(clock::VirtualClock::starting_on(date, speed))
+    } else {
+        clock::PreviewPacing::Even(Duration::from_millis(delay))
+    }
+}
+
 fn main() -> Result<()> {
     let (wake_tx, wake_rx) = channel::<WakeEvent>();
 
@@ -48,26 +110,95 @@ fn main() -> Result<()> {
         .init();
 
     if matches!(args.action, cli::Action::Set { daemon: true, .. }) {
+        start_child_exit_handler(Signals::new([SIGCHLD])?, wake_tx.clone());
         start_appearance_change_handler(wake_tx);
     }
 
     match args.action {
-        cli::Action::Info { file } => actions::info(file),
+        cli::Action::Info {
+            paths,
+            format,
+            show,
+            frame,
+        } => actions::info(&paths, format.into(), show, frame),
         cli::Action::Preview {
             file,
             delay,
             repeat,
-        } => actions::preview(file, delay, repeat, &wake_rx),
-        cli::Action::Unpack { file, output } => actions::unpack(file, output),
+            terminal,
+            output,
+            crossfade,
+            width,
+            realistic,
+            speed,
+            from,
+            to,
+            frames,
+            reverse,
+            bounce,
+            date,
+        } => {
+            let selection = selection::FrameSelection {
+                from: from.map(Into::into),
+                to: to.map(Into::into),
+                frames,
+                reverse,
+                bounce,
+            };
+            let pacing = preview_pacing(realistic, delay, speed, date);
+            match output {
+                Some(output) => actions::render_preview(
+                    file,
+                    output,
+                    date,
+                    &selection,
+                    &animation::AnimationOptions {
+                        delay_ms: delay,
+                        crossfade,
+                        repeat,
+                        width,
+                    },
+                ),
+                None if terminal => {
+                    actions::preview_terminal(file, pacing, &selection, repeat, &wake_rx)
+                }
+                None => actions::preview(file, pacing, &selection, repeat, &wake_rx),
+            }
+        }
+        cli::Action::Unpack {
+            file,
+            output,
+            format,
+        } => actions::unpack(file, output, format.into()),
+        cli::Action::Thumbnails {
+            file,
+            output,
+            width,
+            columns,
+            separate,
+            format,
+        } => actions::thumbnails(file, output, width, columns, separate, format.into()),
         cli::Action::Set {
             file,
             daemon,
             appearance,
-        } => actions::set(file.as_ref(), daemon, appearance.map(Into::into), &wake_rx),
+            at,
+        } => actions::set(
+            file.as_ref(),
+            daemon,
+            appearance.map(Into::into),
+            at,
+            &wake_rx,
+        ),
         cli::Action::Unset => actions::unset(),
-        cli::Action::Clear { all } => {
-            actions::clear(all);
-            Ok(())
-        }
+        cli::Action::Doctor => actions::doctor(),
+        cli::Action::Lint { paths } => actions::lint(&paths),
+        cli::Action::Clear { all } => actions::clear(all),
+        cli::Action::Cache {
+            action: cli::CacheAction::List,
+        } => actions::cache_list(),
+        cli::Action::Cache {
+            action: cli::CacheAction::Verify,
+        } => actions::cache_verify(),
     }
 }
@@ -0,0 +1,102 @@
+use std::{fs, path::Path};
+
+use itertools::Itertools;
+
+const DRM_CLASS_DIR: &str = "/sys/class/drm";
+
+/// Display output (monitor) connected to the system.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Output {
+    /// Connector name, e.g. `DP-1` or `eDP-1`.
+    pub name: String,
+    /// Preferred resolution of the output, if known.
+    pub resolution: Option<(u32, u32)>,
+}
+
+/// Get outputs which are currently connected, as reported by the kernel DRM subsystem.
+pub fn connected_outputs() -> Vec<Output> {
+    connected_outputs_in(DRM_CLASS_DIR)
+}
+
+fn connected_outputs_in<P: AsRef<Path>>(drm_dir: P) -> Vec<Output> {
+    let Ok(drm_entries) = fs::read_dir(drm_dir) else {
+        return Vec::new();
+    };
+    drm_entries
+        .flatten()
+        .filter(|entry| {
+            fs::read_to_string(entry.path().join("status"))
+                .is_ok_and(|status| status.trim() == "connected")
+        })
+        .filter_map(|entry| {
+            let dir_name = entry.file_name().into_string().ok()?;
+            // Connector directories are named like 'card0-DP-1'.
+            let (_, name) = dir_name.split_once('-')?;
+            let resolution = fs::read_to_string(entry.path().join("modes"))
+                .ok()
+                .and_then(|modes| modes.lines().next().and_then(parse_resolution));
+            Some(Output {
+                name: name.to_owned(),
+                resolution,
+            })
+        })
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+        .collect_vec()
+}
+
+/// Parse resolution in `WIDTHxHEIGHT` format.
+pub fn parse_resolution(resolution_str: &str) -> Option<(u32, u32)> {
+    let (width, height) = resolution_str.trim().split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use rstest::*;
+
+    use super::*;
+
+    #[fixture]
+    fn drm_dir() -> TempDir {
+        let drm_dir = assert_fs::TempDir::new().unwrap();
+        for (connector, status, modes) in [
+            ("card0-DP-1", "connected", "2560x1440\n1920x1080\n"),
+            ("card0-HDMI-A-1", "disconnected", ""),
+            ("card1-eDP-1", "connected", "1920x1200\n"),
+        ] {
+            let connector_dir = drm_dir.child(connector);
+            connector_dir.child("status").write_str(status).unwrap();
+            connector_dir.child("modes").write_str(modes).unwrap();
+        }
+        drm_dir.child("version").write_str("drm 1.1.0").unwrap();
+        drm_dir
+    }
+
+    #[rstest]
+    fn test_connected_outputs(drm_dir: TempDir) {
+        assert_eq!(
+            connected_outputs_in(&drm_dir),
+            vec![
+                Output {
+                    name: "DP-1".to_owned(),
+                    resolution: Some((2560, 1440)),
+                },
+                Output {
+                    name: "eDP-1".to_owned(),
+                    resolution: Some((1920, 1200)),
+                },
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("3840x2160", Some((3840, 2160)))]
+    #[case("1920x1080\n", Some((1920, 1080)))]
+    #[case("1920x1080i", None)]
+    #[case("garbage", None)]
+    fn test_parse_resolution(#[case] resolution_str: &str, #[case] expected: Option<(u32, u32)>) {
+        assert_eq!(parse_resolution(resolution_str), expected);
+    }
+}
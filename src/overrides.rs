@@ -0,0 +1,425 @@
+use anyhow::{bail, Context, Result};
+use chrono::NaiveTime;
+use ordered_float::NotNan;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    schedule::{
+        get_image_index_order, sort_solar_items, sort_time_items,
+        time::{time_to_day_fraction, TimeOffset},
+    },
+    wallpaper::properties::{
+        Properties, PropertiesAppearance, PropertiesH24, PropertiesSolar, SolarItem, TimeItem,
+    },
+};
+
+/// Schedule type an override can force.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleType {
+    Time,
+    Solar,
+    Appearance,
+}
+
+/// Time of the day written as `HH:MM[:SS]`.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay(pub NaiveTime);
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        ["%H:%M:%S", "%H:%M"]
+            .iter()
+            .find_map(|format| NaiveTime::parse_from_str(&value, format).ok())
+            .map(Self)
+            .with_context(|| format!("expected time like '18:30', got '{value}'"))
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(value: TimeOfDay) -> Self {
+        value.0.format("%H:%M:%S").to_string()
+    }
+}
+
+/// Frame of a time based schedule replacing the embedded one.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct TimeFrame {
+    pub image: usize,
+    pub time: TimeOfDay,
+}
+
+/// Frame of a sun based schedule replacing the embedded one.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct SolarFrame {
+    pub image: usize,
+    pub altitude: NotNan<f64>,
+    pub azimuth: NotNan<f64>,
+}
+
+/// Frame displaying a different image than the wallpaper says.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct FrameRemap {
+    pub frame: usize,
+    pub image: usize,
+}
+
+/// Changes to the schedule of a single wallpaper, for fixing badly authored metadata without
+/// re-encoding the file.
+///
+/// Changes are applied in the order of the fields. Frames are numbered from 1, in the order in
+/// which they are displayed during the day.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, Debug, Default)]
+pub struct ScheduleOverride {
+    /// Time based schedule replacing the embedded one.
+    pub time: Option<Vec<TimeFrame>>,
+    /// Sun based schedule replacing the embedded one.
+    pub solar: Option<Vec<SolarFrame>>,
+    /// Schedule type to use, only switching to the appearance schedule is possible.
+    pub schedule: Option<ScheduleType>,
+    #[serde(default)]
+    pub remap: Vec<FrameRemap>,
+    /// Numbers of frames to remove.
+    #[serde(default)]
+    pub drop: Vec<usize>,
+    /// Offset of all times of the time based schedule.
+    #[serde(default)]
+    pub shift: TimeOffset,
+    /// Offset of all sun altitudes of the sun based schedule, in degrees.
+    #[serde(default)]
+    pub altitude_offset: NotNan<f64>,
+}
+
+impl ScheduleOverride {
+    /// Apply the override to wallpaper properties. Images which don't exist in the wallpaper
+    /// can't be used.
+    pub fn apply(&self, properties: Properties) -> Result<Properties> {
+        let num_images = properties.num_images();
+        let appearance = properties.appearance().cloned();
+        let mut properties = match (&self.time, &self.solar) {
+            (Some(_), Some(_)) => bail!("either time or solar frames can be given, not both"),
+            (Some(frames), None) => Properties::H24(PropertiesH24 {
+                appearance,
+                time_info: frames
+                    .iter()
+                    .map(|frame| TimeItem {
+                        index: frame.image,
+                        time: not_nan!(time_to_day_fraction(frame.time.0)),
+                    })
+                    .collect(),
+            }),
+            (None, Some(frames)) => Properties::Solar(PropertiesSolar {
+                appearance,
+                solar_info: frames
+                    .iter()
+                    .map(|frame| SolarItem {
+                        index: frame.image,
+                        altitude: frame.altitude,
+                        azimuth: frame.azimuth,
+                    })
+                    .collect(),
+            }),
+            (None, None) => properties,
+        };
+
+        if let Some(schedule) = self.schedule {
+            properties = force_schedule(properties, schedule)?;
+        }
+        properties = self.edit_frames(properties)?;
+        properties = self.shift(properties)?;
+
+        if properties.num_frames() == 0 {
+            bail!("no frames are left in the schedule");
+        }
+        if let Some(image) = get_image_index_order(&properties)
+            .into_iter()
+            .find(|image| *image >= num_images)
+        {
+            bail!("image {image} doesn't exist, the wallpaper has {num_images} images");
+        }
+        Ok(properties)
+    }
+
+    /// Remap and drop frames.
+    fn edit_frames(&self, properties: Properties) -> Result<Properties> {
+        if self.remap.is_empty() && self.drop.is_empty() {
+            return Ok(properties);
+        }
+        let properties = match properties {
+            Properties::H24(props) => Properties::H24(PropertiesH24 {
+                time_info: self.edit_items(
+                    sort_time_items(&props.time_info),
+                    |item: &mut TimeItem, image| item.index = image,
+                )?,
+                ..props
+            }),
+            Properties::Solar(props) => Properties::Solar(PropertiesSolar {
+                solar_info: self.edit_items(
+                    sort_solar_items(&props.solar_info),
+                    |item: &mut SolarItem, image| item.index = image,
+                )?,
+                ..props
+            }),
+            Properties::Appearance(_) if !self.drop.is_empty() => {
+                bail!("frames can't be dropped from an appearance schedule")
+            }
+            Properties::Appearance(props) => {
+                let mut images = [props.light, props.dark];
+                for remap in &self.remap {
+                    let slot = check_frame(remap.frame, images.len())?;
+                    images[slot] = i32::try_from(remap.image)
+                        .with_context(|| format!("image {} doesn't exist", remap.image))?;
+                }
+                Properties::Appearance(PropertiesAppearance {
+                    light: images[0],
+                    dark: images[1],
+                })
+            }
+        };
+        Ok(properties)
+    }
+
+    /// Remap and drop schedule items, given in the order in which they are displayed.
+    fn edit_items<T: Clone>(
+        &self,
+        sorted_items: Vec<&T>,
+        set_image: impl Fn(&mut T, usize),
+    ) -> Result<Vec<T>> {
+        let mut items = sorted_items.into_iter().cloned().collect::<Vec<_>>();
+        for remap in &self.remap {
+            let position = check_frame(remap.frame, items.len())?;
+            set_image(&mut items[position], remap.image);
+        }
+        let dropped = self
+            .drop
+            .iter()
+            .map(|frame| check_frame(*frame, items.len()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(items
+            .into_iter()
+            .enumerate()
+            .filter(|(position, _)| !dropped.contains(position))
+            .map(|(_, item)| item)
+            .collect())
+    }
+
+    /// Shift times or sun altitudes.
+    fn shift(&self, properties: Properties) -> Result<Properties> {
+        let shift_time = self.shift != TimeOffset::default();
+        let shift_altitude = self.altitude_offset != 0.0;
+        let properties = match properties {
+            Properties::H24(mut props) if shift_time => {
+                for item in &mut props.time_info {
+                    item.time = not_nan!(self.shift.shift_day_fraction(item.time.into_inner()));
+                }
+                Properties::H24(props)
+            }
+            Properties::Solar(mut props) if shift_altitude => {
+                for item in &mut props.solar_info {
+                    item.altitude += self.altitude_offset;
+                }
+                Properties::Solar(props)
+            }
+            _ if shift_time => bail!("shift only applies to time based schedules"),
+            _ if shift_altitude => bail!("altitude offset only applies to sun based schedules"),
+            properties => properties,
+        };
+        Ok(properties)
+    }
+}
+
+/// Switch properties to a given schedule type.
+fn force_schedule(properties: Properties, schedule: ScheduleType) -> Result<Properties> {
+    match (properties, schedule) {
+        (properties @ Properties::H24(_), ScheduleType::Time)
+        | (properties @ Properties::Solar(_), ScheduleType::Solar)
+        | (properties @ Properties::Appearance(_), ScheduleType::Appearance) => Ok(properties),
+        (properties, ScheduleType::Appearance) => properties
+            .appearance()
+            .cloned()
+            .map(Properties::Appearance)
+            .context("wallpaper has no appearance metadata"),
+        (properties, _) => bail!(
+            "{} schedule can't be turned into another one, give the frames instead",
+            properties.schedule_type()
+        ),
+    }
+}
+
+/// Get position of a frame with a given number, starting from 1.
+fn check_frame(frame: usize, frame_count: usize) -> Result<usize> {
+    if (1..=frame_count).contains(&frame) {
+        Ok(frame - 1)
+    } else {
+        bail!("there is no frame {frame} in the schedule")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    #[fixture]
+    fn properties_h24() -> Properties {
+        let item = |index, time| TimeItem {
+            index,
+            time: not_nan!(time),
+        };
+        Properties::H24(PropertiesH24 {
+            appearance: Some(PropertiesAppearance { dark: 2, light: 0 }),
+            time_info: vec![item(2, 0.75), item(0, 0.25), item(1, 0.5)],
+        })
+    }
+
+    #[fixture]
+    fn properties_solar() -> Properties {
+        let item = |index, altitude, azimuth| SolarItem {
+            index,
+            altitude: not_nan!(altitude),
+            azimuth: not_nan!(azimuth),
+        };
+        Properties::Solar(PropertiesSolar {
+            appearance: None,
+            solar_info: vec![item(0, 10.0, 90.0), item(1, 60.0, 180.0)],
+        })
+    }
+
+    fn parse(value: &str) -> ScheduleOverride {
+        toml::from_str(value).unwrap()
+    }
+
+    /// Get `(image, time)` pairs of a time based schedule in the display order.
+    fn time_frames(properties: &Properties) -> Vec<(usize, String)> {
+        let Properties::H24(props) = properties else {
+            panic!("expected time based schedule, got {properties:?}");
+        };
+        sort_time_items(&props.time_info)
+            .iter()
+            .map(|item| {
+                let time = crate::schedule::time::day_fraction_to_time(item.time.into_inner());
+                (item.index, time.format("%H:%M").to_string())
+            })
+            .collect()
+    }
+
+    #[rstest]
+    fn test_apply_nothing(properties_h24: Properties) {
+        let expected = time_frames(&properties_h24);
+
+        let properties = ScheduleOverride::default().apply(properties_h24).unwrap();
+
+        assert_eq!(time_frames(&properties), expected);
+    }
+
+    #[rstest]
+    fn test_apply_replace_time(properties_solar: Properties) {
+        let schedule_override =
+            parse("time = [{ image = 1, time = '20:00' }, { image = 0, time = '07:30' }]");
+
+        let properties = schedule_override.apply(properties_solar).unwrap();
+
+        assert_eq!(
+            time_frames(&properties),
+            vec![(0, "07:30".to_string()), (1, "20:00".to_string())]
+        );
+    }
+
+    #[rstest]
+    fn test_apply_replace_solar(properties_h24: Properties) {
+        let schedule_override = parse(
+            "solar = [{ image = 0, altitude = -20.0, azimuth = 0.0 }, \
+             { image = 1, altitude = 40.0, azimuth = 180.0 }]",
+        );
+
+        let properties = schedule_override.apply(properties_h24).unwrap();
+
+        let Properties::Solar(props) = properties else {
+            panic!("expected sun based schedule");
+        };
+        assert_eq!(props.solar_info.len(), 2);
+        assert_eq!(
+            props.appearance,
+            Some(PropertiesAppearance { dark: 2, light: 0 })
+        );
+    }
+
+    #[rstest]
+    fn test_apply_remap_and_drop(properties_h24: Properties) {
+        let schedule_override = parse("remap = [{ frame = 3, image = 1 }]\ndrop = [2]");
+
+        let properties = schedule_override.apply(properties_h24).unwrap();
+
+        assert_eq!(
+            time_frames(&properties),
+            vec![(0, "06:00".to_string()), (1, "18:00".to_string())]
+        );
+    }
+
+    #[rstest]
+    #[case("-7h", vec![(2, "11:00"), (0, "23:00"), (1, "05:00")])]
+    #[case("30m", vec![(0, "06:30"), (1, "12:30"), (2, "18:30")])]
+    fn test_apply_shift(
+        properties_h24: Properties,
+        #[case] shift: &str,
+        #[case] expected: Vec<(usize, &str)>,
+    ) {
+        let schedule_override = parse(&format!("shift = '{shift}'"));
+
+        let properties = schedule_override.apply(properties_h24).unwrap();
+
+        let mut expected = expected
+            .into_iter()
+            .map(|(image, time)| (image, time.to_string()))
+            .collect::<Vec<_>>();
+        expected.sort_by(|a, b| a.1.cmp(&b.1));
+        assert_eq!(time_frames(&properties), expected);
+    }
+
+    #[rstest]
+    fn test_apply_altitude_offset(properties_solar: Properties) {
+        let properties = parse("altitude_offset = -5.0")
+            .apply(properties_solar)
+            .unwrap();
+
+        let Properties::Solar(props) = properties else {
+            panic!("expected sun based schedule");
+        };
+        let altitudes = props
+            .solar_info
+            .iter()
+            .map(|item| item.altitude.into_inner())
+            .collect::<Vec<_>>();
+        assert_eq!(altitudes, vec![5.0, 55.0]);
+    }
+
+    #[rstest]
+    fn test_apply_force_appearance(properties_h24: Properties) {
+        let properties = parse("schedule = 'appearance'")
+            .apply(properties_h24)
+            .unwrap();
+
+        assert!(matches!(
+            properties,
+            Properties::Appearance(PropertiesAppearance { dark: 2, light: 0 })
+        ));
+    }
+
+    #[rstest]
+    #[case::both_schedules(
+        "time = [{ image = 0, time = '12:00' }]\nsolar = [{ image = 0, altitude = 0.0, azimuth = 0.0 }]"
+    )]
+    #[case::no_appearance("schedule = 'appearance'")]
+    #[case::other_type("schedule = 'time'")]
+    #[case::nonexistent_frame("remap = [{ frame = 3, image = 0 }]")]
+    #[case::nonexistent_image("remap = [{ frame = 1, image = 2 }]")]
+    #[case::nothing_left("drop = [1, 2]")]
+    #[case::shift_solar("shift = '1h'")]
+    fn test_apply_invalid(properties_solar: Properties, #[case] value: &str) {
+        assert!(parse(value).apply(properties_solar).is_err());
+    }
+}
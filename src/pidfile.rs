@@ -1,69 +1,95 @@
 use std::{
-    env, fs,
+    env, fs, io,
     path::{Path, PathBuf},
 };
 
+use anyhow::{Context, Result};
 use directories::ProjectDirs;
 
 use crate::constants::{APP_NAME, APP_QUALIFIER};
 
+const PIDFILE_EXTENSION: &str = "pid";
+
 /// Abstraction over a pidfile containing PID of the last ran wallpaper setter process.
+/// Each configured setter has its own pidfile.
 pub struct SetterPidFile {
     pidfile_path: PathBuf,
 }
 
 impl SetterPidFile {
-    /// Find user's runtime directory and load instance from there.
-    pub fn find() -> Self {
-        let runtime_dir = if let Result::Ok(path_str) = env::var("TIMEWALL_RUNTIME_DIR") {
-            PathBuf::from(path_str)
-        } else {
-            match ProjectDirs::from(APP_QUALIFIER, "", APP_NAME) {
-                Some(app_dirs) => app_dirs
-                    .runtime_dir()
-                    .map_or_else(|| env::temp_dir().join(APP_NAME), Path::to_path_buf),
-                None => panic!("couldn't determine user's home directory"),
-            }
+    /// Find user's runtime directory and load instance for the setter with a given index.
+    pub fn find(setter_index: usize) -> Result<Self> {
+        Self::load(get_runtime_dir()?.join(format!("setter_{setter_index}.{PIDFILE_EXTENSION}")))
+    }
+
+    /// Find all setter pidfiles present in user's runtime directory.
+    pub fn find_all() -> Result<Vec<Self>> {
+        let Ok(runtime_dir_entries) = get_runtime_dir()?.read_dir() else {
+            return Ok(Vec::new());
         };
-        Self::load(runtime_dir.join("last_setter.pid"))
+        runtime_dir_entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == PIDFILE_EXTENSION))
+            .map(Self::load)
+            .collect()
     }
 
     /// Load instance from given path.
-    fn load<P: AsRef<Path>>(pid_path: P) -> Self {
+    pub fn load<P: AsRef<Path>>(pid_path: P) -> Result<Self> {
         let pid_path = pid_path.as_ref();
-        let parent_dir = pid_path.parent().unwrap();
-
-        if !parent_dir.exists() {
-            fs::create_dir_all(parent_dir).expect("couldn't create runtime directory");
+        if let Some(parent_dir) = pid_path.parent() {
+            if !parent_dir.exists() {
+                fs::create_dir_all(parent_dir).context("couldn't create runtime directory")?;
+            }
         }
 
-        Self {
+        Ok(Self {
             pidfile_path: pid_path.to_path_buf(),
-        }
+        })
     }
 
     /// Save the PID.
-    pub fn save(&self, pid: u32) {
-        fs::write(&self.pidfile_path, pid.to_string()).expect("couldn't write setter pidfile");
+    pub fn save(&self, pid: u32) -> Result<()> {
+        fs::write(&self.pidfile_path, pid.to_string()).context("couldn't write setter pidfile")
     }
 
     /// Read the PID value if it exists.
-    pub fn read(&self) -> Option<u32> {
-        if self.pidfile_path.exists() {
-            Some(
-                fs::read_to_string(&self.pidfile_path)
-                    .unwrap()
-                    .parse::<u32>()
-                    .unwrap(),
-            )
-        } else {
-            None
+    pub fn read(&self) -> Result<Option<u32>> {
+        if !self.pidfile_path.exists() {
+            return Ok(None);
         }
+        let pid_str =
+            fs::read_to_string(&self.pidfile_path).context("couldn't read setter pidfile")?;
+        pid_str
+            .trim()
+            .parse::<u32>()
+            .map(Some)
+            .with_context(|| format!("malformed setter pidfile {}", self.pidfile_path.display()))
     }
 
-    /// Remove the pidfile.
-    pub fn clear(&self) {
-        fs::remove_file(&self.pidfile_path).expect("cound't remove setter pidfile");
+    /// Remove the pidfile. Does nothing if it doesn't exist.
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_file(&self.pidfile_path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                Err(err).context("couldn't remove setter pidfile")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn get_runtime_dir() -> Result<PathBuf> {
+    if let Result::Ok(path_str) = env::var("TIMEWALL_RUNTIME_DIR") {
+        Ok(PathBuf::from(path_str))
+    } else {
+        ProjectDirs::from(APP_QUALIFIER, "", APP_NAME)
+            .map(|app_dirs| {
+                app_dirs
+                    .runtime_dir()
+                    .map_or_else(|| env::temp_dir().join(APP_NAME), Path::to_path_buf)
+            })
+            .context("couldn't determine user's home directory")
     }
 }
 
@@ -85,7 +111,7 @@ mod tests {
         let fake_runtime_dir = tmp_dir.child("fake_runtime_dir");
         let pidfile_path = fake_runtime_dir.child("last_setter.pid");
 
-        SetterPidFile::load(&pidfile_path);
+        SetterPidFile::load(&pidfile_path).unwrap();
 
         fake_runtime_dir.assert(predicate::path::exists());
     }
@@ -94,26 +120,36 @@ mod tests {
     fn test_setter_pidfile_save_read(tmp_dir: TempDir) {
         let pidfile_path = tmp_dir.child("test.pid");
 
-        let pidfile = SetterPidFile::load(&pidfile_path);
+        let pidfile = SetterPidFile::load(&pidfile_path).unwrap();
         pidfile_path.assert(predicate::path::missing());
-        assert_eq!(pidfile.read(), None);
+        assert_eq!(pidfile.read().unwrap(), None);
+
+        pidfile.save(1234).unwrap();
+        assert_eq!(pidfile.read().unwrap(), Some(1234));
 
-        pidfile.save(1234);
-        assert_eq!(pidfile.read(), Some(1234));
+        pidfile.save(420).unwrap();
+        assert_eq!(pidfile.read().unwrap(), Some(420));
+    }
+
+    #[rstest]
+    fn test_setter_pidfile_read_malformed(tmp_dir: TempDir) {
+        let pidfile_path = tmp_dir.child("test.pid");
+        pidfile_path.write_str("garbage").unwrap();
 
-        pidfile.save(420);
-        assert_eq!(pidfile.read(), Some(420));
+        let pidfile = SetterPidFile::load(&pidfile_path).unwrap();
+        assert!(pidfile.read().is_err());
     }
 
     #[rstest]
     fn test_setter_pidfile_clear(tmp_dir: TempDir) {
         let pidfile_path = tmp_dir.child("test.pid");
 
-        let last_pid = SetterPidFile::load(&pidfile_path);
-        last_pid.save(1234);
-        assert_eq!(last_pid.read(), Some(1234));
+        let last_pid = SetterPidFile::load(&pidfile_path).unwrap();
+        last_pid.save(1234).unwrap();
+        assert_eq!(last_pid.read().unwrap(), Some(1234));
 
-        last_pid.clear();
-        assert_eq!(last_pid.read(), None);
+        last_pid.clear().unwrap();
+        assert_eq!(last_pid.read().unwrap(), None);
+        last_pid.clear().unwrap();
     }
 }
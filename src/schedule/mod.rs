@@ -5,3 +5,14 @@ pub mod time;
 pub use appearance::{current_image_index_appearance, get_image_index_order_appearance};
 pub use h24::{current_image_index_h24, get_image_index_order_h24, sort_time_items};
 pub use solar::{current_image_index_solar, get_image_index_order_solar, sort_solar_items};
+
+use crate::wallpaper::properties::Properties;
+
+/// Get indices of images in the order they appear throughout the day.
+pub fn get_image_index_order(properties: &Properties) -> Vec<usize> {
+    match properties {
+        Properties::H24(ref props) => get_image_index_order_h24(&props.time_info),
+        Properties::Solar(ref props) => get_image_index_order_solar(&props.solar_info),
+        Properties::Appearance(ref props) => get_image_index_order_appearance(props),
+    }
+}
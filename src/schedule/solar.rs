@@ -4,10 +4,7 @@ use itertools::{Itertools, MinMaxResult};
 use log::debug;
 use sun::Position;
 
-use crate::{
-    geo::{Coords, Hemisphere},
-    wallpaper::properties::SolarItem,
-};
+use crate::{geo::Coords, wallpaper::properties::SolarItem};
 
 /// Get the index of the image which should be displayed for given datetime and location.
 pub fn current_image_index_solar(
@@ -21,16 +18,15 @@ pub fn current_image_index_solar(
         altitude: sun_pos.altitude.to_degrees(),
     };
     debug!("sun position: {sun_pos_degrees:?}");
-    current_image_index_from_sun_pos(solar_items, &sun_pos_degrees, &coords.hemisphere())
+    current_image_index_from_sun_pos(solar_items, &sun_pos_degrees)
 }
 
 /// Get the index of image which should be displayed for a given sun position.
 fn current_image_index_from_sun_pos(
     solar_items: &[SolarItem],
     sun_pos: &Position,
-    hemisphere: &Hemisphere,
 ) -> Result<usize> {
-    Ok(current_item_solar_from_sun_pos(solar_items, sun_pos, hemisphere)?.index)
+    Ok(current_item_solar_from_sun_pos(solar_items, sun_pos)?.index)
 }
 
 /// Get the solar item which should be displayed for a given sun position.
@@ -38,11 +34,10 @@ fn current_image_index_from_sun_pos(
 fn current_item_solar_from_sun_pos<'i>(
     solar_items: &'i [SolarItem],
     sun_pos: &Position,
-    hemisphere: &Hemisphere,
 ) -> Result<&'i SolarItem> {
     let (min_alt_item, max_alt_item) = get_minmax_alt_items(solar_items)?;
     let sorted_items = sort_solar_items(solar_items);
-    let current_phase_items = if is_rising(sun_pos.azimuth, hemisphere) {
+    let current_phase_items = if is_rising(sun_pos.azimuth) {
         get_items_between(&sorted_items, min_alt_item, max_alt_item)
     } else {
         get_items_between(&sorted_items, max_alt_item, min_alt_item)
@@ -84,13 +79,12 @@ fn get_items_between<'i>(
     items_between
 }
 
-/// Check whether given sun azimuth corresponds with rising or setting sun position
-/// on given hemisphere.
-fn is_rising(azimuth: f64, hemishphere: &Hemisphere) -> bool {
-    match hemishphere {
-        Hemisphere::Northern => azimuth <= 180.0,
-        Hemisphere::Southern => azimuth > 180.0,
-    }
+/// Check whether given sun azimuth corresponds with rising or setting sun position.
+/// The sun rises before it crosses the meridian, so while it's in the eastern half of the sky.
+/// That holds on both hemispheres, the sun only moves through the north instead of the south
+/// on the southern one.
+fn is_rising(azimuth: f64) -> bool {
+    azimuth <= 180.0
 }
 
 /// Get indices of images in appearance order.
@@ -101,16 +95,35 @@ pub fn get_image_index_order_solar(solar_items: &[SolarItem]) -> Vec<usize> {
         .collect_vec()
 }
 
-/// Sort solar items by their occurrence order in a day.
+/// Sort solar items by their occurrence order in a day, starting around midnight.
+///
+/// There is no location in the metadata, but the sun path the frames follow is known from where
+/// the sun culminates. If it's in the south, as seen north of the tropics, the sun moves
+/// clockwise: from the east, through the south, to the west. If it's in the north, as seen
+/// south of the tropics, the sun moves counterclockwise, so the azimuths are mirrored.
 pub fn sort_solar_items(solar_items: &[SolarItem]) -> Vec<&SolarItem> {
-    // We assume Northern Hemisphere and just sort by azimuth value.
-    // There is no localization metadata in images so I don't see other option.
+    let counterclockwise = culminates_north(solar_items);
     solar_items
         .iter()
-        .sorted_by_key(|item| item.azimuth)
+        .sorted_by_key(|item| {
+            if counterclockwise {
+                not_nan!((180.0 - item.azimuth.into_inner()).rem_euclid(360.0))
+            } else {
+                item.azimuth
+            }
+        })
         .collect_vec()
 }
 
+/// Check whether the sun is further north at its highest position than at its lowest one.
+fn culminates_north(solar_items: &[SolarItem]) -> bool {
+    let Ok((min_alt_item, max_alt_item)) = get_minmax_alt_items(solar_items) else {
+        return false;
+    };
+    let northing = |item: &SolarItem| item.azimuth.to_radians().cos();
+    northing(max_alt_item) > northing(min_alt_item)
+}
+
 #[cfg(test)]
 mod tests {
     use rstest::*;
@@ -148,6 +161,20 @@ mod tests {
         ]
     }
 
+    #[fixture]
+    #[rustfmt::skip]
+    fn solar_items_south() -> Vec<SolarItem> {
+        // Taken south of the tropics, the sun culminates in the north, intentionally unordered
+        vec![
+            SolarItem { index: 3, azimuth: not_nan!(10.0), altitude: not_nan!(60.0) },
+            SolarItem { index: 0, azimuth: not_nan!(180.0), altitude: not_nan!(-60.0) },
+            SolarItem { index: 4, azimuth: not_nan!(300.0), altitude: not_nan!(30.0) },
+            SolarItem { index: 1, azimuth: not_nan!(110.0), altitude: not_nan!(-10.0) },
+            SolarItem { index: 5, azimuth: not_nan!(250.0), altitude: not_nan!(-10.0) },
+            SolarItem { index: 2, azimuth: not_nan!(70.0), altitude: not_nan!(20.0) },
+        ]
+    }
+
     // Normal, expected cases.
     #[rstest]
     #[case(Position { azimuth: 100.0, altitude: -70.0 }, 5)] // wrap around to last item
@@ -171,8 +198,7 @@ mod tests {
         #[case] sun_pos: Position,
         #[case] expected_index: usize,
     ) {
-        let result =
-            current_image_index_from_sun_pos(&solar_items_1, &sun_pos, &Hemisphere::Northern);
+        let result = current_image_index_from_sun_pos(&solar_items_1, &sun_pos);
         assert_eq!(result.unwrap(), expected_index);
     }
 
@@ -187,8 +213,7 @@ mod tests {
         #[case] sun_pos: Position,
         #[case] expected_index: usize,
     ) {
-        let result =
-            current_image_index_from_sun_pos(&solar_items_2, &sun_pos, &Hemisphere::Northern);
+        let result = current_image_index_from_sun_pos(&solar_items_2, &sun_pos);
         assert_eq!(result.unwrap(), expected_index);
     }
 
@@ -203,8 +228,7 @@ mod tests {
         #[case] sun_pos: Position,
         #[case] expected_index: usize,
     ) {
-        let result =
-            current_image_index_from_sun_pos(&solar_items_3, &sun_pos, &Hemisphere::Northern);
+        let result = current_image_index_from_sun_pos(&solar_items_3, &sun_pos);
         assert_eq!(result.unwrap(), expected_index);
     }
 
@@ -213,4 +237,52 @@ mod tests {
         let result = get_image_index_order_solar(&solar_items_1);
         assert_eq!(result, vec![0, 1, 2, 3, 4, 5]);
     }
+
+    #[rstest]
+    fn test_get_image_index_order_solar_south(solar_items_south: Vec<SolarItem>) {
+        let result = get_image_index_order_solar(&solar_items_south);
+        assert_eq!(result, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    // Sun path through the northern sky, as seen south of the tropics.
+    #[rstest]
+    #[case(Position { azimuth: 150.0, altitude: -50.0 }, 0)]
+    #[case(Position { azimuth: 100.0, altitude: 0.0 }, 1)]
+    #[case(Position { azimuth: 60.0, altitude: 25.0 }, 2)]
+    #[case(Position { azimuth: 10.0, altitude: 55.0 }, 3)]
+    #[case(Position { azimuth: 300.0, altitude: 25.0 }, 4)]
+    #[case(Position { azimuth: 250.0, altitude: -20.0 }, 5)]
+    #[case(Position { azimuth: 210.0, altitude: -50.0 }, 0)]
+    fn test_current_image_index_from_sun_pos_south(
+        solar_items_south: Vec<SolarItem>,
+        #[case] sun_pos: Position,
+        #[case] expected_index: usize,
+    ) {
+        let result = current_image_index_from_sun_pos(&solar_items_south, &sun_pos);
+        assert_eq!(result.unwrap(), expected_index);
+    }
+
+    // Sydney, at the same solar altitude in the morning and in the evening.
+    #[rstest]
+    #[case::morning_north_wallpaper(solar_items_1(), "2024-12-20T21:00:00Z", 2)]
+    #[case::evening_north_wallpaper(solar_items_1(), "2024-12-21T07:00:00Z", 4)]
+    #[case::morning_south_wallpaper(solar_items_south(), "2024-12-20T21:00:00Z", 2)]
+    #[case::evening_south_wallpaper(solar_items_south(), "2024-12-21T07:00:00Z", 4)]
+    fn test_current_image_index_solar_southern_hemisphere(
+        #[case] solar_items: Vec<SolarItem>,
+        #[case] datetime: &str,
+        #[case] expected_index: usize,
+    ) {
+        let datetime = DateTime::parse_from_rfc3339(datetime)
+            .unwrap()
+            .with_timezone(&Local);
+        let coords = Coords {
+            lat: -33.87,
+            lon: 151.21,
+        };
+
+        let result = current_image_index_solar(&solar_items, &datetime, &coords);
+
+        assert_eq!(result.unwrap(), expected_index);
+    }
 }
@@ -1,4 +1,8 @@
-use chrono::{NaiveTime, Timelike};
+use std::{fmt::Write, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use chrono::{NaiveTime, TimeDelta, Timelike};
+use serde::{Deserialize, Serialize};
 
 const SECONDS_IN_A_DAY: u32 = 24 * 60 * 60;
 
@@ -12,3 +16,148 @@ pub fn day_fraction_to_time(day_fraction: f64) -> NaiveTime {
     let seconds_passed = (day_fraction * f64::from(SECONDS_IN_A_DAY)) as u32;
     NaiveTime::from_num_seconds_from_midnight_opt(seconds_passed, 0).unwrap()
 }
+
+/// Offset of schedule times, written as a signed combination of hours, minutes and seconds,
+/// e.g. `-2h`, `1h30m` or `45s`.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOffset(pub TimeDelta);
+
+impl TimeOffset {
+    /// Shift a time given as a fraction of the day, wrapping around midnight.
+    pub fn shift_day_fraction(self, day_fraction: f64) -> f64 {
+        #[allow(
+            clippy::cast_precision_loss,
+            reason = "offsets are much shorter than 2^52 s"
+        )]
+        let offset = self.0.num_seconds() as f64 / f64::from(SECONDS_IN_A_DAY);
+        (day_fraction + offset).rem_euclid(1.0)
+    }
+}
+
+impl FromStr for TimeOffset {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = || anyhow!("expected time offset like '-2h' or '1h30m', got '{value}'");
+        let (negative, units) = match value.strip_prefix('-') {
+            Some(units) => (true, units),
+            None => (false, value.strip_prefix('+').unwrap_or(value)),
+        };
+        if units.is_empty() {
+            return Err(invalid());
+        }
+        if units == "0" {
+            return Ok(Self::default());
+        }
+
+        let mut offset = TimeDelta::zero();
+        let mut rest = units;
+        while !rest.is_empty() {
+            let digits_end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .filter(|end| *end > 0)
+                .ok_or_else(invalid)?;
+            let amount = rest[..digits_end].parse::<i64>().map_err(|_| invalid())?;
+            let part = match rest[digits_end..].chars().next() {
+                Some('h') => TimeDelta::try_hours(amount),
+                Some('m') => TimeDelta::try_minutes(amount),
+                Some('s') => TimeDelta::try_seconds(amount),
+                _ => None,
+            };
+            offset = part
+                .and_then(|part| offset.checked_add(&part))
+                .ok_or_else(invalid)?;
+            rest = &rest[digits_end + 1..];
+        }
+        Ok(Self(if negative { -offset } else { offset }))
+    }
+}
+
+impl TryFrom<String> for TimeOffset {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<TimeOffset> for String {
+    fn from(value: TimeOffset) -> Self {
+        let seconds = value.0.num_seconds();
+        let sign = if seconds < 0 { "-" } else { "" };
+        let seconds = seconds.unsigned_abs();
+        let parts = [
+            (seconds / 3600, 'h'),
+            (seconds / 60 % 60, 'm'),
+            (seconds % 60, 's'),
+        ]
+        .iter()
+        .filter(|(amount, _)| *amount > 0)
+        .fold(Self::new(), |mut parts, (amount, unit)| {
+            let _ = write!(parts, "{amount}{unit}");
+            parts
+        });
+        if parts.is_empty() {
+            Self::from("0")
+        } else {
+            format!("{sign}{parts}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case("2h", TimeDelta::hours(2))]
+    #[case("-2h", TimeDelta::hours(-2))]
+    #[case("+90m", TimeDelta::minutes(90))]
+    #[case("1h30m", TimeDelta::minutes(90))]
+    #[case("-1h30m15s", -TimeDelta::seconds(5415))]
+    #[case("0", TimeDelta::zero())]
+    fn test_time_offset_parse(#[case] value: &str, #[case] expected: TimeDelta) {
+        assert_eq!(value.parse::<TimeOffset>().unwrap(), TimeOffset(expected));
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("-")]
+    #[case("2")]
+    #[case("h")]
+    #[case("2d")]
+    #[case("1h-30m")]
+    #[case("99999999999999999h")]
+    fn test_time_offset_parse_invalid(#[case] value: &str) {
+        assert!(value.parse::<TimeOffset>().is_err());
+    }
+
+    #[rstest]
+    #[case("-2h")]
+    #[case("1h30m15s")]
+    #[case("45s")]
+    #[case("0")]
+    fn test_time_offset_to_string(#[case] value: &str) {
+        assert_eq!(String::from(value.parse::<TimeOffset>().unwrap()), value);
+    }
+
+    #[rstest]
+    #[case(0.5, "2h", 0.5 + 1.0 / 12.0)]
+    #[case(0.0, "-6h", 0.75)]
+    #[case(0.9, "6h", 0.15)]
+    fn test_time_offset_shift_day_fraction(
+        #[case] day_fraction: f64,
+        #[case] offset: &str,
+        #[case] expected: f64,
+    ) {
+        let shifted = offset
+            .parse::<TimeOffset>()
+            .unwrap()
+            .shift_day_fraction(day_fraction);
+
+        assert!((shifted - expected).abs() < 1e-9);
+    }
+}
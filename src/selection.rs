@@ -0,0 +1,186 @@
+use anyhow::{bail, Result};
+use chrono::NaiveTime;
+
+/// Bound of a range of previewed frames.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FrameBound {
+    /// Frame number, starting from 1.
+    Number(usize),
+    /// Frame displayed at a given time of the day.
+    Time(NaiveTime),
+}
+
+/// Which frames of the wallpaper are previewed, and in which direction.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct FrameSelection {
+    pub from: Option<FrameBound>,
+    pub to: Option<FrameBound>,
+    /// Explicit frame numbers, taking precedence over the range.
+    pub frames: Option<Vec<usize>>,
+    pub reverse: bool,
+    /// Go back and forth instead of jumping from the last frame to the first one.
+    pub bounce: bool,
+}
+
+impl FrameSelection {
+    /// Select positions of frames in an order of `frame_count` frames.
+    ///
+    /// Times are resolved with `position_at`, which returns position of the frame displayed at
+    /// a given time. Ranges with the start after the end wrap around the end of the order,
+    /// e.g. over midnight. In the bounce mode, the frames go back to the first one, unless
+    /// `repeat` is set, in which case the loop itself gets back to it.
+    pub fn select(
+        &self,
+        frame_count: usize,
+        repeat: bool,
+        position_at: impl Fn(NaiveTime) -> Result<usize>,
+    ) -> Result<Vec<usize>> {
+        let check_number = |number: usize| {
+            if (1..=frame_count).contains(&number) {
+                Ok(number - 1)
+            } else {
+                bail!("there is no frame {number} in the wallpaper")
+            }
+        };
+        let resolve = |bound| match bound {
+            FrameBound::Number(number) => check_number(number),
+            FrameBound::Time(time) => position_at(time),
+        };
+
+        let mut positions = if let Some(frames) = &self.frames {
+            frames
+                .iter()
+                .map(|number| check_number(*number))
+                .collect::<Result<Vec<_>>>()?
+        } else if frame_count == 0 {
+            Vec::new()
+        } else {
+            let from = self.from.map(resolve).transpose()?.unwrap_or(0);
+            let to = self.to.map(resolve).transpose()?.unwrap_or(frame_count - 1);
+            if from <= to {
+                (from..=to).collect()
+            } else {
+                (from..frame_count).chain(0..=to).collect()
+            }
+        };
+
+        if self.reverse {
+            positions.reverse();
+        }
+        if self.bounce && positions.len() > 1 {
+            let back = positions.iter().rev().skip(1);
+            let back = if repeat {
+                back.take(positions.len() - 2).copied().collect::<Vec<_>>()
+            } else {
+                back.copied().collect()
+            };
+            positions.extend(back);
+        }
+        Ok(positions)
+    }
+
+    /// Get the time window given by the range bounds. Only times can be used then.
+    pub fn time_window(&self) -> Result<(Option<NaiveTime>, Option<NaiveTime>)> {
+        let to_time = |bound| match bound {
+            Some(FrameBound::Time(time)) => Ok(Some(time)),
+            Some(FrameBound::Number(_)) => {
+                bail!("only times of the day can bound realistic preview")
+            }
+            None => Ok(None),
+        };
+        Ok((to_time(self.from)?, to_time(self.to)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Timelike;
+    use rstest::*;
+
+    use super::*;
+
+    fn time(hour: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    /// Position of the frame at a given time, with frames every 3 hours.
+    #[allow(clippy::unnecessary_wraps)]
+    fn position_at(time: NaiveTime) -> Result<usize> {
+        Ok(time.hour() as usize / 3)
+    }
+
+    fn range(from: Option<FrameBound>, to: Option<FrameBound>) -> FrameSelection {
+        FrameSelection {
+            from,
+            to,
+            ..FrameSelection::default()
+        }
+    }
+
+    #[rstest]
+    #[case::all(range(None, None), vec![0, 1, 2, 3, 4, 5, 6, 7])]
+    #[case::numbers(
+        range(Some(FrameBound::Number(2)), Some(FrameBound::Number(4))),
+        vec![1, 2, 3]
+    )]
+    #[case::from_only(range(Some(FrameBound::Number(7)), None), vec![6, 7])]
+    #[case::to_only(range(None, Some(FrameBound::Number(2))), vec![0, 1])]
+    #[case::times(
+        range(Some(FrameBound::Time(time(15))), Some(FrameBound::Time(time(21)))),
+        vec![5, 6, 7]
+    )]
+    #[case::over_midnight(
+        range(Some(FrameBound::Time(time(21))), Some(FrameBound::Time(time(3)))),
+        vec![7, 0, 1]
+    )]
+    #[case::frames(
+        FrameSelection { frames: Some(vec![5, 6, 7]), ..FrameSelection::default() },
+        vec![4, 5, 6]
+    )]
+    #[case::reverse(
+        FrameSelection {
+            to: Some(FrameBound::Number(3)),
+            reverse: true,
+            ..FrameSelection::default()
+        },
+        vec![2, 1, 0]
+    )]
+    fn test_select(#[case] selection: FrameSelection, #[case] expected: Vec<usize>) {
+        assert_eq!(selection.select(8, false, position_at).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::once(false, vec![0, 1, 2, 1, 0])]
+    #[case::repeat(true, vec![0, 1, 2, 1])]
+    fn test_select_bounce(#[case] repeat: bool, #[case] expected: Vec<usize>) {
+        let selection = FrameSelection {
+            frames: Some(vec![1, 2, 3]),
+            bounce: true,
+            ..FrameSelection::default()
+        };
+
+        assert_eq!(selection.select(8, repeat, position_at).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(range(Some(FrameBound::Number(0)), None))]
+    #[case(range(None, Some(FrameBound::Number(9))))]
+    #[case(FrameSelection { frames: Some(vec![1, 9]), ..FrameSelection::default() })]
+    fn test_select_nonexistent_frame(#[case] selection: FrameSelection) {
+        assert!(selection.select(8, false, position_at).is_err());
+    }
+
+    #[rstest]
+    fn test_time_window() {
+        let selection = range(Some(FrameBound::Time(time(18))), None);
+
+        assert_eq!(selection.time_window().unwrap(), (Some(time(18)), None));
+    }
+
+    #[rstest]
+    fn test_time_window_frame_number() {
+        let selection = range(Some(FrameBound::Number(2)), None);
+
+        assert!(selection.time_window().is_err());
+    }
+}
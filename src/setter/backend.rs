@@ -0,0 +1,480 @@
+use std::{
+    env,
+    ffi::OsStr,
+    fmt::Display,
+    fs,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+
+/// Names of wallpaper daemons which are looked for among running processes.
+const WALLPAPER_DAEMONS: [&str; 3] = ["awww-daemon", "hyprpaper", "swaybg"];
+
+/// Desktop environments which are configured via `gsettings`.
+const GNOME_LIKE_DESKTOPS: [&str; 4] = ["gnome", "unity", "budgie", "pantheon"];
+
+/// Known method of setting the wallpaper which doesn't require a custom command.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// Generic setter from `wallpape-rs`, supporting many desktop environments.
+    Builtin,
+    /// GNOME and derivatives, via `gsettings`.
+    Gnome,
+    /// KDE Plasma, via `plasma-apply-wallpaperimage`.
+    Plasma,
+    /// Sway, via `swaymsg`.
+    Sway,
+    /// Hyprland's `hyprpaper` daemon, via `hyprctl`.
+    Hyprpaper,
+    /// `awww` daemon, via `awww img`.
+    Awww,
+    /// `swaybg`, which keeps running for as long as the wallpaper is displayed.
+    Swaybg,
+    /// `feh`, for X11 window managers.
+    Feh,
+}
+
+impl Backend {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Builtin => "builtin",
+            Self::Gnome => "gnome",
+            Self::Plasma => "plasma",
+            Self::Sway => "sway",
+            Self::Hyprpaper => "hyprpaper",
+            Self::Awww => "awww",
+            Self::Swaybg => "swaybg",
+            Self::Feh => "feh",
+        }
+    }
+
+    /// Get commands which need to be run to set the wallpaper, in order.
+    /// `%f` is a placeholder for the image path, same as in custom commands.
+    /// Builtin backend doesn't run any commands.
+    pub const fn commands(self) -> &'static [&'static [&'static str]] {
+        match self {
+            Self::Builtin => &[],
+            Self::Gnome => &[
+                &[
+                    "gsettings",
+                    "set",
+                    "org.gnome.desktop.background",
+                    "picture-uri",
+                    "file://%f",
+                ],
+                &[
+                    "gsettings",
+                    "set",
+                    "org.gnome.desktop.background",
+                    "picture-uri-dark",
+                    "file://%f",
+                ],
+            ],
+            Self::Plasma => &[&["plasma-apply-wallpaperimage", "%f"]],
+            Self::Sway => &[&["swaymsg", "output", "*", "bg", "%f", "fill"]],
+            Self::Hyprpaper => &[&["hyprctl", "hyprpaper", "reload", ",%f"]],
+            Self::Awww => &[&["awww", "img", "%f"]],
+            Self::Swaybg => &[&["swaybg", "--mode", "fill", "--image", "%f"]],
+            Self::Feh => &[&["feh", "--no-fehbg", "--bg-fill", "%f"]],
+        }
+    }
+
+    /// Whether the backend process keeps running to display the wallpaper.
+    pub const fn is_long_running(self) -> bool {
+        matches!(self, Self::Swaybg)
+    }
+
+    /// Whether the current wallpaper can be determined, so it can be restored later.
+    /// Sway and `swaybg` don't report what they display.
+    pub const fn can_restore(self) -> bool {
+        !matches!(self, Self::Sway | Self::Swaybg)
+    }
+
+    /// Get the command which prints the current wallpaper, if the backend has one.
+    /// Builtin and Plasma backends are queried through `wallpape-rs`, `feh` through `~/.fehbg`.
+    pub const fn query_command(self) -> Option<&'static [&'static str]> {
+        match self {
+            Self::Gnome => Some(&[
+                "gsettings",
+                "get",
+                "org.gnome.desktop.background",
+                "picture-uri",
+            ]),
+            Self::Hyprpaper => Some(&["hyprctl", "hyprpaper", "listactive"]),
+            Self::Awww => Some(&["awww", "query"]),
+            _ => None,
+        }
+    }
+
+    /// Get the path to the current wallpaper from the result of the backend query.
+    pub fn parse_current_wallpaper(self, output: &str) -> Option<PathBuf> {
+        let value = match self {
+            Self::Builtin | Self::Plasma => output.trim(),
+            Self::Gnome => output.trim().trim_matches('\''),
+            Self::Hyprpaper => output.lines().find_map(|line| line.split_once(" = "))?.1,
+            Self::Awww => {
+                output
+                    .lines()
+                    .find_map(|line| line.split_once("image: "))?
+                    .1
+            }
+            // Last argument of the `feh` command saved in `~/.fehbg`, in single quotes.
+            Self::Feh => {
+                let command = output
+                    .lines()
+                    .rfind(|line| line.trim_start().starts_with("feh "))?;
+                command.rsplit_once('\'')?.0.rsplit_once('\'')?.1
+            }
+            Self::Sway | Self::Swaybg => return None,
+        };
+        let value = value.trim();
+        let path = match value.strip_prefix("file://") {
+            Some(uri_path) => {
+                let path_bytes = percent_decode_str(uri_path).collect::<Vec<_>>();
+                PathBuf::from(OsStr::from_bytes(&path_bytes))
+            }
+            None => PathBuf::from(value),
+        };
+        (!path.as_os_str().is_empty()).then_some(path)
+    }
+
+    /// Check whether all executables required by the backend can be found in `PATH`.
+    pub fn is_available(self) -> bool {
+        self.commands()
+            .iter()
+            .all(|command| find_executable(command[0]).is_some())
+    }
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Information about the current graphical session relevant for choosing a backend.
+#[derive(Debug, Default)]
+pub struct Session {
+    /// Value of `XDG_CURRENT_DESKTOP`.
+    pub current_desktop: Option<String>,
+    /// Value of `WAYLAND_DISPLAY`.
+    pub wayland_display: Option<String>,
+    /// Value of `DISPLAY`.
+    pub x11_display: Option<String>,
+    /// Whether Hyprland instance signature is present.
+    pub hyprland: bool,
+    /// Whether Sway IPC socket is present.
+    pub sway: bool,
+    /// Whether Niri IPC socket is present.
+    pub niri: bool,
+    /// Known wallpaper daemons which are currently running.
+    pub running_daemons: Vec<String>,
+}
+
+impl Session {
+    /// Probe the environment and running processes.
+    pub fn probe() -> Self {
+        Self {
+            current_desktop: non_empty_var("XDG_CURRENT_DESKTOP"),
+            wayland_display: non_empty_var("WAYLAND_DISPLAY"),
+            x11_display: non_empty_var("DISPLAY"),
+            hyprland: non_empty_var("HYPRLAND_INSTANCE_SIGNATURE").is_some(),
+            sway: non_empty_var("SWAYSOCK").is_some(),
+            niri: non_empty_var("NIRI_SOCKET").is_some(),
+            running_daemons: find_running_daemons(),
+        }
+    }
+
+    fn is_daemon_running(&self, name: &str) -> bool {
+        self.running_daemons.iter().any(|daemon| daemon == name)
+    }
+
+    fn is_desktop_any_of(&self, names: &[&str]) -> bool {
+        self.current_desktop.as_ref().is_some_and(|desktops| {
+            desktops
+                .split(':')
+                .any(|desktop| names.contains(&desktop.to_lowercase().as_str()))
+        })
+    }
+}
+
+impl Display for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let display_var = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".into());
+        let display_bool = |value: bool| if value { "yes" } else { "no" };
+
+        writeln!(
+            f,
+            "XDG_CURRENT_DESKTOP: {}",
+            display_var(&self.current_desktop)
+        )?;
+        writeln!(f, "WAYLAND_DISPLAY: {}", display_var(&self.wayland_display))?;
+        writeln!(f, "DISPLAY: {}", display_var(&self.x11_display))?;
+        writeln!(f, "Hyprland: {}", display_bool(self.hyprland))?;
+        writeln!(f, "Sway: {}", display_bool(self.sway))?;
+        writeln!(f, "Niri: {}", display_bool(self.niri))?;
+        if self.running_daemons.is_empty() {
+            writeln!(f, "Running wallpaper daemons: -")?;
+        } else {
+            writeln!(
+                f,
+                "Running wallpaper daemons: {}",
+                self.running_daemons.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Backend suggested by the detection, together with the reason it was chosen.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Candidate {
+    pub backend: Backend,
+    pub reason: &'static str,
+}
+
+/// Get backends suitable for a given session, ordered from the most to the least preferred.
+///
+/// Already running wallpaper daemons take precedence, followed by desktop specific methods and
+/// generic Wayland or X11 setters. Builtin backend is always included as the last resort.
+pub fn detect_backends(session: &Session) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    let mut suggest = |condition: bool, backend: Backend, reason: &'static str| {
+        if condition {
+            candidates.push(Candidate { backend, reason });
+        }
+    };
+
+    suggest(
+        session.is_daemon_running("awww-daemon"),
+        Backend::Awww,
+        "awww-daemon is running",
+    );
+    suggest(
+        session.is_daemon_running("hyprpaper"),
+        Backend::Hyprpaper,
+        "hyprpaper is running",
+    );
+    suggest(
+        session.is_daemon_running("swaybg"),
+        Backend::Swaybg,
+        "swaybg is running",
+    );
+    suggest(
+        session.is_desktop_any_of(&GNOME_LIKE_DESKTOPS),
+        Backend::Gnome,
+        "GNOME-like desktop detected",
+    );
+    suggest(
+        session.is_desktop_any_of(&["kde"]),
+        Backend::Plasma,
+        "KDE Plasma desktop detected",
+    );
+    suggest(session.sway, Backend::Sway, "Sway socket found");
+    suggest(
+        session.hyprland,
+        Backend::Hyprpaper,
+        "Hyprland instance detected",
+    );
+    suggest(session.niri, Backend::Swaybg, "Niri socket found");
+    suggest(
+        session.wayland_display.is_some(),
+        Backend::Swaybg,
+        "Wayland session detected",
+    );
+    suggest(
+        session.wayland_display.is_none() && session.x11_display.is_some(),
+        Backend::Feh,
+        "X11 session detected",
+    );
+    suggest(true, Backend::Builtin, "generic fallback");
+
+    candidates
+        .into_iter()
+        .unique_by(|candidate| candidate.backend)
+        .collect_vec()
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// Find known wallpaper daemons among processes listed in `/proc`.
+fn find_running_daemons() -> Vec<String> {
+    let Ok(proc_entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    proc_entries
+        .flatten()
+        .filter_map(|entry| fs::read_to_string(entry.path().join("comm")).ok())
+        .map(|comm| comm.trim().to_owned())
+        .filter(|comm| WALLPAPER_DAEMONS.contains(&comm.as_str()))
+        .unique()
+        .sorted()
+        .collect_vec()
+}
+
+/// Find an executable with a given name in `PATH`.
+fn find_executable(name: &str) -> Option<PathBuf> {
+    let name_path = Path::new(name);
+    if name_path.is_absolute() {
+        return name_path.is_file().then(|| name_path.to_path_buf());
+    }
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    fn detected(session: &Session) -> Vec<Backend> {
+        detect_backends(session)
+            .into_iter()
+            .map(|candidate| candidate.backend)
+            .collect_vec()
+    }
+
+    #[rstest]
+    #[case(Backend::Builtin, "/usr/share/backgrounds/default.png\n")]
+    #[case(Backend::Plasma, "file:///usr/share/backgrounds/default.png")]
+    #[case(Backend::Gnome, "'file:///usr/share/backgrounds/default.png'\n")]
+    #[case(
+        Backend::Hyprpaper,
+        "DP-1 = /usr/share/backgrounds/default.png\neDP-1 = /tmp/other.png\n"
+    )]
+    #[case(
+        Backend::Awww,
+        "eDP-1: 1920x1080, scale: 1, currently displaying: image: /usr/share/backgrounds/default.png\n"
+    )]
+    #[case(
+        Backend::Feh,
+        "#!/bin/sh\nfeh --no-fehbg --bg-fill '/usr/share/backgrounds/default.png' \n"
+    )]
+    fn test_parse_current_wallpaper(#[case] backend: Backend, #[case] output: &str) {
+        assert_eq!(
+            backend.parse_current_wallpaper(output),
+            Some(PathBuf::from("/usr/share/backgrounds/default.png"))
+        );
+    }
+
+    #[rstest]
+    #[case(
+        Backend::Gnome,
+        "'file:///home/user/My%20Pictures/Za%C5%BC%C3%B3%C5%82%C4%87.png'"
+    )]
+    #[case(
+        Backend::Plasma,
+        "file:///home/user/My%20Pictures/Za%C5%BC%C3%B3%C5%82%C4%87.png"
+    )]
+    #[case(Backend::Hyprpaper, "DP-1 = /home/user/My Pictures/Zażółć.png")]
+    fn test_parse_current_wallpaper_special_characters(
+        #[case] backend: Backend,
+        #[case] output: &str,
+    ) {
+        assert_eq!(
+            backend.parse_current_wallpaper(output),
+            Some(PathBuf::from("/home/user/My Pictures/Zażółć.png"))
+        );
+    }
+
+    #[rstest]
+    #[case(Backend::Gnome, "''")]
+    #[case(Backend::Hyprpaper, "no wallpapers active")]
+    #[case(Backend::Feh, "#!/bin/sh\n")]
+    #[case(Backend::Swaybg, "/usr/share/backgrounds/default.png")]
+    fn test_parse_current_wallpaper_unknown(#[case] backend: Backend, #[case] output: &str) {
+        assert_eq!(backend.parse_current_wallpaper(output), None);
+    }
+
+    #[rstest]
+    fn test_detect_backends_nothing() {
+        let session = Session::default();
+        assert_eq!(detected(&session), vec![Backend::Builtin]);
+    }
+
+    #[rstest]
+    #[case("GNOME", vec![Backend::Gnome, Backend::Swaybg, Backend::Builtin])]
+    #[case("ubuntu:GNOME", vec![Backend::Gnome, Backend::Swaybg, Backend::Builtin])]
+    #[case("KDE", vec![Backend::Plasma, Backend::Swaybg, Backend::Builtin])]
+    #[case("Hyprland", vec![Backend::Swaybg, Backend::Builtin])]
+    fn test_detect_backends_desktop(#[case] desktop: &str, #[case] expected: Vec<Backend>) {
+        let session = Session {
+            current_desktop: Some(desktop.to_owned()),
+            wayland_display: Some("wayland-1".to_owned()),
+            ..Session::default()
+        };
+        assert_eq!(detected(&session), expected);
+    }
+
+    #[rstest]
+    fn test_detect_backends_compositors() {
+        let session = Session {
+            wayland_display: Some("wayland-1".to_owned()),
+            hyprland: true,
+            sway: true,
+            niri: true,
+            ..Session::default()
+        };
+        assert_eq!(
+            detected(&session),
+            vec![
+                Backend::Sway,
+                Backend::Hyprpaper,
+                Backend::Swaybg,
+                Backend::Builtin
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_detect_backends_running_daemons_first() {
+        let session = Session {
+            wayland_display: Some("wayland-1".to_owned()),
+            hyprland: true,
+            running_daemons: vec!["awww-daemon".to_owned(), "swaybg".to_owned()],
+            ..Session::default()
+        };
+        assert_eq!(
+            detected(&session),
+            vec![
+                Backend::Awww,
+                Backend::Swaybg,
+                Backend::Hyprpaper,
+                Backend::Builtin
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_detect_backends_x11() {
+        let session = Session {
+            x11_display: Some(":0".to_owned()),
+            ..Session::default()
+        };
+        assert_eq!(detected(&session), vec![Backend::Feh, Backend::Builtin]);
+    }
+
+    #[rstest]
+    fn test_detect_backends_reason() {
+        let session = Session {
+            sway: true,
+            ..Session::default()
+        };
+        assert_eq!(
+            detect_backends(&session)[0],
+            Candidate {
+                backend: Backend::Sway,
+                reason: "Sway socket found"
+            }
+        );
+    }
+}
@@ -0,0 +1,800 @@
+pub mod backend;
+pub mod placeholders;
+pub mod supervisor;
+
+use nix::errno::Errno;
+use nix::sys::signal::Signal;
+use nix::{sys::signal::kill, unistd::Pid};
+use std::cmp::max;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+use std::{env, thread};
+
+use anyhow::{anyhow, bail, Context, Result};
+use directories::BaseDirs;
+use itertools::Itertools;
+use log::{debug, warn};
+use wallpape_rs as wallpaper;
+
+use crate::cache::PreviousWallpaper;
+use crate::config::{Config, Setter};
+use crate::outputs::{connected_outputs, Output};
+use crate::pidfile::SetterPidFile;
+use backend::{detect_backends, Backend, Session};
+use placeholders::Placeholders;
+use supervisor::SetterProcess;
+
+/// Set wallpaper to the image given in placeholders using all configured setters.
+///
+/// Each setter runs its custom command if provided, otherwise it tries configured or
+/// automatically detected backends. Setters limited to a different appearance or to
+/// a disconnected output are skipped. If no setters are configured, backends are detected
+/// automatically.
+///
+/// Returns the spawned long-running processes, which can be supervised.
+pub fn set_wallpaper(
+    placeholders: &Placeholders,
+    setter_configs: &[Setter],
+) -> Result<Vec<SetterProcess>> {
+    run_setters(placeholders, setter_configs, |_| true)
+}
+
+/// Run again only the setters with given indices, e.g. because their processes exited.
+pub fn restart_setters(
+    placeholders: &Placeholders,
+    setter_configs: &[Setter],
+    setter_indices: &[usize],
+) -> Result<Vec<SetterProcess>> {
+    run_setters(placeholders, setter_configs, |setter_index| {
+        setter_indices.contains(&setter_index)
+    })
+}
+
+fn run_setters(
+    placeholders: &Placeholders,
+    setter_configs: &[Setter],
+    is_selected: impl Fn(usize) -> bool,
+) -> Result<Vec<SetterProcess>> {
+    let setter = get_setter();
+    let default_setter_configs = [Setter::default()];
+    let setter_configs = if setter_configs.is_empty() {
+        &default_setter_configs
+    } else {
+        setter_configs
+    };
+    let outputs = setter_configs
+        .iter()
+        .any(|setter_config| setter_config.output.is_some())
+        .then(connected_outputs)
+        .unwrap_or_default();
+
+    let mut processes = Vec::new();
+    let mut failures = Vec::new();
+    for (setter_index, setter_config) in setter_configs.iter().enumerate() {
+        if !is_selected(setter_index) {
+            continue;
+        }
+        if !should_run(setter_config, placeholders, &outputs) {
+            debug!("skipping setter {setter_index}, its filters don't match");
+            continue;
+        }
+
+        let placeholders = placeholders.with_output(setter_config.output.as_deref());
+        let result = SetterPidFile::find(setter_index).and_then(|pidfile| {
+            if let Some(ref command) = setter_config.command {
+                setter.set_wallpaper_custom_command(&placeholders, command, setter_config, &pidfile)
+            } else {
+                let backends = get_effective_backends(setter_config);
+                setter.set_wallpaper(&placeholders, &backends, setter_config, &pidfile)
+            }
+        });
+        match result {
+            Ok(Some(child)) => {
+                // Commands not marked as long-running are allowed to finish on their own.
+                let may_finish =
+                    setter_config.command.is_some() && setter_config.long_running.is_none();
+                processes.push(SetterProcess::new(setter_index, child, may_finish));
+            }
+            Ok(None) => {}
+            Err(err) => {
+                debug!("setter {setter_index} failed: {err:#}");
+                failures.push(err);
+            }
+        }
+    }
+
+    match failures.len() {
+        0 => Ok(processes),
+        1 => Err(failures.remove(0)),
+        _ => Err(anyhow!(
+            "{} setters failed:\n{}",
+            failures.len(),
+            failures.iter().map(|err| format!("{err:#}")).join("\n")
+        )),
+    }
+}
+
+/// Unset wallpaper, if any.
+///
+/// This will only work if tme wallpaper is set using a custom, long-running command.
+/// Usetting will terminate the processes of all setters.
+pub fn unset_wallpaper() -> Result<bool> {
+    let setter = get_setter();
+    setter.cleanup()
+}
+
+/// Remember the current desktop wallpaper, so it can be restored later.
+///
+/// Nothing is saved if there's already a saved wallpaper, or if the current one can't be determined.
+pub fn save_previous_wallpaper(setter_configs: &[Setter]) {
+    let setter = get_setter();
+    let current = get_restore_backends(setter_configs)
+        .into_iter()
+        .find_map(|backend| Some((backend, setter.current_wallpaper(backend)?)));
+    let Some((backend, current_wallpaper)) = current else {
+        debug!("couldn't determine current desktop wallpaper, it won't be restored");
+        return;
+    };
+    debug!(
+        "current desktop wallpaper: {} ({backend})",
+        current_wallpaper.display()
+    );
+    if let Err(err) = PreviousWallpaper::find().and_then(|previous_wallpaper| {
+        previous_wallpaper.save_if_missing(current_wallpaper, backend)
+    }) {
+        debug!("failed to save previous wallpaper: {err:#}");
+    }
+}
+
+/// Restore the desktop wallpaper which was set before timewall, if it's known.
+/// It's set using the same backend it was determined with.
+pub fn restore_previous_wallpaper() -> Result<bool> {
+    let setter = get_setter();
+    let previous_wallpaper = PreviousWallpaper::find()?;
+    let Some(saved) = previous_wallpaper.get() else {
+        return Ok(false);
+    };
+
+    debug!(
+        "restoring previous wallpaper: {} ({})",
+        saved.path.display(),
+        saved.backend
+    );
+    setter
+        .restore_wallpaper(saved.backend, &saved.path)
+        .with_context(|| format!("failed to restore wallpaper {}", saved.path.display()))?;
+    previous_wallpaper.clear();
+    Ok(true)
+}
+
+/// Get backends which can determine and restore the wallpaper displayed before timewall.
+///
+/// These are the backends of the first setter which doesn't run a custom command.
+/// If all of them do, automatically detected backends are used, as the wallpaper is most likely
+/// displayed by one of them.
+fn get_restore_backends(setter_configs: &[Setter]) -> Vec<Backend> {
+    let default_setter_config = Setter::default();
+    let setter_config = setter_configs
+        .iter()
+        .find(|setter_config| setter_config.command.is_none())
+        .unwrap_or(&default_setter_config);
+    get_effective_backends(setter_config)
+        .into_iter()
+        .filter(|backend| backend.can_restore())
+        .collect_vec()
+}
+
+/// Get backends which should be tried in order: configured ones if present, otherwise
+/// the automatically detected ones which are available in the system.
+pub fn get_effective_backends(setter_config: &Setter) -> Vec<Backend> {
+    if setter_config.backends.is_empty() {
+        detect_backends(&Session::probe())
+            .into_iter()
+            .map(|candidate| candidate.backend)
+            .filter(|backend| backend.is_available())
+            .collect_vec()
+    } else {
+        setter_config.backends.clone()
+    }
+}
+
+/// Check whether setter filters match the current appearance and connected outputs.
+fn should_run(setter_config: &Setter, placeholders: &Placeholders, outputs: &[Output]) -> bool {
+    let appearance_matches = setter_config
+        .appearance
+        .is_none_or(|required| placeholders.appearance == required);
+    let output_matches = setter_config
+        .output
+        .as_ref()
+        .is_none_or(|required| outputs.iter().any(|output| output.name == *required));
+    appearance_matches && output_matches
+}
+
+fn get_setter() -> Box<dyn WallpaperSetter> {
+    match env::var("TIMEWALL_DRY_RUN") {
+        Err(_) => Box::new(DefaultSetter {}),
+        Ok(_) => Box::new(DryRunSetter {}),
+    }
+}
+
+trait WallpaperSetter {
+    fn set_wallpaper(
+        &self,
+        placeholders: &Placeholders,
+        backends: &[Backend],
+        setter_config: &Setter,
+        pidfile: &SetterPidFile,
+    ) -> Result<Option<Child>>;
+    fn set_wallpaper_custom_command(
+        &self,
+        placeholders: &Placeholders,
+        command: &[String],
+        setter_config: &Setter,
+        pidfile: &SetterPidFile,
+    ) -> Result<Option<Child>>;
+    fn cleanup(&self) -> Result<bool>;
+    fn current_wallpaper(&self, backend: Backend) -> Option<PathBuf>;
+    fn restore_wallpaper(&self, backend: Backend, path: &Path) -> Result<()>;
+}
+
+/// Real, actual wallpaper setter.
+struct DefaultSetter {}
+impl WallpaperSetter for DefaultSetter {
+    fn set_wallpaper(
+        &self,
+        placeholders: &Placeholders,
+        backends: &[Backend],
+        setter_config: &Setter,
+        pidfile: &SetterPidFile,
+    ) -> Result<Option<Child>> {
+        let mut failures = Vec::with_capacity(backends.len());
+        for backend in backends {
+            debug!("trying to set wallpaper using {backend} backend");
+            match set_wallpaper_backend(placeholders, *backend, setter_config, pidfile) {
+                Ok(process) => {
+                    debug!("wallpaper set using {backend} backend");
+                    return Ok(process);
+                }
+                Err(err) => {
+                    debug!("{backend} backend failed: {err:#}");
+                    failures.push(format!("{backend}: {err:#}"));
+                }
+            }
+        }
+
+        let config_location = Config::find_path().map_or_else(
+            |_| "in the configuration directory".to_string(),
+            |path| format!("at {}", path.display()),
+        );
+        Err(anyhow!(format!(
+            concat!(
+                "Automated wallpaper setting failed:\n{}\n",
+                "This is most likely caused by an unsupported DE or WM.\n",
+                "Run `timewall doctor` to see which backends were detected, ",
+                "or configure a custom wallpaper setting command in the config file.\n",
+                "You can find it {}"
+            ),
+            failures.join("\n"),
+            config_location
+        )))
+    }
+
+    fn set_wallpaper_custom_command(
+        &self,
+        placeholders: &Placeholders,
+        command: &[String],
+        setter_config: &Setter,
+        pidfile: &SetterPidFile,
+    ) -> Result<Option<Child>> {
+        let expanded_command = placeholders.expand_command(command);
+
+        if setter_config.long_running == Some(false) {
+            run_command_to_completion(&expanded_command, placeholders, setter_config)
+                .with_context(|| "failed to run custom command")?;
+            cleanup_pidfile(pidfile)?;
+            Ok(None)
+        } else {
+            let must_keep_running = setter_config.long_running == Some(true);
+            run_long_running_command(
+                &expanded_command,
+                placeholders,
+                setter_config,
+                pidfile,
+                must_keep_running,
+            )
+            .with_context(|| "failed to run custom command")
+        }
+    }
+
+    fn cleanup(&self) -> Result<bool> {
+        let mut did_terminate_any = false;
+        for pidfile in SetterPidFile::find_all()? {
+            did_terminate_any |= cleanup_pidfile(&pidfile)?;
+        }
+        Ok(did_terminate_any)
+    }
+
+    fn current_wallpaper(&self, backend: Backend) -> Option<PathBuf> {
+        let output = match backend {
+            Backend::Builtin | Backend::Plasma => wallpaper::get().ok()?,
+            Backend::Feh => fs::read_to_string(BaseDirs::new()?.home_dir().join(".fehbg")).ok()?,
+            _ => query_backend(backend)?,
+        };
+        let path = backend.parse_current_wallpaper(&output)?;
+        path.is_file().then_some(path)
+    }
+
+    fn restore_wallpaper(&self, backend: Backend, path: &Path) -> Result<()> {
+        let pidfile = SetterPidFile::find(0)?;
+        set_wallpaper_backend(
+            &Placeholders::for_image(path),
+            backend,
+            &Setter::default(),
+            &pidfile,
+        )?;
+        Ok(())
+    }
+}
+
+/// Run the query command of a backend and get what it printed.
+fn query_backend(backend: Backend) -> Option<String> {
+    let (program, args) = backend.query_command()?.split_first()?;
+    let output = Command::new(program)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Set wallpaper using a given backend. Returns the process if the backend is long-running.
+fn set_wallpaper_backend(
+    placeholders: &Placeholders,
+    backend: Backend,
+    setter_config: &Setter,
+    pidfile: &SetterPidFile,
+) -> Result<Option<Child>> {
+    let placeholders = Placeholders {
+        image: placeholders.image.canonicalize()?,
+        ..placeholders.clone()
+    };
+
+    if backend == Backend::Builtin {
+        let image_path = placeholders
+            .image
+            .to_str()
+            .context("image path is not valid UTF-8")?;
+        wallpaper::set_from_path(image_path).map_err(|err| anyhow!("{err}"))?;
+        return Ok(None);
+    }
+
+    let mut process = None;
+    for command in backend.commands() {
+        let expanded_command = placeholders.expand_command(command);
+        if backend.is_long_running() {
+            process = run_long_running_command(
+                &expanded_command,
+                &placeholders,
+                setter_config,
+                pidfile,
+                true,
+            )?;
+        } else {
+            run_command_to_completion(&expanded_command, &placeholders, setter_config)?;
+        }
+    }
+
+    Ok(process)
+}
+
+/// Spawn a command which keeps running, replacing the previous process of the same setter.
+///
+/// Unless the command must keep running, it may also finish successfully. It's then waited for up
+/// to the setter timeout, or the overlap if it's longer, and there's no process to keep.
+/// Pidfile is only saved for a process which is still running.
+fn run_long_running_command(
+    command: &[String],
+    placeholders: &Placeholders,
+    setter_config: &Setter,
+    pidfile: &SetterPidFile,
+    must_keep_running: bool,
+) -> Result<Option<Child>> {
+    let mut wallpaper_process = spawn_command(command, placeholders, setter_config.quiet)?;
+
+    let exit_status = if must_keep_running {
+        thread::sleep(Duration::from_millis(setter_config.overlap));
+        wallpaper_process.try_wait()?
+    } else {
+        let wait_time = max(setter_config.overlap, setter_config.timeout);
+        wait_with_timeout(&mut wallpaper_process, Duration::from_millis(wait_time))?
+    };
+    if let Some(status) = exit_status {
+        if !status.success() {
+            bail!("`{}` exited with {status}", command.join(" "));
+        }
+    }
+    cleanup_pidfile(pidfile)?;
+    match exit_status {
+        Some(_) if !must_keep_running => {
+            debug!(
+                "`{}` finished after setting the wallpaper",
+                command.join(" ")
+            );
+            Ok(None)
+        }
+        // The process already exited, so it's left for the supervisor to restart.
+        Some(_) => Ok(Some(wallpaper_process)),
+        None => {
+            pidfile.save(wallpaper_process.id())?;
+            Ok(Some(wallpaper_process))
+        }
+    }
+}
+
+/// Terminate the process saved in a pidfile, if any, and remove the pidfile.
+///
+/// Pidfile which can't be read is stale, so it's just removed.
+fn cleanup_pidfile(pidfile: &SetterPidFile) -> Result<bool> {
+    match pidfile.read() {
+        Ok(Some(last_pid)) => {
+            let did_terminate = terminate_process_if_exists(last_pid)
+                .context("failed to cleanup setter process")?;
+            pidfile.clear()?;
+            Ok(did_terminate)
+        }
+        Ok(None) => Ok(false),
+        Err(err) => {
+            warn!("removing stale setter pidfile: {err:#}");
+            pidfile.clear()?;
+            Ok(false)
+        }
+    }
+}
+
+/// Dry run setter, mainly for use in tests.
+/// Instead of actually setting the wallpaper, prints out the actions.
+/// Current wallpaper can be faked with `TIMEWALL_DRY_RUN_CURRENT_WALLPAPER` variable.
+struct DryRunSetter;
+impl WallpaperSetter for DryRunSetter {
+    fn set_wallpaper(
+        &self,
+        placeholders: &Placeholders,
+        _backends: &[Backend],
+        _setter_config: &Setter,
+        _pidfile: &SetterPidFile,
+    ) -> Result<Option<Child>> {
+        println!("Set: {}", placeholders.image.display());
+        Ok(None)
+    }
+
+    fn set_wallpaper_custom_command(
+        &self,
+        placeholders: &Placeholders,
+        command: &[String],
+        _setter_config: &Setter,
+        _pidfile: &SetterPidFile,
+    ) -> Result<Option<Child>> {
+        let expanded_command = placeholders.expand_command(command);
+        println!("Run: {}", expanded_command.join(" "));
+        Ok(None)
+    }
+
+    fn cleanup(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn current_wallpaper(&self, _backend: Backend) -> Option<PathBuf> {
+        env::var("TIMEWALL_DRY_RUN_CURRENT_WALLPAPER")
+            .ok()
+            .map(PathBuf::from)
+    }
+
+    fn restore_wallpaper(&self, _backend: Backend, path: &Path) -> Result<()> {
+        println!("Restore: {}", path.display());
+        Ok(())
+    }
+}
+
+fn make_output_handle(quiet: bool) -> Stdio {
+    if quiet {
+        Stdio::null()
+    } else {
+        Stdio::inherit()
+    }
+}
+
+/// Build a process command with placeholders passed as environment variables.
+fn build_command(command: &[String], placeholders: &Placeholders) -> Result<Command> {
+    let (program, args) = command.split_first().context("command is empty")?;
+    let mut process_command = Command::new(program);
+    process_command.args(args).envs(placeholders.env_vars());
+    debug!("running command: {process_command:?}");
+    Ok(process_command)
+}
+
+pub fn spawn_command(
+    command: &[String],
+    placeholders: &Placeholders,
+    quiet: bool,
+) -> Result<Child> {
+    build_command(command, placeholders)?
+        .stdout(make_output_handle(quiet))
+        .stderr(make_output_handle(quiet))
+        .spawn()
+        .with_context(|| format!("failed to run `{}`", command[0]))
+}
+
+/// Run a command and wait for it to finish, but no longer than the setter timeout.
+///
+/// Fails if the command exits with non-zero status or doesn't finish in time.
+/// Error contains the captured `stderr` of the command.
+fn run_command_to_completion(
+    command: &[String],
+    placeholders: &Placeholders,
+    setter_config: &Setter,
+) -> Result<()> {
+    let mut process = build_command(command, placeholders)?
+        .stdout(make_output_handle(setter_config.quiet))
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run `{}`", command[0]))?;
+    // Read stderr in the background, so the process can't block on a full pipe.
+    let stderr_reader = process.stderr.take().map(|mut stderr| {
+        thread::spawn(move || {
+            let mut stderr_buf = String::new();
+            let _ = stderr.read_to_string(&mut stderr_buf);
+            stderr_buf
+        })
+    });
+
+    let timeout = Duration::from_millis(setter_config.timeout);
+    let Some(status) = wait_with_timeout(&mut process, timeout)? else {
+        let _ = process.kill();
+        let _ = process.wait();
+        bail!(
+            "`{}` did not finish within {} ms",
+            command.join(" "),
+            setter_config.timeout
+        );
+    };
+
+    let stderr = stderr_reader
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+    if !setter_config.quiet {
+        eprint!("{stderr}");
+    }
+    if !status.success() {
+        bail!(
+            "`{}` exited with {}: {}",
+            command.join(" "),
+            status,
+            stderr.trim()
+        );
+    }
+
+    Ok(())
+}
+
+/// Wait for a process to exit. Returns `None` if it's still running after the timeout.
+pub fn wait_with_timeout(process: &mut Child, timeout: Duration) -> Result<Option<ExitStatus>> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    let start = Instant::now();
+    loop {
+        if let Some(status) = process.try_wait()? {
+            return Ok(Some(status));
+        }
+        if start.elapsed() >= timeout {
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn terminate_process_if_exists(pid: u32) -> Result<bool> {
+    debug!("Sending SIGTERM to process: {pid}");
+    #[allow(clippy::cast_possible_wrap, reason = "std uses u32 because of windows")]
+    let pid = Pid::from_raw(pid as i32);
+    match kill(pid, Signal::SIGTERM) {
+        Ok(()) => Ok(true),
+        Err(Errno::ESRCH) => Ok(false),
+        Err(errno) => Err(anyhow!("Failed to SIGTERM process: {pid}, errno: {errno}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rstest::*;
+
+    use super::*;
+    use crate::appearance::Appearance;
+
+    fn placeholders(appearance: Appearance) -> Placeholders {
+        Placeholders {
+            image: PathBuf::from("0.png"),
+            image_index: 0,
+            frame_number: 1,
+            frame_count: 2,
+            schedule_type: "time",
+            appearance,
+            wallpaper: PathBuf::from("wall.heic"),
+            output: None,
+        }
+    }
+
+    fn command(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(ToString::to_string).collect()
+    }
+
+    fn output(name: &str) -> Output {
+        Output {
+            name: name.to_owned(),
+            resolution: None,
+        }
+    }
+
+    #[rstest]
+    #[case(Appearance::Light, None, true)]
+    #[case(Appearance::Dark, None, true)]
+    #[case(Appearance::Dark, Some(Appearance::Dark), true)]
+    #[case(Appearance::Light, Some(Appearance::Dark), false)]
+    fn test_should_run_appearance(
+        #[case] appearance: Appearance,
+        #[case] required_appearance: Option<Appearance>,
+        #[case] expected: bool,
+    ) {
+        let setter_config = Setter {
+            appearance: required_appearance,
+            ..Setter::default()
+        };
+        assert_eq!(
+            should_run(&setter_config, &placeholders(appearance), &[]),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(None, true)]
+    #[case(Some("DP-1"), true)]
+    #[case(Some("HDMI-A-1"), false)]
+    fn test_should_run_output(#[case] required_output: Option<&str>, #[case] expected: bool) {
+        let setter_config = Setter {
+            output: required_output.map(ToOwned::to_owned),
+            ..Setter::default()
+        };
+        let outputs = [output("DP-1"), output("eDP-1")];
+        assert_eq!(
+            should_run(&setter_config, &placeholders(Appearance::Light), &outputs),
+            expected
+        );
+    }
+
+    #[rstest]
+    fn test_get_restore_backends() {
+        let setter_configs = [
+            Setter {
+                command: Some(command(&["cp", "%f", "lockscreen.png"])),
+                ..Setter::default()
+            },
+            Setter {
+                backends: vec![Backend::Swaybg, Backend::Hyprpaper, Backend::Feh],
+                ..Setter::default()
+            },
+        ];
+
+        assert_eq!(
+            get_restore_backends(&setter_configs),
+            vec![Backend::Hyprpaper, Backend::Feh]
+        );
+    }
+
+    #[rstest]
+    fn test_run_command_to_completion_success() {
+        let result = run_command_to_completion(
+            &command(&["true"]),
+            &placeholders(Appearance::Light),
+            &Setter::default(),
+        );
+        assert!(result.is_ok());
+    }
+
+    #[rstest]
+    fn test_run_command_to_completion_failure_includes_stderr() {
+        let result = run_command_to_completion(
+            &command(&["sh", "-c", "echo 'no such schema' >&2; exit 3"]),
+            &placeholders(Appearance::Light),
+            &Setter::default(),
+        );
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(error.contains("exit status: 3"));
+        assert!(error.contains("no such schema"));
+    }
+
+    #[rstest]
+    fn test_run_command_to_completion_timeout() {
+        let setter_config = Setter {
+            timeout: 50,
+            ..Setter::default()
+        };
+        let result = run_command_to_completion(
+            &command(&["sleep", "5"]),
+            &placeholders(Appearance::Light),
+            &setter_config,
+        );
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(error.contains("did not finish within 50 ms"));
+    }
+
+    #[rstest]
+    #[case::finished(&["true"], false, false, false)]
+    #[case::finished_after_overlap(&["sleep", "0.2"], false, false, false)]
+    #[case::running(&["sleep", "5"], false, true, true)]
+    #[case::finished_must_keep_running(&["true"], true, true, false)]
+    fn test_run_long_running_command(
+        #[case] parts: &[&str],
+        #[case] must_keep_running: bool,
+        #[case] expected_process: bool,
+        #[case] expected_pidfile: bool,
+    ) {
+        let tmp_dir = assert_fs::TempDir::new().unwrap();
+        let pidfile = SetterPidFile::load(tmp_dir.path().join("setter_0.pid")).unwrap();
+        let setter_config = Setter {
+            overlap: 100,
+            timeout: 1000,
+            ..Setter::default()
+        };
+
+        let process = run_long_running_command(
+            &command(parts),
+            &placeholders(Appearance::Light),
+            &setter_config,
+            &pidfile,
+            must_keep_running,
+        )
+        .unwrap();
+
+        assert_eq!(process.is_some(), expected_process);
+        assert_eq!(pidfile.read().unwrap().is_some(), expected_pidfile);
+        if let Some(mut process) = process {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+    }
+
+    #[rstest]
+    fn test_run_long_running_command_failure() {
+        let tmp_dir = assert_fs::TempDir::new().unwrap();
+        let pidfile = SetterPidFile::load(tmp_dir.path().join("setter_0.pid")).unwrap();
+        let setter_config = Setter {
+            overlap: 0,
+            timeout: 1000,
+            ..Setter::default()
+        };
+
+        let result = run_long_running_command(
+            &command(&["sh", "-c", "sleep 0.2; exit 1"]),
+            &placeholders(Appearance::Light),
+            &setter_config,
+            &pidfile,
+            false,
+        );
+
+        assert!(format!("{:#}", result.unwrap_err()).contains("exit status: 1"));
+    }
+
+    #[rstest]
+    fn test_spawn_empty_command() {
+        let result = spawn_command(&[], &placeholders(Appearance::Light), true);
+
+        assert!(result.is_err());
+    }
+}
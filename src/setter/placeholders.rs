@@ -0,0 +1,174 @@
+use std::path::{Path, PathBuf};
+
+use crate::appearance::Appearance;
+
+/// Values which can be substituted into commands using placeholders like `%f`.
+/// The same values are also exported to spawned commands as `TIMEWALL_*` environment variables.
+#[derive(Clone, Debug)]
+pub struct Placeholders {
+    /// Path to the image being set (`%f`).
+    pub image: PathBuf,
+    /// Index of the image in the wallpaper (`%i`).
+    pub image_index: usize,
+    /// Number of the frame in the schedule order, starting from 1 (`%n`).
+    pub frame_number: usize,
+    /// Number of frames in the wallpaper (`%N`).
+    pub frame_count: usize,
+    /// Type of the wallpaper schedule (`%s`).
+    pub schedule_type: &'static str,
+    /// Resolved system or user appearance (`%a`).
+    pub appearance: Appearance,
+    /// Path to the source wallpaper file (`%w`).
+    pub wallpaper: PathBuf,
+    /// Output the setter is limited to, if any (`%o`).
+    pub output: Option<String>,
+}
+
+impl Placeholders {
+    /// Get placeholders for a standalone image, e.g. the wallpaper set before timewall.
+    pub fn for_image(image: &Path) -> Self {
+        Self {
+            image: image.to_path_buf(),
+            image_index: 0,
+            frame_number: 1,
+            frame_count: 1,
+            schedule_type: "",
+            appearance: Appearance::Light,
+            wallpaper: image.to_path_buf(),
+            output: None,
+        }
+    }
+
+    /// Get a copy with the output replaced.
+    pub fn with_output(&self, output: Option<&str>) -> Self {
+        Self {
+            output: output.map(ToOwned::to_owned),
+            ..self.clone()
+        }
+    }
+
+    /// Get the value for a given placeholder character, if it's known.
+    fn value(&self, placeholder: char) -> Option<String> {
+        let value = match placeholder {
+            'f' => self.image.display().to_string(),
+            'i' => self.image_index.to_string(),
+            'n' => self.frame_number.to_string(),
+            'N' => self.frame_count.to_string(),
+            's' => self.schedule_type.to_owned(),
+            'a' => self.appearance.to_string(),
+            'w' => self.wallpaper.display().to_string(),
+            'o' => self.output.clone().unwrap_or_default(),
+            '%' => String::from("%"),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Replace all placeholders in a given string. Unknown placeholders are left untouched.
+    pub fn expand(&self, template: &str) -> String {
+        let mut expanded = String::with_capacity(template.len());
+        let mut chars = template.chars();
+        while let Some(char) = chars.next() {
+            if char != '%' {
+                expanded.push(char);
+                continue;
+            }
+            let Some(placeholder) = chars.next() else {
+                expanded.push('%');
+                break;
+            };
+            if let Some(value) = self.value(placeholder) {
+                expanded.push_str(&value);
+            } else {
+                expanded.push('%');
+                expanded.push(placeholder);
+            }
+        }
+        expanded
+    }
+
+    /// Replace all placeholders in each part of a given command.
+    pub fn expand_command<S: AsRef<str>>(&self, command: &[S]) -> Vec<String> {
+        command
+            .iter()
+            .map(|item| self.expand(item.as_ref()))
+            .collect()
+    }
+
+    /// Get environment variables corresponding to the placeholders.
+    pub fn env_vars(&self) -> Vec<(&'static str, String)> {
+        [
+            ("TIMEWALL_IMAGE", 'f'),
+            ("TIMEWALL_IMAGE_INDEX", 'i'),
+            ("TIMEWALL_FRAME", 'n'),
+            ("TIMEWALL_FRAME_COUNT", 'N'),
+            ("TIMEWALL_SCHEDULE", 's'),
+            ("TIMEWALL_APPEARANCE", 'a'),
+            ("TIMEWALL_WALLPAPER", 'w'),
+            ("TIMEWALL_OUTPUT", 'o'),
+        ]
+        .into_iter()
+        .map(|(name, placeholder)| (name, self.value(placeholder).unwrap()))
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    #[fixture]
+    fn placeholders() -> Placeholders {
+        Placeholders {
+            image: PathBuf::from("/cache/abc/3.png"),
+            image_index: 3,
+            frame_number: 5,
+            frame_count: 16,
+            schedule_type: "solar",
+            appearance: Appearance::Dark,
+            wallpaper: PathBuf::from("/walls/mojave.heic"),
+            output: Some("DP-1".to_owned()),
+        }
+    }
+
+    #[rstest]
+    #[case("%f", "/cache/abc/3.png")]
+    #[case("file://%f", "file:///cache/abc/3.png")]
+    #[case("%i/%n/%N", "3/5/16")]
+    #[case("%s-%a", "solar-dark")]
+    #[case("%w", "/walls/mojave.heic")]
+    #[case("--output=%o", "--output=DP-1")]
+    #[case("100%%", "100%")]
+    #[case("%%f", "%f")]
+    #[case("%x%", "%x%")]
+    #[case("no placeholders", "no placeholders")]
+    fn test_expand(placeholders: Placeholders, #[case] template: &str, #[case] expected: &str) {
+        assert_eq!(placeholders.expand(template), expected);
+    }
+
+    #[rstest]
+    fn test_expand_command(placeholders: Placeholders) {
+        let command = ["feh", "--bg-fill", "%f"];
+        assert_eq!(
+            placeholders.expand_command(&command),
+            vec!["feh", "--bg-fill", "/cache/abc/3.png"]
+        );
+    }
+
+    #[rstest]
+    fn test_expand_no_output(placeholders: Placeholders) {
+        let placeholders = placeholders.with_output(None);
+        assert_eq!(placeholders.expand("[%o]"), "[]");
+    }
+
+    #[rstest]
+    fn test_env_vars(placeholders: Placeholders) {
+        let env_vars = placeholders.env_vars();
+        assert!(env_vars.contains(&("TIMEWALL_IMAGE", "/cache/abc/3.png".to_owned())));
+        assert!(env_vars.contains(&("TIMEWALL_FRAME_COUNT", "16".to_owned())));
+        assert!(env_vars.contains(&("TIMEWALL_APPEARANCE", "dark".to_owned())));
+        assert!(env_vars.contains(&("TIMEWALL_OUTPUT", "DP-1".to_owned())));
+    }
+}
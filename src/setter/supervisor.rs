@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+use std::process::Child;
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+/// Maximum number of restarts allowed within [`RESTART_WINDOW`].
+const MAX_RESTARTS: usize = 5;
+/// Time window in which restarts are counted for the crash loop protection.
+const RESTART_WINDOW: Duration = Duration::from_secs(60);
+
+/// Long-running process spawned by a setter, e.g. `swaybg`.
+#[derive(Debug)]
+pub struct SetterProcess {
+    /// Index of the setter in the config which spawned the process.
+    pub setter_index: usize,
+    child: Child,
+    /// Whether the process may finish on its own, so only its failure is unexpected.
+    may_finish: bool,
+}
+
+impl SetterProcess {
+    pub const fn new(setter_index: usize, child: Child, may_finish: bool) -> Self {
+        Self {
+            setter_index,
+            child,
+            may_finish,
+        }
+    }
+}
+
+/// Keeps track of long-running setter processes and detects when they exit unexpectedly.
+#[derive(Debug, Default)]
+pub struct Supervisor {
+    /// Processes which should keep running.
+    processes: Vec<SetterProcess>,
+    /// Replaced processes which are expected to exit, kept only to reap them.
+    retired: Vec<Child>,
+    /// Times of recent restarts, used for the crash loop protection.
+    restarts: VecDeque<Instant>,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace all supervised processes with new ones, e.g. after the wallpaper changed.
+    /// Exit of the replaced processes is expected and won't be reported.
+    pub fn replace(&mut self, processes: Vec<SetterProcess>) {
+        self.retired
+            .extend(self.processes.drain(..).map(|process| process.child));
+        self.processes = processes;
+    }
+
+    /// Add processes to supervise, e.g. after restarting the ones which exited.
+    pub fn add(&mut self, processes: Vec<SetterProcess>) {
+        self.processes.extend(processes);
+    }
+
+    /// Reap processes which exited and return indices of setters whose processes exited
+    /// unexpectedly.
+    pub fn reap_exited(&mut self) -> Vec<usize> {
+        self.retired
+            .retain_mut(|child| !matches!(child.try_wait(), Ok(Some(_)) | Err(_)));
+
+        let mut exited = Vec::new();
+        self.processes
+            .retain_mut(|process| match process.child.try_wait() {
+                Ok(None) => true,
+                Ok(Some(status)) if process.may_finish && status.success() => {
+                    debug!("process of setter {} finished", process.setter_index);
+                    false
+                }
+                Ok(Some(status)) => {
+                    debug!(
+                        "process of setter {} exited with {status}",
+                        process.setter_index
+                    );
+                    exited.push(process.setter_index);
+                    false
+                }
+                Err(err) => {
+                    debug!(
+                        "failed to check process of setter {}: {err}",
+                        process.setter_index
+                    );
+                    exited.push(process.setter_index);
+                    false
+                }
+            });
+        exited
+    }
+
+    /// Record a restart at a given time and check whether it's allowed.
+    ///
+    /// Restarts are refused if processes exited too many times recently,
+    /// to avoid restarting a crashing process in a loop.
+    pub fn allow_restart(&mut self, now: Instant) -> bool {
+        while self
+            .restarts
+            .front()
+            .is_some_and(|restart| now.duration_since(*restart) > RESTART_WINDOW)
+        {
+            self.restarts.pop_front();
+        }
+        if self.restarts.len() >= MAX_RESTARTS {
+            return false;
+        }
+        self.restarts.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+    use std::thread;
+
+    use rstest::*;
+
+    use super::*;
+
+    fn spawn(command: &str) -> Child {
+        Command::new("sh").args(["-c", command]).spawn().unwrap()
+    }
+
+    fn reap_exited_eventually(supervisor: &mut Supervisor) -> Vec<usize> {
+        for _ in 0..100 {
+            let exited = supervisor.reap_exited();
+            if !exited.is_empty() {
+                return exited;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Vec::new()
+    }
+
+    #[rstest]
+    fn test_reap_exited() {
+        let mut supervisor = Supervisor::new();
+        supervisor.replace(vec![
+            SetterProcess::new(0, spawn("sleep 5"), false),
+            SetterProcess::new(2, spawn("exit 1"), false),
+        ]);
+
+        assert_eq!(reap_exited_eventually(&mut supervisor), vec![2]);
+        assert_eq!(supervisor.processes.len(), 1);
+
+        supervisor.processes[0].child.kill().unwrap();
+        assert_eq!(reap_exited_eventually(&mut supervisor), vec![0]);
+    }
+
+    #[rstest]
+    fn test_reap_exited_ignores_replaced() {
+        let mut supervisor = Supervisor::new();
+        supervisor.replace(vec![SetterProcess::new(0, spawn("exit 0"), false)]);
+        supervisor.replace(vec![SetterProcess::new(0, spawn("sleep 5"), false)]);
+
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(supervisor.reap_exited(), Vec::<usize>::new());
+        assert!(supervisor.retired.is_empty());
+
+        supervisor.processes[0].child.kill().unwrap();
+    }
+
+    #[rstest]
+    fn test_reap_exited_allows_finishing() {
+        let mut supervisor = Supervisor::new();
+        supervisor.replace(vec![
+            SetterProcess::new(0, spawn("exit 0"), true),
+            SetterProcess::new(1, spawn("sleep 0.1; exit 1"), true),
+        ]);
+
+        assert_eq!(reap_exited_eventually(&mut supervisor), vec![1]);
+        assert!(supervisor.processes.is_empty());
+    }
+
+    #[rstest]
+    fn test_allow_restart() {
+        let mut supervisor = Supervisor::new();
+        let start = Instant::now();
+
+        for _ in 0..MAX_RESTARTS {
+            assert!(supervisor.allow_restart(start));
+        }
+        assert!(!supervisor.allow_restart(start + Duration::from_secs(30)));
+        assert!(supervisor.allow_restart(start + RESTART_WINDOW + Duration::from_secs(1)));
+    }
+}
@@ -12,6 +12,7 @@ use crate::appearance;
 pub enum WakeEvent {
     Terminated,
     ThemeChanged,
+    ChildExited,
 }
 
 pub fn start_signal_handler(mut signals: Signals, wake_tx: Sender<WakeEvent>) {
@@ -26,6 +27,16 @@ pub fn start_signal_handler(mut signals: Signals, wake_tx: Sender<WakeEvent>) {
     });
 }
 
+pub fn start_child_exit_handler(mut signals: Signals, wake_tx: Sender<WakeEvent>) {
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            if wake_tx.send(WakeEvent::ChildExited).is_err() {
+                break;
+            }
+        }
+    });
+}
+
 pub fn start_appearance_change_handler(wake_tx: Sender<WakeEvent>) {
     appearance::start_appearance_listener(move || {
         let _ = wake_tx.send(WakeEvent::ThemeChanged);
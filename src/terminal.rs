@@ -0,0 +1,470 @@
+use std::{
+    env,
+    fmt::Write as _,
+    io::{self, Write},
+    os::fd::AsRawFd,
+};
+
+use anyhow::Result;
+use base64::Engine;
+use log::debug;
+
+use crate::image::{png, resize::resize, RgbImage};
+
+/// Terminal size used when it can't be queried, e.g. when the output is not a terminal.
+const DEFAULT_SIZE: TerminalSize = TerminalSize {
+    columns: 80,
+    rows: 24,
+    cell_width: 10,
+    cell_height: 20,
+};
+/// Maximum size of a single chunk of data sent with the kitty graphics protocol.
+const KITTY_CHUNK_SIZE: usize = 4096;
+/// ID of the displayed image, so each frame replaces the previous one in kitty.
+const KITTY_IMAGE_ID: u32 = 0x7469_6d65;
+/// Number of levels of each channel in the sixel palette, making a color cube.
+const SIXEL_LEVELS: u8 = 6;
+
+nix::ioctl_read_bad!(get_window_size, nix::libc::TIOCGWINSZ, nix::libc::winsize);
+
+/// Way of drawing images in the terminal.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Graphics {
+    /// Kitty graphics protocol, supported by kitty, `WezTerm`, Ghostty and others.
+    Kitty,
+    /// Sixel graphics, supported by foot, mlterm, xterm with sixel enabled and others.
+    Sixel,
+    /// Unicode upper half blocks with 24-bit colors, two pixels per cell. Works everywhere.
+    Blocks,
+}
+
+impl Graphics {
+    /// Guess the graphics supported by the current terminal from its environment variables.
+    pub fn detect() -> Self {
+        Self::detect_from(|name| env::var(name).ok())
+    }
+
+    fn detect_from<F: Fn(&str) -> Option<String>>(get_var: F) -> Self {
+        let term = get_var("TERM").unwrap_or_default();
+        let term_program = get_var("TERM_PROGRAM").unwrap_or_default();
+
+        let graphics = if get_var("KITTY_WINDOW_ID").is_some()
+            || ["xterm-kitty", "xterm-ghostty"].contains(&term.as_str())
+            || ["WezTerm", "ghostty"].contains(&term_program.as_str())
+        {
+            Self::Kitty
+        } else if term.contains("sixel")
+            || ["foot", "foot-extra", "mlterm", "yaft-256color"].contains(&term.as_str())
+            || term_program == "iTerm.app"
+        {
+            Self::Sixel
+        } else {
+            Self::Blocks
+        };
+        debug!("detected terminal graphics: {graphics:?}");
+        graphics
+    }
+}
+
+/// Size of the terminal window in cells, along with the size of a single cell in pixels.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TerminalSize {
+    pub columns: u32,
+    pub rows: u32,
+    pub cell_width: u32,
+    pub cell_height: u32,
+}
+
+impl TerminalSize {
+    /// Query the size of the terminal attached to stdout.
+    pub fn query() -> Self {
+        let mut winsize = nix::libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: TIOCGWINSZ only writes into the given winsize struct.
+        let result = unsafe { get_window_size(io::stdout().as_raw_fd(), &raw mut winsize) };
+        if result.is_err() || winsize.ws_col == 0 || winsize.ws_row == 0 {
+            debug!("couldn't query terminal size, assuming {DEFAULT_SIZE:?}");
+            return DEFAULT_SIZE;
+        }
+
+        let (columns, rows) = (u32::from(winsize.ws_col), u32::from(winsize.ws_row));
+        // Some terminals don't report their size in pixels.
+        let (cell_width, cell_height) = if winsize.ws_xpixel == 0 || winsize.ws_ypixel == 0 {
+            (DEFAULT_SIZE.cell_width, DEFAULT_SIZE.cell_height)
+        } else {
+            (
+                (u32::from(winsize.ws_xpixel) / columns).max(1),
+                (u32::from(winsize.ws_ypixel) / rows).max(1),
+            )
+        };
+        Self {
+            columns,
+            rows,
+            cell_width,
+            cell_height,
+        }
+    }
+}
+
+/// Image scaled and encoded for displaying in the terminal.
+#[derive(Debug)]
+pub struct TerminalImage {
+    /// Escape sequences drawing the image at the cursor position.
+    data: String,
+    /// Number of terminal rows taken by the image.
+    rows: u32,
+}
+
+impl TerminalImage {
+    /// Scale the image to fit into the terminal, leaving one row for the prompt, and encode it.
+    pub fn new(image: &RgbImage, graphics: Graphics, size: TerminalSize) -> Result<Self> {
+        let (cell_width, cell_height) = match graphics {
+            Graphics::Kitty | Graphics::Sixel => (size.cell_width, size.cell_height),
+            Graphics::Blocks => (1, 2),
+        };
+        let (width, height, columns, rows) = fit_image(
+            image.width,
+            image.height,
+            size.columns,
+            size.rows.saturating_sub(1).max(1),
+            cell_width,
+            cell_height,
+        );
+        let scaled = resize(image, width, height);
+        let data = match graphics {
+            Graphics::Kitty => encode_kitty(&scaled, columns, rows)?,
+            Graphics::Sixel => encode_sixel(&scaled),
+            Graphics::Blocks => encode_blocks(&scaled),
+        };
+        Ok(Self { data, rows })
+    }
+}
+
+/// Draws images in the terminal, each one in place of the previous one.
+#[derive(Debug, Default)]
+pub struct TerminalDisplay {
+    /// Number of rows reserved for the images so far.
+    reserved_rows: u32,
+}
+
+impl TerminalDisplay {
+    /// Draw the image below the cursor, replacing the previously drawn one.
+    pub fn show(&mut self, image: &TerminalImage) -> Result<()> {
+        let mut stdout = io::stdout().lock();
+        if self.reserved_rows > 0 {
+            // Go back to the top of the previous image, it's going to be drawn over.
+            write!(stdout, "\x1b[{}A\r", self.reserved_rows)?;
+        }
+        // Reserve space first, so the terminal scrolls before the image is drawn, and not while
+        // it's drawn, which would make it impossible to return to its top. Cursor ends up on the
+        // last row of the image, so it's then moved to the row right after the reserved space.
+        let reserved_rows = self.reserved_rows.max(image.rows);
+        write!(
+            stdout,
+            "{}\x1b[{reserved_rows}A\r{}\r\x1b[{}B",
+            "\n".repeat(reserved_rows as usize),
+            image.data,
+            reserved_rows - image.rows + 1,
+        )?;
+        stdout.flush()?;
+        self.reserved_rows = reserved_rows;
+        Ok(())
+    }
+}
+
+/// Fit image into a given number of terminal cells, keeping its aspect ratio and never scaling it
+/// up. Returns the pixel width and height of the scaled image, and the number of columns and rows
+/// it takes.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    reason = "scaled sizes are positive and not bigger than the terminal"
+)]
+fn fit_image(
+    width: u32,
+    height: u32,
+    columns: u32,
+    rows: u32,
+    cell_width: u32,
+    cell_height: u32,
+) -> (u32, u32, u32, u32) {
+    let width_ratio = f64::from(columns * cell_width) / f64::from(width);
+    let height_ratio = f64::from(rows * cell_height) / f64::from(height);
+    let ratio = width_ratio.min(height_ratio).min(1.0);
+    let scaled_width = ((f64::from(width) * ratio).round() as u32).max(1);
+    let scaled_height = ((f64::from(height) * ratio).round() as u32).max(1);
+    (
+        scaled_width,
+        scaled_height,
+        scaled_width.div_ceil(cell_width),
+        scaled_height.div_ceil(cell_height),
+    )
+}
+
+/// Encode the image as PNG sent in chunks with the kitty graphics protocol, stretched over given
+/// number of columns and rows.
+fn encode_kitty(image: &RgbImage, columns: u32, rows: u32) -> Result<String> {
+    let mut png_data = Vec::new();
+    png::write(image.view(), 1, &mut png_data)?;
+    let encoded = base64::engine::general_purpose::STANDARD.encode(png_data);
+
+    let mut data = String::new();
+    let chunks = encoded
+        .as_bytes()
+        .chunks(KITTY_CHUNK_SIZE)
+        .collect::<Vec<_>>();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk)?;
+        if i == 0 {
+            write!(
+                data,
+                "\x1b_Ga=T,f=100,q=2,i={KITTY_IMAGE_ID},c={columns},r={rows},m={more};{chunk}\x1b\\"
+            )?;
+        } else {
+            write!(data, "\x1b_Gm={more};{chunk}\x1b\\")?;
+        }
+    }
+    Ok(data)
+}
+
+/// Encode the image as sixels, with colors reduced to a fixed color cube.
+fn encode_sixel(image: &RgbImage) -> String {
+    let colors = image
+        .data
+        .chunks_exact(RgbImage::CHANNELS)
+        .map(|pixel| {
+            let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(quantize_channel);
+            usize::from(r) * 36 + usize::from(g) * 6 + usize::from(b)
+        })
+        .collect::<Vec<_>>();
+    let (width, height) = (image.width as usize, image.height as usize);
+    let num_colors = usize::from(SIXEL_LEVELS).pow(3);
+
+    let mut data = format!("\x1bPq\"1;1;{width};{height}");
+    for color in 0..num_colors {
+        let [r, g, b] = [color / 36, color / 6 % 6, color % 6]
+            .map(|level| level * 100 / usize::from(SIXEL_LEVELS - 1));
+        let _ = write!(data, "#{color};2;{r};{g};{b}");
+    }
+
+    for band_y in (0..height).step_by(6) {
+        let band_height = (height - band_y).min(6);
+        let mut used_colors = vec![false; num_colors];
+        for y in band_y..band_y + band_height {
+            for &color in &colors[y * width..(y + 1) * width] {
+                used_colors[color] = true;
+            }
+        }
+
+        for color in (0..num_colors).filter(|&color| used_colors[color]) {
+            let _ = write!(data, "#{color}");
+            let sixels = (0..width).map(|x| {
+                (0..band_height)
+                    .filter(|dy| colors[(band_y + dy) * width + x] == color)
+                    .fold(0u8, |bits, dy| bits | (1 << dy))
+            });
+            write_sixel_run_lengths(&mut data, sixels);
+            // Return to the start of the band for the next color.
+            data.push('$');
+        }
+        data.push('-');
+    }
+    data.push_str("\x1b\\");
+    data
+}
+
+/// Write sixels, compressing repeated ones.
+fn write_sixel_run_lengths<I: Iterator<Item = u8>>(data: &mut String, sixels: I) {
+    let mut write_run = |sixel: u8, count: usize| {
+        let sixel = char::from(63 + sixel);
+        if count > 3 {
+            let _ = write!(data, "!{count}{sixel}");
+        } else {
+            data.extend(std::iter::repeat_n(sixel, count));
+        }
+    };
+
+    let mut run: Option<(u8, usize)> = None;
+    for sixel in sixels {
+        run = match run {
+            Some((run_sixel, count)) if run_sixel == sixel => Some((sixel, count + 1)),
+            Some((run_sixel, count)) => {
+                write_run(run_sixel, count);
+                Some((sixel, 1))
+            }
+            None => Some((sixel, 1)),
+        };
+    }
+    if let Some((sixel, count)) = run {
+        write_run(sixel, count);
+    }
+}
+
+/// Reduce 8-bit channel value to one of the sixel palette levels.
+fn quantize_channel(value: u8) -> u8 {
+    let levels = u16::from(SIXEL_LEVELS - 1);
+    u8::try_from((u16::from(value) * levels + 127) / 255).unwrap_or(SIXEL_LEVELS - 1)
+}
+
+/// Encode the image as lines of upper half blocks, with the foreground color being the upper
+/// pixel and the background color being the lower one.
+fn encode_blocks(image: &RgbImage) -> String {
+    let view = image.view();
+    let mut data = String::new();
+    for y in (0..image.height).step_by(2) {
+        for x in 0..image.width {
+            let [r, g, b] = view.pixel(x, y);
+            let _ = write!(data, "\x1b[38;2;{r};{g};{b}m");
+            if y + 1 < image.height {
+                let [r, g, b] = view.pixel(x, y + 1);
+                let _ = write!(data, "\x1b[48;2;{r};{g};{b}m");
+            }
+            data.push('▀');
+        }
+        data.push_str("\x1b[0m");
+        if y + 2 < image.height {
+            data.push('\n');
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rstest::*;
+
+    use super::*;
+
+    fn image(width: u32, height: u32, pixels: &[[u8; 3]]) -> RgbImage {
+        RgbImage {
+            width,
+            height,
+            data: pixels.concat(),
+        }
+    }
+
+    #[rstest]
+    #[case::kitty_env(&[("KITTY_WINDOW_ID", "1"), ("TERM", "xterm-256color")], Graphics::Kitty)]
+    #[case::kitty_term(&[("TERM", "xterm-kitty")], Graphics::Kitty)]
+    #[case::wezterm(&[("TERM_PROGRAM", "WezTerm")], Graphics::Kitty)]
+    #[case::foot(&[("TERM", "foot")], Graphics::Sixel)]
+    #[case::xterm_sixel(&[("TERM", "xterm-sixel")], Graphics::Sixel)]
+    #[case::xterm(&[("TERM", "xterm-256color")], Graphics::Blocks)]
+    #[case::nothing(&[], Graphics::Blocks)]
+    fn test_graphics_detect(#[case] vars: &[(&str, &str)], #[case] expected: Graphics) {
+        let vars = vars.iter().copied().collect::<HashMap<_, _>>();
+
+        let graphics = Graphics::detect_from(|name| vars.get(name).map(ToString::to_string));
+
+        assert_eq!(graphics, expected);
+    }
+
+    #[rstest]
+    #[case::fit_width(1000, 500, 50, 50, 10, 20, (500, 250, 50, 13))]
+    #[case::fit_height(1000, 1000, 100, 10, 10, 20, (200, 200, 20, 10))]
+    #[case::no_upscaling(100, 50, 100, 100, 10, 20, (100, 50, 10, 3))]
+    #[case::blocks(512, 512, 80, 23, 1, 2, (46, 46, 46, 23))]
+    fn test_fit_image(
+        #[case] width: u32,
+        #[case] height: u32,
+        #[case] columns: u32,
+        #[case] rows: u32,
+        #[case] cell_width: u32,
+        #[case] cell_height: u32,
+        #[case] expected: (u32, u32, u32, u32),
+    ) {
+        assert_eq!(
+            fit_image(width, height, columns, rows, cell_width, cell_height),
+            expected
+        );
+    }
+
+    #[rstest]
+    fn test_encode_blocks() {
+        let image = image(
+            2,
+            3,
+            &[
+                [1, 2, 3],
+                [4, 5, 6],
+                [7, 8, 9],
+                [0, 0, 0],
+                [9, 9, 9],
+                [5, 5, 5],
+            ],
+        );
+
+        assert_eq!(
+            encode_blocks(&image),
+            "\x1b[38;2;1;2;3m\x1b[48;2;7;8;9m▀\x1b[38;2;4;5;6m\x1b[48;2;0;0;0m▀\x1b[0m\n\
+             \x1b[38;2;9;9;9m▀\x1b[38;2;5;5;5m▀\x1b[0m"
+        );
+    }
+
+    #[rstest]
+    fn test_encode_sixel() {
+        let image = image(5, 2, &[[255, 0, 0]; 10]);
+
+        let data = encode_sixel(&image);
+
+        assert!(data.starts_with("\x1bPq\"1;1;5;2#0;2;0;0;0"));
+        assert!(data.contains("#180;2;100;0;0"));
+        // Red is #180, both rows of the band are set, repeated 5 times.
+        assert!(data.ends_with("#180!5B$-\x1b\\"));
+    }
+
+    #[rstest]
+    #[case(&[3, 3, 3], "BBB")]
+    #[case(&[3, 3, 3, 3, 4], "!4BC")]
+    #[case(&[0, 63, 63], "?~~")]
+    #[case(&[], "")]
+    fn test_write_sixel_run_lengths(#[case] sixels: &[u8], #[case] expected: &str) {
+        let mut data = String::new();
+
+        write_sixel_run_lengths(&mut data, sixels.iter().copied());
+
+        assert_eq!(data, expected);
+    }
+
+    #[rstest]
+    fn test_encode_kitty_chunks() {
+        // Noise compresses badly, so the data doesn't fit in a single chunk.
+        let mut state = 1u32;
+        let pixels = (0..64 * 64)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                let bytes = state.to_le_bytes();
+                [bytes[0], bytes[1], bytes[2]]
+            })
+            .collect::<Vec<_>>();
+        let image = image(64, 64, &pixels);
+
+        let data = encode_kitty(&image, 6, 3).unwrap();
+
+        assert!(data.starts_with(&format!(
+            "\x1b_Ga=T,f=100,q=2,i={KITTY_IMAGE_ID},c=6,r=3,m=1;"
+        )));
+        assert!(data.contains("\x1b\\\x1b_Gm=1;"));
+        assert!(data.contains("\x1b_Gm=0;"));
+        assert!(data.ends_with("\x1b\\"));
+    }
+
+    #[rstest]
+    #[case(0, 0)]
+    #[case(25, 0)]
+    #[case(26, 1)]
+    #[case(128, 3)]
+    #[case(255, 5)]
+    fn test_quantize_channel(#[case] value: u8, #[case] expected: u8) {
+        assert_eq!(quantize_channel(value), expected);
+    }
+}
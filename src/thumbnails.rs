@@ -0,0 +1,320 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use log::debug;
+
+use crate::{
+    heif,
+    image::{
+        font::{draw_text, fill_rect, text_height, text_width},
+        resize::scale_to_width,
+        write_image, Encoding, RgbImage,
+    },
+    schedule::{
+        get_image_index_order_appearance, get_image_index_order_h24, get_image_index_order_solar,
+        sort_solar_items, sort_time_items, time::day_fraction_to_time,
+    },
+    wallpaper::{metadata::get_apple_desktop_metadata_from_heif, properties::Properties},
+};
+
+/// Space between thumbnails in the contact sheet and around them, in pixels.
+const SHEET_GAP: u32 = 8;
+const SHEET_BACKGROUND: [u8; 3] = [32, 32, 32];
+const LABEL_COLOR: [u8; 3] = [255, 255, 255];
+/// Thumbnail width per a single step of the label scale.
+const LABEL_SCALE_STEP: u32 = 200;
+
+/// Single frame of the wallpaper schedule.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Frame {
+    pub image_index: usize,
+    /// Time or sun position at which the frame is displayed.
+    pub label: String,
+}
+
+/// Get all frames of the schedule, in the order in which they are displayed during the day.
+pub fn schedule_frames(properties: &Properties) -> Vec<Frame> {
+    let (indices, labels): (Vec<usize>, Vec<String>) = match properties {
+        Properties::H24(props) => (
+            get_image_index_order_h24(&props.time_info),
+            sort_time_items(&props.time_info)
+                .iter()
+                .map(|item| {
+                    day_fraction_to_time(item.time.into_inner())
+                        .format("%H:%M")
+                        .to_string()
+                })
+                .collect(),
+        ),
+        Properties::Solar(props) => (
+            get_image_index_order_solar(&props.solar_info),
+            sort_solar_items(&props.solar_info)
+                .iter()
+                .map(|item| format!("ALT {:.1}° AZ {:.1}°", item.altitude, item.azimuth))
+                .collect(),
+        ),
+        Properties::Appearance(props) => (
+            get_image_index_order_appearance(props),
+            vec!["LIGHT".to_string(), "DARK".to_string()],
+        ),
+    };
+    indices
+        .into_iter()
+        .zip(labels)
+        .map(|(image_index, label)| Frame { image_index, label })
+        .collect()
+}
+
+/// Render labelled thumbnails of all frames of the wallpaper, in schedule order.
+/// Thumbnails have a given width and keep the aspect ratio of the wallpaper.
+pub fn render_thumbnails<P: AsRef<Path>>(wallpaper_path: P, width: u32) -> Result<Vec<RgbImage>> {
+    let heif_ctx = heif::from_file(wallpaper_path)?;
+    let metadata = get_apple_desktop_metadata_from_heif(&heif_ctx)?;
+    let properties = Properties::from_apple_desktop(&metadata)?;
+    let frames = schedule_frames(&properties);
+
+    // Each image is scaled only once, even if it's displayed in many frames.
+    let scaled = heif::get_rgb_images(&heif_ctx)?
+        .iter()
+        .map(|image| scale_to_width(image, width))
+        .collect::<Vec<_>>();
+    debug!("rendering {} thumbnails", frames.len());
+
+    frames
+        .iter()
+        .map(|frame| {
+            let mut thumbnail = scaled
+                .get(frame.image_index)
+                .with_context(|| format!("frame uses nonexistent image {}", frame.image_index))?
+                .clone();
+            draw_label(&mut thumbnail, &frame.label);
+            Ok(thumbnail)
+        })
+        .collect()
+}
+
+/// Arrange thumbnails into a grid with a given number of columns.
+/// All thumbnails are expected to have the same size.
+pub fn contact_sheet(thumbnails: &[RgbImage], columns: u32) -> RgbImage {
+    let Some(first) = thumbnails.first() else {
+        return RgbImage::new(0, 0);
+    };
+    let (tile_width, tile_height) = (first.width + SHEET_GAP, first.height + SHEET_GAP);
+    let count = u32::try_from(thumbnails.len()).unwrap_or(u32::MAX);
+    let columns = columns.clamp(1, count);
+    let rows = count.div_ceil(columns);
+
+    let (width, height) = (
+        columns * tile_width + SHEET_GAP,
+        rows * tile_height + SHEET_GAP,
+    );
+    let mut sheet = RgbImage {
+        width,
+        height,
+        data: SHEET_BACKGROUND.repeat(width as usize * height as usize),
+    };
+    for (i, thumbnail) in (0..).zip(thumbnails) {
+        let x = SHEET_GAP + (i % columns) * tile_width;
+        let y = SHEET_GAP + (i / columns) * tile_height;
+        paste(&mut sheet, thumbnail, x, y);
+    }
+    sheet
+}
+
+/// Get the default number of contact sheet columns, making the grid roughly square.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    reason = "number of frames is small and positive"
+)]
+pub fn default_columns(count: usize) -> u32 {
+    let count = u32::try_from(count).unwrap_or(u32::MAX);
+    (f64::from(count).sqrt().ceil() as u32).max(1)
+}
+
+/// Write thumbnails as separate files named by their frame number, starting from 1:
+/// `frame_1.png`, `frame_2.png`...
+pub fn write_thumbnails<P: AsRef<Path>>(
+    thumbnails: &[RgbImage],
+    dest_dir_path: P,
+    encoding: Encoding,
+) -> Result<()> {
+    let dest_dir_path = dest_dir_path.as_ref();
+    for (frame, thumbnail) in (1..).zip(thumbnails) {
+        let path = dest_dir_path.join(format!("frame_{frame}.{}", encoding.format.extension()));
+        debug!("writing thumbnail to {}", path.display());
+        write_image(thumbnail.view(), encoding, path)?;
+    }
+    Ok(())
+}
+
+/// Draw the label on a darkened strip at the bottom of the thumbnail.
+fn draw_label(thumbnail: &mut RgbImage, label: &str) {
+    let scale = (thumbnail.width / LABEL_SCALE_STEP).max(1);
+    let padding = 2 * scale;
+    let strip_height = text_height(scale) + 2 * padding;
+    let strip_y = thumbnail.height.saturating_sub(strip_height);
+
+    fill_rect(
+        thumbnail,
+        0,
+        strip_y,
+        thumbnail.width,
+        strip_height,
+        |pixel| pixel.map(|value| value / 2),
+    );
+    let text_x = thumbnail.width.saturating_sub(text_width(label, scale)) / 2;
+    draw_text(
+        thumbnail,
+        text_x,
+        strip_y + padding,
+        label,
+        scale,
+        LABEL_COLOR,
+    );
+}
+
+/// Copy the image into a bigger one at given coordinates.
+fn paste(dest: &mut RgbImage, image: &RgbImage, x: u32, y: u32) {
+    let dest_line_length = dest.line_length();
+    let offset = x as usize * RgbImage::CHANNELS;
+    for line in 0..image.height {
+        let dest_start = (y + line) as usize * dest_line_length + offset;
+        dest.data[dest_start..dest_start + image.line_length()]
+            .copy_from_slice(image.view().line(line));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+    use crate::wallpaper::properties::{
+        PropertiesAppearance, PropertiesH24, PropertiesSolar, SolarItem, TimeItem,
+    };
+
+    #[rstest]
+    fn test_schedule_frames_h24() {
+        let properties = Properties::H24(PropertiesH24 {
+            appearance: None,
+            time_info: vec![
+                TimeItem {
+                    index: 0,
+                    time: not_nan!(0.75),
+                },
+                TimeItem {
+                    index: 1,
+                    time: not_nan!(0.25),
+                },
+            ],
+        });
+
+        assert_eq!(
+            schedule_frames(&properties),
+            vec![
+                Frame {
+                    image_index: 1,
+                    label: "06:00".to_string(),
+                },
+                Frame {
+                    image_index: 0,
+                    label: "18:00".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_schedule_frames_solar() {
+        let properties = Properties::Solar(PropertiesSolar {
+            appearance: None,
+            solar_info: vec![
+                SolarItem {
+                    index: 0,
+                    altitude: not_nan!(-10.0),
+                    azimuth: not_nan!(270.34),
+                },
+                SolarItem {
+                    index: 1,
+                    altitude: not_nan!(45.0),
+                    azimuth: not_nan!(180.0),
+                },
+            ],
+        });
+
+        assert_eq!(
+            schedule_frames(&properties),
+            vec![
+                Frame {
+                    image_index: 1,
+                    label: "ALT 45.0° AZ 180.0°".to_string(),
+                },
+                Frame {
+                    image_index: 0,
+                    label: "ALT -10.0° AZ 270.3°".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_schedule_frames_appearance() {
+        let properties = Properties::Appearance(PropertiesAppearance { dark: 0, light: 1 });
+
+        assert_eq!(
+            schedule_frames(&properties),
+            vec![
+                Frame {
+                    image_index: 1,
+                    label: "LIGHT".to_string(),
+                },
+                Frame {
+                    image_index: 0,
+                    label: "DARK".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case(1, 1)]
+    #[case(4, 2)]
+    #[case(5, 3)]
+    #[case(16, 4)]
+    fn test_default_columns(#[case] count: usize, #[case] expected: u32) {
+        assert_eq!(default_columns(count), expected);
+    }
+
+    #[rstest]
+    #[case(3, 3, 1)]
+    #[case(2, 2, 2)]
+    #[case(10, 3, 1)]
+    fn test_contact_sheet_size(
+        #[case] columns: u32,
+        #[case] expected_columns: u32,
+        #[case] expected_rows: u32,
+    ) {
+        let thumbnails = vec![RgbImage::new(4, 2); 3];
+
+        let sheet = contact_sheet(&thumbnails, columns);
+
+        assert_eq!(sheet.width, expected_columns * (4 + SHEET_GAP) + SHEET_GAP);
+        assert_eq!(sheet.height, expected_rows * (2 + SHEET_GAP) + SHEET_GAP);
+    }
+
+    #[rstest]
+    fn test_contact_sheet_placement() {
+        let mut second = RgbImage::new(2, 2);
+        fill_rect(&mut second, 0, 0, 2, 2, |_| [255, 0, 0]);
+        let thumbnails = vec![RgbImage::new(2, 2), second];
+
+        let sheet = contact_sheet(&thumbnails, 2);
+
+        let view = sheet.view();
+        assert_eq!(view.pixel(0, 0), SHEET_BACKGROUND);
+        assert_eq!(view.pixel(SHEET_GAP, SHEET_GAP), [0, 0, 0]);
+        assert_eq!(view.pixel(2 * SHEET_GAP + 2, SHEET_GAP), [255, 0, 0]);
+        assert_eq!(view.pixel(2 * SHEET_GAP + 3, SHEET_GAP + 1), [255, 0, 0]);
+    }
+}
@@ -1,49 +1,157 @@
 pub mod metadata;
 pub mod properties;
 
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    thread::{self, JoinHandle},
+};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use libheif_rs::HeifContext;
 use log::debug;
 use metadata::AppleDesktop;
 use properties::Properties;
 
 use crate::heif;
+use crate::image::{resize::Fit, Encoding, ImageFormat};
 
 const PROPERTIES_NAME: &str = "properties.xml";
 
 /// Unpacked wallpaper laying somewhere in the filesystem.
 #[derive(Debug)]
 pub struct Wallpaper {
-    /// Paths of extracted images.
+    /// Paths of extracted images. Images of lazily loaded wallpaper may not exist yet.
     pub images: Vec<PathBuf>,
     /// Wallpaper properties.
     pub properties: Properties,
+    /// Directory the wallpaper was unpacked to.
+    dir_path: PathBuf,
+    /// Source of images which weren't extracted yet.
+    source: Option<ImageSource>,
+}
+
+/// HEIF file along with the settings needed to extract the missing wallpaper images from it.
+#[derive(Clone, Debug)]
+pub struct ImageSource {
+    pub wall_path: PathBuf,
+    pub fit: Option<Fit>,
+    pub encoding: Encoding,
 }
 
 impl Wallpaper {
-    /// Load wallpaper from a directory it was unpacked to.
-    pub fn load<P: AsRef<Path>>(dir_path: P) -> Result<Self> {
-        let dir_path = dir_path.as_ref();
+    /// Load wallpaper from a directory it was unpacked to, possibly only partially.
+    ///
+    /// Only the properties need to be present. Missing images are extracted from a given source
+    /// when they are first needed.
+    pub fn load<P: AsRef<Path>>(dir_path: P, source: Option<ImageSource>) -> Result<Self> {
+        let dir_path = dir_path.as_ref().canonicalize()?;
 
         let properties = Properties::from_xml_file(dir_path.join(PROPERTIES_NAME))?;
-        let mut images: Vec<PathBuf> = Vec::with_capacity(properties.num_images());
+        let expected_format = source
+            .as_ref()
+            .map_or_else(ImageFormat::default, |source| source.encoding.format);
+        let images = (0..properties.num_images())
+            .map(|i| {
+                ImageFormat::ALL
+                    .iter()
+                    .map(|format| dir_path.join(format!("{i}.{}", format.extension())))
+                    .find(|path| path.exists())
+                    .unwrap_or_else(|| {
+                        dir_path.join(format!("{i}.{}", expected_format.extension()))
+                    })
+            })
+            .collect();
 
-        for i in 0..properties.num_images() {
-            let image_path = dir_path.join(format!("{i}.png")).canonicalize()?;
-            if !image_path.exists() {
-                return Err(anyhow!("image {i} present in properties but not in dir"));
-            }
-            images.push(image_path);
+        Ok(Self {
+            images,
+            properties,
+            dir_path,
+            source,
+        })
+    }
+
+    /// Check whether the directory the wallpaper was unpacked to still exists.
+    pub fn is_available(&self) -> bool {
+        self.dir_path.is_dir()
+    }
+
+    /// Get indices of images which weren't extracted yet.
+    pub fn missing_images(&self) -> Vec<usize> {
+        self.images
+            .iter()
+            .enumerate()
+            .filter(|(_, path)| !path.exists())
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Get path to the image with a given index, extracting it first if it's missing.
+    pub fn image(&self, index: usize) -> Result<PathBuf> {
+        let image_path = self
+            .images
+            .get(index)
+            .with_context(|| format!("missing image {index} specified by metadata"))?;
+        if !image_path.exists() {
+            debug!("image {index} missing, extracting it");
+            unpack_images(self.source.as_ref(), &self.dir_path, &[index])?;
+        }
+        Ok(image_path.clone())
+    }
+
+    /// Extract all missing images.
+    pub fn unpack_missing(&self) -> Result<()> {
+        unpack_images(self.source.as_ref(), &self.dir_path, &self.missing_images())
+    }
+
+    /// Extract all missing images in a background thread. Returns `None` if nothing is missing.
+    pub fn unpack_missing_in_background(&self) -> Option<JoinHandle<()>> {
+        let missing_images = self.missing_images();
+        if missing_images.is_empty() {
+            return None;
         }
+        let source = self.source.clone();
+        let dir_path = self.dir_path.clone();
+        Some(thread::spawn(move || {
+            debug!("extracting images {missing_images:?} in background");
+            if let Err(err) = unpack_images(source.as_ref(), &dir_path, &missing_images) {
+                log::warn!("failed to extract images in background: {err:#}");
+            }
+        }))
+    }
+}
 
-        Ok(Self { images, properties })
+/// Extract images with given indices from a source into a directory.
+fn unpack_images(source: Option<&ImageSource>, dir_path: &Path, indices: &[usize]) -> Result<()> {
+    if indices.is_empty() {
+        return Ok(());
     }
+    let Some(source) = source else {
+        bail!("wallpaper images {indices:?} are missing");
+    };
+    let heif_ctx = heif::from_file(&source.wall_path)?;
+    for &index in indices {
+        heif::unpack_image(&heif_ctx, index, dir_path, source.fit, source.encoding)?;
+    }
+    Ok(())
+}
+
+/// Unpack only wallpaper properties from HEIF into a directory, leaving the images for later.
+pub fn unpack_properties_only<IP: AsRef<Path>, DP: AsRef<Path>>(
+    wall_path: IP,
+    dest_dir_path: DP,
+) -> Result<()> {
+    let heif_ctx = heif::from_file(wall_path)?;
+    unpack_properties(&heif_ctx, dest_dir_path.as_ref().join(PROPERTIES_NAME))
 }
 
 /// Unpack wallpaper images and properties from HEIF into a directory.
-pub fn unpack<IP: AsRef<Path>, DP: AsRef<Path>>(wall_path: IP, dest_dir_path: DP) -> Result<()> {
+/// If fit is given, images are resized to the target size. Images are written using given encoding.
+pub fn unpack<IP: AsRef<Path>, DP: AsRef<Path>>(
+    wall_path: IP,
+    dest_dir_path: DP,
+    fit: Option<Fit>,
+    encoding: Encoding,
+) -> Result<()> {
     let image_path = wall_path.as_ref();
     let dest_dir_path = dest_dir_path.as_ref();
     eprintln!("Unpacking '{}'...", image_path.display());
@@ -53,7 +161,7 @@ pub fn unpack<IP: AsRef<Path>, DP: AsRef<Path>>(wall_path: IP, dest_dir_path: DP
     }
 
     let heif_ctx = heif::from_file(wall_path)?;
-    heif::unpack_images(&heif_ctx, dest_dir_path)?;
+    heif::unpack_images(&heif_ctx, dest_dir_path, fit, encoding)?;
     unpack_properties(&heif_ctx, dest_dir_path.join(PROPERTIES_NAME))?;
 
     Ok(())
@@ -19,7 +19,7 @@ pub struct PropertiesH24 {
 }
 
 /// Wallpaper appearance depending on the theme.
-#[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug)]
 pub struct PropertiesAppearance {
     // Index of the image to use for a dark theme.
     #[serde(rename = "d")]
@@ -150,7 +150,7 @@ impl Properties {
             Self::Solar(props) => props.solar_info.iter().map(|item| item.index).max(),
             Self::Appearance(..) => Some(1),
         };
-        max_index.unwrap() + 1
+        max_index.map_or(0, |index| index + 1)
     }
 
     /// Get number of frames defined by those properties.
@@ -164,6 +164,15 @@ impl Properties {
         }
     }
 
+    /// Get human readable name of the schedule type.
+    pub const fn schedule_type(&self) -> &'static str {
+        match self {
+            Self::H24(..) => "time",
+            Self::Solar(..) => "solar",
+            Self::Appearance(..) => "appearance",
+        }
+    }
+
     /// Get appearance properties if present.
     pub const fn appearance(&self) -> Option<&PropertiesAppearance> {
         match self {
@@ -0,0 +1,179 @@
+#![allow(
+    clippy::missing_panics_doc,
+    clippy::must_use_candidate,
+    clippy::return_self_not_must_use,
+    clippy::new_without_default,
+    clippy::missing_const_for_fn,
+    clippy::too_long_first_doc_paragraph,
+    clippy::use_self
+)]
+
+mod common;
+
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use assert_fs::prelude::*;
+use common::{
+    cached_wallpaper_dir, testenv, TestEnv, CONFIG_WITH_LOCATION, EXAMPLE_SUN, EXAMPLE_TIME,
+    IMAGE_NIGHT, IMAGE_SET_MESSAGE, WALLPAPER_HASHES,
+};
+use predicates::prelude::*;
+use rstest::rstest;
+
+#[rstest]
+fn test_cache_list(testenv: TestEnv) {
+    let testenv = testenv.with_config(CONFIG_WITH_LOCATION);
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success();
+
+    testenv
+        .run(&["cache", "list"])
+        .success()
+        .stdout(predicate::str::contains(
+            *WALLPAPER_HASHES.get(&*EXAMPLE_TIME).unwrap(),
+        ))
+        .stdout(predicate::str::contains(
+            EXAMPLE_TIME.canonicalize().unwrap().to_str().unwrap(),
+        ))
+        .stdout(predicate::str::contains("Total: "));
+}
+
+#[rstest]
+fn test_cache_list_empty(testenv: TestEnv) {
+    testenv
+        .run(&["cache", "list"])
+        .success()
+        .stdout(predicate::str::contains("Cache is empty."));
+}
+
+#[rstest]
+fn test_cache_size_limit(testenv: TestEnv) {
+    let first_wall_cache_dir = cached_wallpaper_dir(&testenv.cache_dir, &EXAMPLE_SUN);
+    let second_wall_cache_dir = cached_wallpaper_dir(&testenv.cache_dir, &EXAMPLE_TIME);
+
+    let testenv = testenv.with_config(&format!("{CONFIG_WITH_LOCATION}\n[cache]\nmax_size_mb = 0"));
+    testenv
+        .run(&["set", EXAMPLE_SUN.to_str().unwrap()])
+        .success();
+    assert!(predicates::path::is_dir().eval(&first_wall_cache_dir));
+
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success();
+    assert!(predicates::path::missing().eval(&first_wall_cache_dir));
+    assert!(predicates::path::is_dir().eval(&second_wall_cache_dir));
+}
+
+#[rstest]
+fn test_set_repairs_broken_cache_entry(testenv: TestEnv) {
+    let cache_dir = cached_wallpaper_dir(&testenv.cache_dir, &EXAMPLE_TIME);
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success();
+    fs::write(cache_dir.join("properties.xml"), "garbage").unwrap();
+
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains(IMAGE_SET_MESSAGE));
+    assert!(predicate::str::contains("<plist")
+        .eval(&fs::read_to_string(cache_dir.join("properties.xml")).unwrap()));
+}
+
+#[rstest]
+fn test_cache_verify(testenv: TestEnv) {
+    let cache_dir = cached_wallpaper_dir(&testenv.cache_dir, &EXAMPLE_TIME);
+    let hash = *WALLPAPER_HASHES.get(&*EXAMPLE_TIME).unwrap();
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success();
+
+    testenv
+        .run(&["cache", "verify"])
+        .success()
+        .stdout(predicate::str::contains(format!("{hash}: ok")));
+
+    fs::remove_file(cache_dir.join(IMAGE_NIGHT)).unwrap();
+    let stale_file = cache_dir.join(format!(".{IMAGE_NIGHT}.{}-0.tmp", u32::MAX));
+    fs::write(&stale_file, "partial image").unwrap();
+
+    testenv
+        .run(&["cache", "verify"])
+        .success()
+        .stdout(predicate::str::contains(format!("{hash}: repaired")))
+        .stdout(predicate::str::contains("Removed 1 stale temporary files"));
+    assert!(predicates::path::is_file().eval(&cache_dir.join(IMAGE_NIGHT)));
+    assert!(predicates::path::missing().eval(&stale_file));
+}
+
+#[rstest]
+fn test_cache_verify_removes_unrepairable_entry(testenv: TestEnv) {
+    let wall_copy = testenv.cwd.child("wallpaper.heic");
+    fs::copy(&*EXAMPLE_TIME, &wall_copy).unwrap();
+    let cache_dir = cached_wallpaper_dir(&testenv.cache_dir, &EXAMPLE_TIME);
+    let hash = *WALLPAPER_HASHES.get(&*EXAMPLE_TIME).unwrap();
+    testenv.run(&["set", wall_copy.to_str().unwrap()]).success();
+    fs::remove_file(&wall_copy).unwrap();
+    fs::write(cache_dir.join("properties.xml"), "garbage").unwrap();
+
+    testenv
+        .run(&["cache", "verify"])
+        .success()
+        .stdout(predicate::str::contains(format!("{hash}: removed")));
+    assert!(predicates::path::missing().eval(&cache_dir));
+}
+
+#[rstest]
+fn test_set_updates_last_used_once_a_day(testenv: TestEnv) {
+    let manifest_path =
+        cached_wallpaper_dir(&testenv.cache_dir, &EXAMPLE_TIME).with_extension("json");
+    let read_last_used = || {
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&manifest_path).unwrap()).unwrap();
+        manifest["last_used"].as_u64().unwrap()
+    };
+    let write_last_used = |last_used: u64| {
+        let mut manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&manifest_path).unwrap()).unwrap();
+        manifest["last_used"] = last_used.into();
+        fs::write(&manifest_path, manifest.to_string()).unwrap();
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let testenv = testenv.with_config(CONFIG_WITH_LOCATION);
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success();
+
+    write_last_used(now - 60 * 60);
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success();
+    assert_eq!(read_last_used(), now - 60 * 60);
+
+    write_last_used(now - 2 * 24 * 60 * 60);
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success();
+    assert!(read_last_used() >= now);
+}
+
+#[rstest]
+fn test_wallpaper_hash_indexed(testenv: TestEnv) {
+    let hash = *WALLPAPER_HASHES.get(&*EXAMPLE_TIME).unwrap();
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success();
+
+    testenv
+        .cache_dir
+        .child("hash_index.json")
+        .assert(predicate::str::contains(hash))
+        .assert(predicate::str::contains(
+            EXAMPLE_TIME.canonicalize().unwrap().to_str().unwrap(),
+        ));
+}
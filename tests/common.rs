@@ -12,6 +12,7 @@
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use assert_cmd::{assert::Assert, cargo, Command};
@@ -131,6 +132,9 @@ pub struct TestEnv {
     pub cache_dir: ChildPath,
     pub runtime_dir: ChildPath,
     datetime: Option<DateTime<Local>>,
+    envs: Vec<(String, String)>,
+    dry_run: bool,
+    timeout: Option<Duration>,
 }
 
 impl TestEnv {
@@ -141,6 +145,9 @@ impl TestEnv {
             cache_dir: assert_fs::TempDir::new().unwrap().child("cache"),
             runtime_dir: assert_fs::TempDir::new().unwrap().child("runtime"),
             datetime: None,
+            envs: Vec::new(),
+            dry_run: true,
+            timeout: None,
         }
     }
 
@@ -159,19 +166,44 @@ impl TestEnv {
         self
     }
 
+    /// Set additional environment variable seen by the binary.
+    pub fn with_env(mut self, key: &str, value: &str) -> Self {
+        self.envs.push((key.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Actually run setter commands instead of printing them.
+    pub fn without_dry_run(mut self) -> Self {
+        self.dry_run = false;
+        self
+    }
+
+    /// Kill the binary after a given time, e.g. to stop the daemon.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     /// Run the command and return `Assert` object.
     pub fn run(&self, args: &[&str]) -> Assert {
         let mut command = Command::new(cargo::cargo_bin!());
         command
             .current_dir(&self.cwd)
-            .env("TIMEWALL_DRY_RUN", "true")
+            .env_remove("TIMEWALL_DRY_RUN")
             .env("TIMEWALL_CONFIG_DIR", self.config_dir.path())
             .env("TIMEWALL_CACHE_DIR", self.cache_dir.path())
             .env("TIMEWALL_RUNTIME_DIR", self.runtime_dir.path())
+            .envs(self.envs.iter().cloned())
             .args(args);
+        if self.dry_run {
+            command.env("TIMEWALL_DRY_RUN", "true");
+        }
         if let Some(datetime) = self.datetime {
             command.env("TIMEWALL_OVERRIDE_TIME", datetime.to_rfc3339());
         }
+        if let Some(timeout) = self.timeout {
+            command.timeout(timeout);
+        }
         command.assert()
     }
 }
@@ -0,0 +1,56 @@
+#![allow(
+    clippy::missing_panics_doc,
+    clippy::must_use_candidate,
+    clippy::return_self_not_must_use,
+    clippy::new_without_default,
+    clippy::missing_const_for_fn,
+    clippy::too_long_first_doc_paragraph,
+    clippy::use_self
+)]
+
+mod common;
+
+use common::{testenv, TestEnv, CONFIG_WITH_COMMAND};
+use predicates::prelude::*;
+use rstest::rstest;
+
+const CONFIG_WITH_BACKENDS: &str = r"
+[setter]
+backends = ['swaybg', 'builtin']
+";
+
+#[rstest]
+fn test_doctor_detects_desktop(testenv: TestEnv) {
+    testenv
+        .with_env("XDG_CURRENT_DESKTOP", "GNOME")
+        .run(&["doctor"])
+        .success()
+        .stdout(predicate::str::contains("XDG_CURRENT_DESKTOP: GNOME"))
+        .stdout(predicate::str::contains(
+            "gnome (GNOME-like desktop detected",
+        ))
+        .stdout(predicate::str::contains("builtin (generic fallback)"))
+        .stdout(predicate::str::contains("Using detected backends"));
+}
+
+#[rstest]
+fn test_doctor_configured_backends(testenv: TestEnv) {
+    testenv
+        .with_config(CONFIG_WITH_BACKENDS)
+        .run(&["doctor"])
+        .success()
+        .stdout(predicate::str::contains(
+            "Using configured backends, in order: swaybg, builtin",
+        ));
+}
+
+#[rstest]
+fn test_doctor_custom_command(testenv: TestEnv) {
+    testenv
+        .with_config(CONFIG_WITH_COMMAND)
+        .run(&["doctor"])
+        .success()
+        .stdout(predicate::str::contains(
+            "Using custom command: feh --bg-fill %f",
+        ));
+}
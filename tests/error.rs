@@ -10,7 +10,8 @@
 
 mod common;
 
-use common::{testenv, TestEnv, EXAMPLE_UNSUPPORTED};
+use assert_fs::prelude::*;
+use common::{testenv, TestEnv, EXAMPLE_TIME, EXAMPLE_UNSUPPORTED};
 use predicates::prelude::*;
 use rstest::rstest;
 
@@ -46,7 +47,6 @@ fn test_not_existing_path(testenv: TestEnv, #[case] args: &[&str]) {
 }
 
 #[rstest]
-#[case(&["info", EXAMPLE_DIR])]
 #[case(&["preview", EXAMPLE_DIR])]
 #[case(&["set", EXAMPLE_DIR])]
 #[case(&["unpack", EXAMPLE_DIR, "out"])]
@@ -58,3 +58,34 @@ fn test_dir_path(testenv: TestEnv, #[case] args: &[&str]) {
         .failure()
         .stderr(predicate::str::contains(expected_message));
 }
+
+#[rstest]
+#[case(&["set", EXAMPLE_TIME.to_str().unwrap()])]
+#[case(&["cache", "list"])]
+#[case(&["clear"])]
+fn test_unusable_cache_dir(testenv: TestEnv, #[case] args: &[&str]) {
+    let cache_file = testenv.cwd.child("cache");
+    cache_file.touch().unwrap();
+    let testenv = testenv.with_env("TIMEWALL_CACHE_DIR", cache_file.to_str().unwrap());
+
+    testenv
+        .run(args)
+        .failure()
+        .stderr(predicate::str::contains(
+            "Error: couldn't create cache directory",
+        ))
+        .stderr(predicate::str::contains("panicked").not());
+}
+
+#[rstest]
+fn test_invalid_override_time(testenv: TestEnv) {
+    let testenv = testenv.with_env("TIMEWALL_OVERRIDE_TIME", "yesterday");
+
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .failure()
+        .stderr(predicate::str::contains(
+            "Error: invalid TIMEWALL_OVERRIDE_TIME 'yesterday'",
+        ))
+        .stderr(predicate::str::contains("panicked").not());
+}
@@ -12,7 +12,7 @@ mod common;
 
 use std::path::PathBuf;
 
-use common::{testenv, TestEnv, EXAMPLE_SUN, EXAMPLE_TIME};
+use common::{testenv, TestEnv, EXAMPLE_SUN, EXAMPLE_TIME, EXAMPLE_UNSUPPORTED};
 use predicates::prelude::*;
 use rstest::rstest;
 
@@ -55,3 +55,140 @@ fn test_info(testenv: TestEnv, #[case] wall_path: PathBuf, #[case] expected_outp
         .success()
         .stdout(predicate::str::ends_with(expected_output));
 }
+
+#[rstest]
+fn test_info_json(testenv: TestEnv) {
+    let output = testenv
+        .run(&["info", "--format", "json", EXAMPLE_TIME.to_str().unwrap()])
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let info: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+    assert_eq!(info["schedule_type"], "time");
+    assert_eq!(info["size"], 88723);
+    assert_eq!(info["width"], 512);
+    assert_eq!(info["frames"], 2);
+    assert_eq!(
+        info["schedule"],
+        serde_json::json!([
+            {"frame": 1, "image": 1, "day_fraction": 0.0, "time": "00:00:00"},
+            {"frame": 2, "image": 0, "day_fraction": 0.5, "time": "12:00:00"},
+        ])
+    );
+    assert_eq!(
+        info["appearance"],
+        serde_json::json!({"light": 0, "dark": 1})
+    );
+}
+
+#[rstest]
+fn test_info_toml(testenv: TestEnv) {
+    let output = testenv
+        .run(&["info", "--format", "toml", EXAMPLE_SUN.to_str().unwrap()])
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let info: toml::Table = toml::from_str(&String::from_utf8(output).unwrap()).unwrap();
+
+    assert_eq!(info["schedule_type"].as_str(), Some("solar"));
+    assert_eq!(info["images"].as_integer(), Some(2));
+    let schedule = info["schedule"].as_array().unwrap();
+    assert_eq!(schedule[0]["image"].as_integer(), Some(0));
+    assert_eq!(schedule[0]["azimuth"].as_float(), Some(169.0));
+    assert_eq!(schedule[1]["altitude"].as_float(), Some(-45.0));
+}
+
+#[rstest]
+fn test_info_table_dir(testenv: TestEnv) {
+    let examples_dir = EXAMPLE_TIME.parent().unwrap();
+
+    testenv
+        .run(&["info", "--format", "table", examples_dir.to_str().unwrap()])
+        .success()
+        .stdout(
+            predicate::str::is_match(r"(?m)^sun\.heic +solar +512x512 +2 +yes +0\.1 MB$").unwrap(),
+        )
+        .stdout(
+            predicate::str::is_match(r"(?m)^time\.heic +time +512x512 +2 +yes +0\.1 MB$").unwrap(),
+        )
+        .stdout(predicate::str::contains("unsupported").not());
+}
+
+#[rstest]
+fn test_info_multiple_files(testenv: TestEnv) {
+    let output = testenv
+        .run(&[
+            "info",
+            "--format",
+            "json",
+            EXAMPLE_TIME.to_str().unwrap(),
+            EXAMPLE_SUN.to_str().unwrap(),
+        ])
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let infos: Vec<serde_json::Value> = serde_json::from_slice(&output).unwrap();
+
+    assert_eq!(infos.len(), 2);
+    assert_eq!(infos[0]["schedule_type"], "time");
+    assert_eq!(infos[1]["schedule_type"], "solar");
+}
+
+#[rstest]
+fn test_info_multiple_files_unsupported(testenv: TestEnv) {
+    testenv
+        .run(&[
+            "info",
+            EXAMPLE_TIME.to_str().unwrap(),
+            EXAMPLE_UNSUPPORTED.to_str().unwrap(),
+        ])
+        .failure()
+        .stderr(predicate::str::contains("only HEIF files are supported"));
+}
+
+#[rstest]
+#[case::current(&[])]
+#[case::selected(&["--frame", "2"])]
+fn test_info_show(testenv: TestEnv, #[case] extra_args: &[&str]) {
+    let mut args = vec!["info", "--show"];
+    args.extend(extra_args);
+    args.push(EXAMPLE_TIME.to_str().unwrap());
+
+    testenv
+        .with_env("TERM", "xterm-kitty")
+        .run(&args)
+        .success()
+        .stdout(predicate::str::contains("Schedule type: time"))
+        .stdout(predicate::str::contains("\x1b_Ga=T,f=100").count(1));
+}
+
+#[rstest]
+fn test_info_show_no_frame(testenv: TestEnv) {
+    testenv
+        .run(&[
+            "info",
+            "--show",
+            "--frame",
+            "3",
+            EXAMPLE_TIME.to_str().unwrap(),
+        ])
+        .failure()
+        .stderr(predicate::str::contains("there is no frame 3"));
+}
+
+#[rstest]
+fn test_info_show_multiple_files(testenv: TestEnv) {
+    testenv
+        .run(&[
+            "info",
+            "--show",
+            EXAMPLE_TIME.to_str().unwrap(),
+            EXAMPLE_SUN.to_str().unwrap(),
+        ])
+        .failure()
+        .stderr(predicate::str::contains("only a single wallpaper file"));
+}
@@ -0,0 +1,58 @@
+#![allow(
+    clippy::missing_panics_doc,
+    clippy::must_use_candidate,
+    clippy::return_self_not_must_use,
+    clippy::new_without_default,
+    clippy::missing_const_for_fn,
+    clippy::too_long_first_doc_paragraph,
+    clippy::use_self
+)]
+
+mod common;
+
+use common::{testenv, TestEnv, EXAMPLE_SUN, EXAMPLE_TIME, EXAMPLE_UNSUPPORTED};
+use predicates::prelude::*;
+use rstest::rstest;
+
+#[rstest]
+fn test_lint_ok(testenv: TestEnv) {
+    testenv
+        .run(&[
+            "lint",
+            EXAMPLE_TIME.to_str().unwrap(),
+            EXAMPLE_SUN.to_str().unwrap(),
+        ])
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "{}: ok",
+            EXAMPLE_TIME.display()
+        )))
+        .stdout(predicate::str::contains(format!(
+            "{}: ok",
+            EXAMPLE_SUN.display()
+        )))
+        .stdout(predicate::str::contains(
+            "Checked 2 wallpapers: 0 errors, 0 warnings",
+        ));
+}
+
+#[rstest]
+fn test_lint_dir(testenv: TestEnv) {
+    let examples_dir = EXAMPLE_TIME.parent().unwrap();
+
+    testenv
+        .run(&["lint", examples_dir.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains(
+            "Checked 2 wallpapers: 0 errors, 0 warnings",
+        ))
+        .stdout(predicate::str::contains("unsupported").not());
+}
+
+#[rstest]
+fn test_lint_unsupported(testenv: TestEnv) {
+    testenv
+        .run(&["lint", EXAMPLE_UNSUPPORTED.to_str().unwrap()])
+        .failure()
+        .stderr(predicate::str::contains("only HEIF files are supported"));
+}
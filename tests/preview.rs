@@ -9,10 +9,12 @@
 )]
 
 mod common;
-use std::path::PathBuf;
+use std::{fs::File, io::BufReader, path::PathBuf};
 
+use assert_fs::prelude::*;
 use common::{
-    testenv, TestEnv, EXAMPLE_SUN, EXAMPLE_TIME, IMAGE_DAY, IMAGE_NIGHT, IMAGE_SET_MESSAGE,
+    testenv, TestEnv, CONFIG_WITH_LOCATION, EXAMPLE_SUN, EXAMPLE_TIME, IMAGE_DAY, IMAGE_NIGHT,
+    IMAGE_SET_MESSAGE,
 };
 use predicates::prelude::*;
 use rstest::rstest;
@@ -28,3 +30,182 @@ fn test_preview(testenv: TestEnv, #[case] wall_path: PathBuf) {
         .stdout(predicate::str::contains(IMAGE_DAY).count(1))
         .stdout(predicate::str::contains(IMAGE_NIGHT).count(1));
 }
+
+#[rstest]
+fn test_preview_restores_previous_wallpaper(testenv: TestEnv) {
+    let previous_wallpaper = "/usr/share/backgrounds/default.png";
+
+    testenv
+        .with_env("TIMEWALL_DRY_RUN_CURRENT_WALLPAPER", previous_wallpaper)
+        .run(&["preview", EXAMPLE_TIME.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains(IMAGE_SET_MESSAGE).count(2))
+        .stdout(predicate::str::ends_with(format!(
+            "Restore: {previous_wallpaper}\n"
+        )));
+}
+
+#[rstest]
+#[case::once(&[], 4, 1)]
+#[case::repeat(&["--repeat"], 6, 0)]
+fn test_preview_output_apng(
+    testenv: TestEnv,
+    #[case] extra_args: &[&str],
+    #[case] expected_frames: u32,
+    #[case] expected_plays: u32,
+) {
+    let output = testenv.cwd.child("day.png");
+
+    let mut args = vec!["preview", "--crossfade", "2", "--width", "32", "--output"];
+    args.push(output.to_str().unwrap());
+    args.extend(extra_args);
+    args.push(EXAMPLE_TIME.to_str().unwrap());
+    testenv
+        .run(&args)
+        .success()
+        .stdout(predicate::str::contains(IMAGE_SET_MESSAGE).not());
+
+    let decoder = png::Decoder::new(BufReader::new(File::open(output.path()).unwrap()));
+    let reader = decoder.read_info().unwrap();
+    let info = reader.info();
+    assert_eq!((info.width, info.height), (32, 32));
+    let animation_control = info.animation_control.unwrap();
+    assert_eq!(animation_control.num_frames, expected_frames);
+    assert_eq!(animation_control.num_plays, expected_plays);
+}
+
+#[rstest]
+fn test_preview_output_frames_dir(testenv: TestEnv) {
+    let testenv = testenv.with_config(CONFIG_WITH_LOCATION);
+    let output = testenv.cwd.child("frames");
+
+    testenv
+        .run(&[
+            "preview",
+            "--date",
+            "2024-06-21",
+            "--output",
+            output.to_str().unwrap(),
+            EXAMPLE_SUN.to_str().unwrap(),
+        ])
+        .success()
+        .stdout(predicate::str::contains(IMAGE_SET_MESSAGE).not());
+
+    output
+        .child("frame_0001.png")
+        .assert(predicate::path::is_file());
+    output
+        .child("frame_0002.png")
+        .assert(predicate::path::is_file());
+    output
+        .child("frame_0003.png")
+        .assert(predicate::path::missing());
+}
+
+#[rstest]
+fn test_preview_terminal(testenv: TestEnv) {
+    testenv
+        .with_env("TERM", "xterm-kitty")
+        .run(&[
+            "preview",
+            "--terminal",
+            "--delay",
+            "0",
+            EXAMPLE_TIME.to_str().unwrap(),
+        ])
+        .success()
+        .stdout(predicate::str::contains("\x1b_Ga=T,f=100").count(2))
+        .stdout(predicate::str::contains(IMAGE_SET_MESSAGE).not());
+}
+
+#[rstest]
+#[case::time(EXAMPLE_TIME.to_path_buf())]
+#[case::sun(EXAMPLE_SUN.to_path_buf())]
+fn test_preview_realistic(testenv: TestEnv, #[case] wall_path: PathBuf) {
+    // Images change only when the schedule says so, the night is set again in the evening.
+    testenv
+        .with_config(CONFIG_WITH_LOCATION)
+        .run(&[
+            "preview",
+            "--realistic",
+            "--speed",
+            "864000x",
+            "--date",
+            "2024-06-21",
+            wall_path.to_str().unwrap(),
+        ])
+        .success()
+        .stdout(predicate::str::contains(IMAGE_SET_MESSAGE).count(3))
+        .stdout(predicate::str::contains(IMAGE_DAY).count(1))
+        .stdout(predicate::str::contains(IMAGE_NIGHT).count(2));
+}
+
+#[rstest]
+fn test_preview_realistic_invalid_speed(testenv: TestEnv) {
+    testenv
+        .run(&[
+            "preview",
+            "--realistic",
+            "--speed",
+            "0x",
+            EXAMPLE_TIME.to_str().unwrap(),
+        ])
+        .failure()
+        .stderr(predicate::str::contains("speed must be positive"));
+}
+
+#[rstest]
+#[case::frames(&["--frames", "2"], 1, 0)]
+#[case::time_range(&["--from", "13:00", "--to", "13:30"], 1, 0)]
+#[case::over_midnight(&["--from", "2", "--to", "1"], 1, 1)]
+#[case::reverse_bounce(&["--reverse", "--bounce"], 2, 1)]
+fn test_preview_selected_frames(
+    testenv: TestEnv,
+    #[case] extra_args: &[&str],
+    #[case] expected_day: usize,
+    #[case] expected_night: usize,
+) {
+    let mut args = vec!["preview", "--delay", "0"];
+    args.extend(extra_args);
+    args.push(EXAMPLE_TIME.to_str().unwrap());
+    testenv
+        .run(&args)
+        .success()
+        .stdout(predicate::str::contains(IMAGE_SET_MESSAGE).count(expected_day + expected_night))
+        .stdout(predicate::str::contains(IMAGE_DAY).count(expected_day))
+        .stdout(predicate::str::contains(IMAGE_NIGHT).count(expected_night));
+}
+
+#[rstest]
+fn test_preview_nonexistent_frame(testenv: TestEnv) {
+    testenv
+        .run(&["preview", "--frames", "1,3", EXAMPLE_TIME.to_str().unwrap()])
+        .failure()
+        .stderr(predicate::str::contains(
+            "there is no frame 3 in the wallpaper",
+        ));
+}
+
+#[rstest]
+fn test_preview_realistic_time_offset(testenv: TestEnv) {
+    // Running 12 hours behind, the day image is displayed first, from midnight until noon.
+    let output = testenv
+        .with_config("[schedule]\ntime_offset = '-12h'")
+        .run(&[
+            "preview",
+            "--realistic",
+            "--speed",
+            "864000x",
+            "--date",
+            "2024-06-21",
+            EXAMPLE_TIME.to_str().unwrap(),
+        ])
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let output = String::from_utf8(output).unwrap();
+    let first_set = output.lines().next().unwrap();
+    assert!(first_set.ends_with(IMAGE_DAY));
+}
@@ -11,6 +11,8 @@
 mod common;
 
 use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
 
 use assert_fs::prelude::*;
 use chrono::{DateTime, Local};
@@ -61,6 +63,86 @@ fn test_sets_correct_image_solar(
         .stdout(predicate::str::contains(expected_image_path_str));
 }
 
+#[rstest]
+#[case("2022-10-18 14:30", IMAGE_DAY)]
+#[case("2022-10-18T22:30:00+00:00", IMAGE_NIGHT)]
+fn test_sets_image_at_given_time(testenv: TestEnv, #[case] at: &str, #[case] expected_image: &str) {
+    let expected_image_path_str =
+        cached_image_path_str(&testenv.cache_dir, &EXAMPLE_TIME, expected_image);
+
+    // The given time takes precedence over the current one.
+    testenv
+        .with_time(*DATETIME_NIGHT)
+        .run(&["set", "--at", at, EXAMPLE_TIME.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains(IMAGE_SET_MESSAGE).count(1))
+        .stdout(predicate::str::contains(expected_image_path_str));
+}
+
+#[rstest]
+fn test_at_conflicts_with_daemon(testenv: TestEnv) {
+    testenv
+        .run(&[
+            "set",
+            "--daemon",
+            "--at",
+            "12:00",
+            EXAMPLE_TIME.to_str().unwrap(),
+        ])
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[rstest]
+#[case::by_path(EXAMPLE_TIME.to_str().unwrap())]
+#[case::by_hash(WALLPAPER_HASHES.get(&*EXAMPLE_TIME).unwrap())]
+fn test_applies_schedule_override(testenv: TestEnv, #[case] key: &str) {
+    let expected_image_path_str =
+        cached_image_path_str(&testenv.cache_dir, &EXAMPLE_TIME, IMAGE_NIGHT);
+    // Day image is displayed from midnight and night image from noon.
+    let config = format!("[wallpaper.'{key}']\nshift = '-12h'");
+
+    testenv
+        .with_config(&config)
+        .with_time(*DATETIME_DAY)
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains(expected_image_path_str));
+}
+
+#[rstest]
+fn test_invalid_schedule_override(testenv: TestEnv) {
+    let config = format!(
+        "[wallpaper.'{}']\nremap = [{{ frame = 1, image = 5 }}]",
+        EXAMPLE_TIME.to_str().unwrap()
+    );
+
+    testenv
+        .with_config(&config)
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .failure()
+        .stderr(predicate::str::contains("invalid schedule override"));
+}
+
+#[rstest]
+#[case::behind("-12h", IMAGE_NIGHT)]
+#[case::none("0", IMAGE_DAY)]
+fn test_applies_time_offset(
+    testenv: TestEnv,
+    #[case] time_offset: &str,
+    #[case] expected_image: &str,
+) {
+    let expected_image_path_str =
+        cached_image_path_str(&testenv.cache_dir, &EXAMPLE_TIME, expected_image);
+
+    testenv
+        .with_config(&format!("[schedule]\ntime_offset = '{time_offset}'"))
+        .with_time(*DATETIME_DAY)
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains(expected_image_path_str));
+}
+
 #[rstest]
 #[case("light", IMAGE_DAY)]
 #[case("dark", IMAGE_NIGHT)]
@@ -103,6 +185,245 @@ fn test_runs_command(testenv: TestEnv) {
         .stdout(predicate::str::contains(expected_command_str));
 }
 
+#[rstest]
+fn test_expands_command_placeholders(testenv: TestEnv) {
+    let wall_path = EXAMPLE_TIME.to_path_buf();
+    let config = r"
+        [setter]
+        command = ['notify', '%i', '%n/%N', '%s', '%w', '100%%']
+    ";
+    let expected_command_str = format!("notify 1 1/2 time {} 100%", wall_path.display());
+
+    testenv
+        .with_config(config)
+        .with_time(*DATETIME_NIGHT)
+        .run(&["set", wall_path.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains(expected_command_str));
+}
+
+#[rstest]
+fn test_runs_multiple_commands(testenv: TestEnv) {
+    let wall_path = EXAMPLE_TIME.to_path_buf();
+    let expected_image_path_str = cached_image_path_str(&testenv.cache_dir, &wall_path, IMAGE_DAY);
+    let config = r"
+        [[setter]]
+        command = ['feh', '--bg-fill', '%f']
+
+        [[setter]]
+        command = ['cp', '%f', 'lockscreen.png']
+
+        [[setter]]
+        command = ['cp', '%f', 'dark.png']
+        appearance = 'dark'
+
+        [[setter]]
+        command = ['cp', '%f', 'missing-output.png']
+        output = 'NOT-CONNECTED-1'
+    ";
+
+    testenv
+        .with_config(config)
+        .with_time(*DATETIME_DAY)
+        .run(&["set", "--appearance", "light", wall_path.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains(COMMAND_RUN_MESSAGE).count(2))
+        .stdout(predicate::str::contains(format!(
+            "feh --bg-fill {expected_image_path_str}"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "cp {expected_image_path_str} lockscreen.png"
+        )));
+}
+
+#[rstest]
+fn test_daemon_keeps_unmarked_long_running_command(testenv: TestEnv) {
+    let wall_path = EXAMPLE_TIME.to_path_buf();
+    // The command isn't marked as long-running, so it must not be killed after the timeout.
+    let config = r"
+        [setter]
+        command = ['sleep', '30']
+        timeout = 100
+
+        [restore]
+        enable = false
+    ";
+    let testenv = testenv.with_config(config).with_time(*DATETIME_DAY);
+    // Fill the cache first, so the daemon sets the wallpaper right away.
+    testenv.run(&["set", wall_path.to_str().unwrap()]).success();
+    let testenv = testenv
+        .without_dry_run()
+        .with_timeout(Duration::from_secs(5));
+
+    testenv
+        .run(&["set", "--daemon", wall_path.to_str().unwrap()])
+        .stderr(predicate::str::contains("did not finish").not())
+        .stderr(predicate::str::contains("failed to set wallpaper").not());
+
+    let pidfile = testenv.runtime_dir.child("setter_0.pid");
+    pidfile.assert(predicate::path::exists());
+    let pid = std::fs::read_to_string(pidfile.path()).unwrap();
+    let is_running = PathBuf::from("/proc").join(pid.trim()).exists();
+    Command::new("kill").arg(pid.trim()).status().unwrap();
+    assert!(is_running);
+}
+
+#[rstest]
+fn test_set_fails_for_unmarked_failing_command(testenv: TestEnv) {
+    // The command fails only after the overlap, which must not be mistaken for a success.
+    let config = r"
+        [setter]
+        command = ['sh', '-c', 'sleep 0.2; exit 1']
+
+        [restore]
+        enable = false
+    ";
+    let testenv = testenv
+        .with_config(config)
+        .with_time(*DATETIME_DAY)
+        .without_dry_run();
+
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .failure()
+        .stderr(predicate::str::contains("exited with exit status: 1"))
+        .stderr(predicate::str::contains("Wallpaper set!").not());
+    testenv
+        .runtime_dir
+        .child("setter_0.pid")
+        .assert(predicate::path::missing());
+}
+
+#[rstest]
+fn test_runs_hooks(testenv: TestEnv) {
+    let config = format!(
+        "{CONFIG_WITH_LOCATION}{}",
+        r"
+        [hooks]
+        on_change = ['notify', 'frame %n']
+        on_wallpaper_switch = ['notify', 'switched to %w']
+        "
+    );
+
+    let testenv = testenv.with_config(&config).with_time(*DATETIME_NIGHT);
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains("Hook on_change: notify frame 1"))
+        .stdout(predicate::str::contains("Hook on_wallpaper_switch").not());
+    testenv
+        .run(&["set", EXAMPLE_SUN.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Hook on_wallpaper_switch: notify switched to {}",
+            EXAMPLE_SUN.display()
+        )));
+}
+
+#[rstest]
+fn test_resizes_cached_images(testenv: TestEnv) {
+    let config = r"
+        [cache]
+        resize = '32x18'
+        resize_mode = 'cover'
+    ";
+    let hash = WALLPAPER_HASHES.get(&*EXAMPLE_TIME).unwrap();
+    let expected_image_path = testenv
+        .cache_dir
+        .child("wallpapers")
+        .child(format!("{hash}-32x18-cover"))
+        .child(IMAGE_NIGHT);
+
+    testenv
+        .with_config(config)
+        .with_time(*DATETIME_NIGHT)
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains(
+            expected_image_path.to_str().unwrap(),
+        ));
+
+    let decoder = png::Decoder::new(std::io::BufReader::new(
+        std::fs::File::open(&expected_image_path).unwrap(),
+    ));
+    let info = decoder.read_info().unwrap().info().clone();
+    assert_eq!((info.width, info.height), (32, 18));
+}
+
+#[rstest]
+fn test_caches_images_in_configured_format(testenv: TestEnv) {
+    let config = r"
+        [cache]
+        format = 'jpeg'
+        quality = 80
+    ";
+    let hash = WALLPAPER_HASHES.get(&*EXAMPLE_TIME).unwrap();
+    let expected_image_path = testenv
+        .cache_dir
+        .child("wallpapers")
+        .child(format!("{hash}-jpeg80"))
+        .child("1.jpg");
+
+    testenv
+        .with_config(config)
+        .with_time(*DATETIME_NIGHT)
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains(
+            expected_image_path.to_str().unwrap(),
+        ));
+
+    let image_data = std::fs::read(&expected_image_path).unwrap();
+    assert_eq!(&image_data[..2], &[0xff, 0xd8]);
+}
+
+#[rstest]
+fn test_unpacks_only_needed_image_in_lazy_mode(testenv: TestEnv) {
+    let config = r"
+        [cache]
+        lazy = true
+    ";
+    let hash = WALLPAPER_HASHES.get(&*EXAMPLE_TIME).unwrap();
+    let cache_entry = testenv.cache_dir.child("wallpapers").child(hash);
+
+    testenv
+        .with_config(config)
+        .with_time(*DATETIME_NIGHT)
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success()
+        .stdout(predicate::str::contains(
+            cache_entry.child(IMAGE_NIGHT).to_str().unwrap(),
+        ));
+
+    cache_entry
+        .child(IMAGE_NIGHT)
+        .assert(predicate::path::is_file());
+    cache_entry
+        .child(IMAGE_DAY)
+        .assert(predicate::path::missing());
+}
+
+#[rstest]
+fn test_fills_lazily_unpacked_cache(testenv: TestEnv) {
+    let hash = WALLPAPER_HASHES.get(&*EXAMPLE_TIME).unwrap();
+    let cache_entry = testenv.cache_dir.child("wallpapers").child(hash);
+    let testenv = testenv
+        .with_config("[cache]\nlazy = true")
+        .with_time(*DATETIME_NIGHT);
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success();
+
+    testenv
+        .with_config("[cache]\nlazy = false")
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success();
+
+    cache_entry
+        .child(IMAGE_DAY)
+        .assert(predicate::path::is_file());
+}
+
 #[rstest]
 fn test_creates_config(testenv: TestEnv) {
     let config_path = testenv.config_dir.child("config.toml");
@@ -0,0 +1,80 @@
+#![allow(
+    clippy::missing_panics_doc,
+    clippy::must_use_candidate,
+    clippy::return_self_not_must_use,
+    clippy::new_without_default,
+    clippy::missing_const_for_fn,
+    clippy::too_long_first_doc_paragraph,
+    clippy::use_self
+)]
+
+mod common;
+
+use std::{fs::File, io::BufReader, path::Path};
+
+use assert_fs::prelude::*;
+use common::{testenv, TestEnv, EXAMPLE_SUN, EXAMPLE_TIME, EXAMPLE_UNSUPPORTED};
+use predicates::prelude::*;
+use rstest::rstest;
+
+fn png_size(path: &Path) -> (u32, u32) {
+    let decoder = png::Decoder::new(BufReader::new(File::open(path).unwrap()));
+    let reader = decoder.read_info().unwrap();
+    (reader.info().width, reader.info().height)
+}
+
+#[rstest]
+#[case::default_columns(&[], (224, 116))]
+#[case::single_column(&["--columns", "1"], (116, 224))]
+fn test_thumbnails_contact_sheet(
+    testenv: TestEnv,
+    #[case] extra_args: &[&str],
+    #[case] expected_size: (u32, u32),
+) {
+    let sheet = testenv.cwd.child("sheet.png");
+
+    let mut args = vec!["thumbnails", "--width", "100"];
+    args.extend(extra_args);
+    args.extend([EXAMPLE_SUN.to_str().unwrap(), sheet.to_str().unwrap()]);
+    testenv.run(&args).success();
+
+    sheet.assert(predicate::path::is_file());
+    assert_eq!(png_size(sheet.path()), expected_size);
+}
+
+#[rstest]
+fn test_thumbnails_separate(testenv: TestEnv) {
+    let thumbnails_dir = testenv.cwd.child("thumbnails");
+
+    testenv
+        .run(&[
+            "thumbnails",
+            "--separate",
+            "--width",
+            "64",
+            EXAMPLE_TIME.to_str().unwrap(),
+            thumbnails_dir.to_str().unwrap(),
+        ])
+        .success();
+
+    for frame in ["frame_1.png", "frame_2.png"] {
+        let thumbnail = thumbnails_dir.child(frame);
+        thumbnail.assert(predicate::path::is_file());
+        assert_eq!(png_size(thumbnail.path()), (64, 64));
+    }
+    thumbnails_dir
+        .child("frame_3.png")
+        .assert(predicate::path::missing());
+}
+
+#[rstest]
+fn test_thumbnails_unsupported(testenv: TestEnv) {
+    testenv
+        .run(&[
+            "thumbnails",
+            EXAMPLE_UNSUPPORTED.to_str().unwrap(),
+            testenv.cwd.child("sheet.png").to_str().unwrap(),
+        ])
+        .failure()
+        .stderr(predicate::str::contains("only HEIF files are supported"));
+}
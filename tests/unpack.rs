@@ -45,3 +45,51 @@ fn test_unpack(testenv: TestEnv, #[case] wall_path: PathBuf, #[case] expected_pr
         .child("properties.xml")
         .assert(predicate::path::eq_file(expected_properties));
 }
+
+#[rstest]
+#[case("jpeg", "jpg")]
+#[case("webp", "webp")]
+#[case("ppm", "ppm")]
+fn test_unpack_format(testenv: TestEnv, #[case] format: &str, #[case] extension: &str) {
+    let unpack_dir = testenv.cwd.child("unpacked");
+    unpack_dir.create_dir_all().unwrap();
+
+    testenv
+        .run(&[
+            "unpack",
+            "--format",
+            format,
+            EXAMPLE_TIME.to_str().unwrap(),
+            unpack_dir.to_str().unwrap(),
+        ])
+        .success();
+
+    for index in 0..2 {
+        unpack_dir
+            .child(format!("{index}.{extension}"))
+            .assert(predicate::path::is_file());
+    }
+    unpack_dir
+        .child(IMAGE_DAY)
+        .assert(predicate::path::missing());
+}
+
+#[rstest]
+fn test_unpack_write_failure(testenv: TestEnv) {
+    let unpack_dir = testenv.cwd.child("unpacked");
+    // A directory in place of the image makes writing it fail.
+    unpack_dir.child(IMAGE_DAY).create_dir_all().unwrap();
+
+    testenv
+        .run(&[
+            "unpack",
+            EXAMPLE_TIME.to_str().unwrap(),
+            unpack_dir.to_str().unwrap(),
+        ])
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "couldn't write {}",
+            unpack_dir.child(IMAGE_DAY).display()
+        )))
+        .stderr(predicate::str::contains("panicked").not());
+}
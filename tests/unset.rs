@@ -0,0 +1,55 @@
+#![allow(
+    clippy::missing_panics_doc,
+    clippy::must_use_candidate,
+    clippy::return_self_not_must_use,
+    clippy::new_without_default,
+    clippy::missing_const_for_fn,
+    clippy::too_long_first_doc_paragraph,
+    clippy::use_self
+)]
+
+mod common;
+
+use common::{testenv, TestEnv, DATETIME_NIGHT, EXAMPLE_TIME};
+use predicates::prelude::*;
+use rstest::rstest;
+
+const PREVIOUS_WALLPAPER: &str = "/usr/share/backgrounds/default.png";
+
+#[rstest]
+fn test_restores_previous_wallpaper(testenv: TestEnv) {
+    let testenv = testenv
+        .with_time(*DATETIME_NIGHT)
+        .with_env("TIMEWALL_DRY_RUN_CURRENT_WALLPAPER", PREVIOUS_WALLPAPER);
+
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success();
+    testenv
+        .run(&["unset"])
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Restore: {PREVIOUS_WALLPAPER}"
+        )))
+        .stderr(predicate::str::contains("Wallpaper unset!"));
+    testenv
+        .run(&["unset"])
+        .success()
+        .stdout(predicate::str::contains("Restore:").not());
+}
+
+#[rstest]
+fn test_restore_disabled(testenv: TestEnv) {
+    let testenv = testenv
+        .with_config("[restore]\nenable = false\n")
+        .with_time(*DATETIME_NIGHT)
+        .with_env("TIMEWALL_DRY_RUN_CURRENT_WALLPAPER", PREVIOUS_WALLPAPER);
+
+    testenv
+        .run(&["set", EXAMPLE_TIME.to_str().unwrap()])
+        .success();
+    testenv
+        .run(&["unset"])
+        .success()
+        .stdout(predicate::str::contains("Restore:").not());
+}